@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS topic;
+DROP TABLE IF EXISTS tutor;
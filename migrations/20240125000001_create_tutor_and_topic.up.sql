@@ -0,0 +1,25 @@
+CREATE TABLE tutor (
+    id serial primary key,
+    first_name varchar(200) not null,
+    last_name varchar(200) not null,
+    email varchar(200) not null
+);
+
+CREATE TABLE topic
+(
+    id serial primary key,
+    tutor_id INT not null,
+    title varchar(140) not null,
+    topic_description varchar(2000),
+    format varchar(30),
+    duration varchar(30),
+    topic_level varchar(30),
+    created_at TIMESTAMP default now(),
+    updated_at TIMESTAMP default now(),
+    CONSTRAINT fk_tutor
+    FOREIGN KEY(tutor_id)
+        REFERENCES tutor(id)
+        ON DELETE cascade
+);
+
+CREATE INDEX idx_topic_tutor_id ON topic(tutor_id);
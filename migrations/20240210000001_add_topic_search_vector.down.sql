@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_topic_search_vector;
+ALTER TABLE topic DROP COLUMN IF EXISTS search_vector;
+DROP FUNCTION IF EXISTS topic_search_vector(varchar, varchar);
@@ -0,0 +1,14 @@
+-- Title matches outrank description matches. dbaccess::topic calls this on every insert and update.
+CREATE FUNCTION topic_search_vector(title varchar, topic_description varchar)
+RETURNS tsvector
+LANGUAGE sql IMMUTABLE
+AS $$
+    SELECT setweight(to_tsvector('english', coalesce(title, '')), 'A')
+        || setweight(to_tsvector('english', coalesce(topic_description, '')), 'B')
+$$;
+
+ALTER TABLE topic ADD COLUMN search_vector tsvector;
+
+UPDATE topic SET search_vector = topic_search_vector(title, topic_description);
+
+CREATE INDEX idx_topic_search_vector ON topic USING GIN (search_vector);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS tutor_email_key;
+
+ALTER TABLE tutor DROP COLUMN IF EXISTS password_hash;
@@ -0,0 +1,4 @@
+ALTER TABLE tutor ADD COLUMN password_hash varchar(255);
+
+-- Tutors log in by email, so it has to identify exactly one of them
+CREATE UNIQUE INDEX tutor_email_key ON tutor (lower(email));
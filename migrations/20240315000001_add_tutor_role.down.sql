@@ -0,0 +1 @@
+ALTER TABLE tutor DROP COLUMN IF EXISTS role;
@@ -0,0 +1,2 @@
+ALTER TABLE tutor ADD COLUMN role varchar(20) NOT NULL DEFAULT 'tutor'
+    CONSTRAINT tutor_role_check CHECK (role IN ('admin', 'tutor'));
@@ -0,0 +1,12 @@
+-- Without the marker trashed rows would come back to life, so remove them first
+DELETE FROM topic WHERE deleted_at IS NOT NULL;
+DELETE FROM tutor WHERE deleted_at IS NOT NULL;
+
+DROP INDEX IF EXISTS idx_topic_deleted_at;
+DROP INDEX IF EXISTS idx_tutor_deleted_at;
+
+DROP INDEX IF EXISTS tutor_email_key;
+CREATE UNIQUE INDEX tutor_email_key ON tutor (lower(email));
+
+ALTER TABLE topic DROP COLUMN IF EXISTS deleted_at;
+ALTER TABLE tutor DROP COLUMN IF EXISTS deleted_at;
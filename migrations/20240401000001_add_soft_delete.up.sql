@@ -0,0 +1,10 @@
+-- Deletes only set deleted_at; rows are removed for good once the retention period passes
+ALTER TABLE tutor ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE topic ADD COLUMN deleted_at TIMESTAMP;
+
+-- A trashed tutor's email can be registered again, after which restoring them is a conflict
+DROP INDEX tutor_email_key;
+CREATE UNIQUE INDEX tutor_email_key ON tutor (lower(email)) WHERE deleted_at IS NULL;
+
+CREATE INDEX idx_tutor_deleted_at ON tutor (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX idx_topic_deleted_at ON topic (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE topic DROP COLUMN version;
+ALTER TABLE tutor DROP COLUMN version;
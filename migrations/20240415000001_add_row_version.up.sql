@@ -0,0 +1,3 @@
+-- Incremented by every update; the API exposes it as the ETag for optimistic concurrency
+ALTER TABLE tutor ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE topic ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
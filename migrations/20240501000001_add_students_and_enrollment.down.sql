@@ -0,0 +1,3 @@
+DROP TABLE enrollment;
+ALTER TABLE topic DROP COLUMN capacity;
+DROP TABLE student;
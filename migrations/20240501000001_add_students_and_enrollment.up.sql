@@ -0,0 +1,25 @@
+CREATE TABLE student (
+    id serial primary key,
+    first_name varchar(200) not null,
+    last_name varchar(200) not null,
+    email varchar(200) not null,
+    -- Set only by the student when they register; a student a tutor created has none and
+    -- cannot log in. The role is implied by the table, so tutor_role_check stays as it is.
+    password_hash varchar(255),
+    created_at TIMESTAMP not null default now()
+);
+
+CREATE UNIQUE INDEX student_email_key ON student (lower(email));
+
+-- NULL means unlimited; 0 closes the topic to new enrollments
+ALTER TABLE topic ADD COLUMN capacity INTEGER
+    CONSTRAINT topic_capacity_check CHECK (capacity >= 0);
+
+CREATE TABLE enrollment (
+    topic_id INT not null REFERENCES topic(id) ON DELETE cascade,
+    student_id INT not null REFERENCES student(id) ON DELETE cascade,
+    enrolled_at TIMESTAMP not null default now(),
+    PRIMARY KEY (topic_id, student_id)
+);
+
+CREATE INDEX idx_enrollment_student_id ON enrollment(student_id);
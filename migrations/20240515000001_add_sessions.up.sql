@@ -0,0 +1,33 @@
+-- Lets the exclusion constraint below compare tutor_id with = alongside a range
+CREATE EXTENSION IF NOT EXISTS btree_gist;
+
+-- When a topic is taught. Times are wall-clock times in `time_zone`; `recurrence` is an
+-- RRULE with COUNT or UNTIL, so every session has a finite list of occurrences.
+CREATE TABLE session (
+    id serial primary key,
+    topic_id INT not null REFERENCES topic(id) ON DELETE cascade,
+    -- Copied from the topic so occurrences can be checked per tutor
+    tutor_id INT not null REFERENCES tutor(id) ON DELETE cascade,
+    starts_at TIMESTAMP not null,
+    ends_at TIMESTAMP not null,
+    time_zone varchar(64) not null,
+    recurrence varchar(200),
+    created_at TIMESTAMP not null default now(),
+    updated_at TIMESTAMP not null default now(),
+    CONSTRAINT session_ends_after_start CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX idx_session_topic_id ON session(topic_id);
+
+-- Every occurrence of every session in UTC, rewritten whenever its session changes. The
+-- constraint rejects any two that overlap for the same tutor, however they were written.
+CREATE TABLE session_occurrence (
+    session_id INT not null REFERENCES session(id) ON DELETE cascade,
+    tutor_id INT not null,
+    starts_at TIMESTAMP not null,
+    ends_at TIMESTAMP not null,
+    CONSTRAINT session_occurrence_no_overlap
+        EXCLUDE USING gist (tutor_id WITH =, tsrange(starts_at, ends_at) WITH &&)
+);
+
+CREATE INDEX idx_session_occurrence_session_id ON session_occurrence(session_id);
@@ -0,0 +1,4 @@
+DROP TABLE booking;
+DROP TABLE availability_exception;
+DROP TABLE availability_window;
+DROP TABLE availability;
@@ -0,0 +1,45 @@
+-- A tutor's weekly availability. Window times are wall-clock times in `time_zone`.
+CREATE TABLE availability (
+    tutor_id INT primary key REFERENCES tutor(id) ON DELETE cascade,
+    time_zone varchar(64) not null,
+    updated_at TIMESTAMP not null default now()
+);
+
+CREATE TABLE availability_window (
+    tutor_id INT not null REFERENCES availability(tutor_id) ON DELETE cascade,
+    -- ISO day number, 1 = Monday
+    weekday SMALLINT not null,
+    starts_at TIME not null,
+    ends_at TIME not null,
+    CONSTRAINT availability_window_weekday CHECK (weekday BETWEEN 1 AND 7),
+    CONSTRAINT availability_window_ends_after_start CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX idx_availability_window_tutor_id ON availability_window(tutor_id);
+
+-- Replaces the weekly windows on one date. A row with null times marks the whole day off.
+CREATE TABLE availability_exception (
+    tutor_id INT not null REFERENCES tutor(id) ON DELETE cascade,
+    on_date DATE not null,
+    starts_at TIME,
+    ends_at TIME,
+    CONSTRAINT availability_exception_window CHECK (
+        (starts_at IS NULL AND ends_at IS NULL) OR ends_at > starts_at
+    )
+);
+
+CREATE INDEX idx_availability_exception_tutor_id ON availability_exception(tutor_id, on_date);
+
+-- Times are UTC. The constraint makes a second booking of the same time fail however the
+-- two requests interleave.
+CREATE TABLE booking (
+    id serial primary key,
+    tutor_id INT not null REFERENCES tutor(id) ON DELETE cascade,
+    student_id INT not null REFERENCES student(id) ON DELETE cascade,
+    starts_at TIMESTAMP not null,
+    ends_at TIMESTAMP not null,
+    created_at TIMESTAMP not null default now(),
+    CONSTRAINT booking_ends_after_start CHECK (ends_at > starts_at),
+    CONSTRAINT booking_no_overlap
+        EXCLUDE USING gist (tutor_id WITH =, tsrange(starts_at, ends_at) WITH &&)
+);
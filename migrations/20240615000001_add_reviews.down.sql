@@ -0,0 +1,7 @@
+ALTER TABLE tutor DROP COLUMN average_rating;
+ALTER TABLE topic DROP COLUMN average_rating;
+ALTER TABLE tutor DROP COLUMN review_count;
+ALTER TABLE tutor DROP COLUMN rating_sum;
+ALTER TABLE topic DROP COLUMN review_count;
+ALTER TABLE topic DROP COLUMN rating_sum;
+DROP TABLE review;
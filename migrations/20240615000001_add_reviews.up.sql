@@ -0,0 +1,28 @@
+-- At most one review per student per topic; a student edits theirs in place
+CREATE TABLE review (
+    id serial primary key,
+    topic_id INT not null REFERENCES topic(id) ON DELETE cascade,
+    student_id INT not null REFERENCES student(id) ON DELETE cascade,
+    rating SMALLINT not null,
+    body TEXT,
+    created_at TIMESTAMP not null default now(),
+    updated_at TIMESTAMP not null default now(),
+    CONSTRAINT review_rating_check CHECK (rating BETWEEN 1 AND 5),
+    CONSTRAINT review_topic_student_key UNIQUE (topic_id, student_id)
+);
+
+CREATE INDEX idx_review_student_id ON review(student_id);
+
+-- Running totals kept in step with `review` by every write to it, so reads never aggregate
+-- the reviews. A tutor's totals cover only their live topics: trashing a topic takes its
+-- ratings off and restoring it puts them back.
+ALTER TABLE topic ADD COLUMN rating_sum INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE topic ADD COLUMN review_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE tutor ADD COLUMN rating_sum INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE tutor ADD COLUMN review_count INTEGER NOT NULL DEFAULT 0;
+
+-- Every query reads the average from one generated column instead of deriving it itself
+ALTER TABLE topic ADD COLUMN average_rating float8
+    GENERATED ALWAYS AS (rating_sum::float8 / NULLIF(review_count, 0)) STORED;
+ALTER TABLE tutor ADD COLUMN average_rating float8
+    GENERATED ALWAYS AS (rating_sum::float8 / NULLIF(review_count, 0)) STORED;
@@ -0,0 +1,365 @@
+use crate::errors::AppErrorType;
+use crate::models::auth::TokenResponse;
+use crate::state::AppState;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{http::header, web, Error, FromRequest, HttpMessage, HttpRequest};
+use argon2::password_hash::{
+    rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::Argon2;
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::str::FromStr;
+
+pub const ACCESS_TOKEN_TTL_SECONDS: i64 = 15 * 60;
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 14 * 24 * 60 * 60;
+
+// HMAC keys used to sign and check both kinds of token
+pub struct JwtKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TokenKind {
+    Access,
+    Refresh,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Tutor,
+    Student,
+    Anonymous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ReadCatalogue,
+    RegisterTutor,
+    UpdateTutor,
+    DeleteTutor,
+    RegisterStudent,
+    WriteTopic,
+    ManageTrash,
+    TransferCatalogue,
+    ReadStudents,
+    ManageStudents,
+    DeleteStudent,
+    ManageBookings,
+    BookSlot,
+    CancelBooking,
+    WriteReview,
+    ModerateReviews,
+}
+
+// Which roles may attempt each operation. Tutors are further limited to their own
+// resources by `Identity::require_owner`, and students to their own by
+// `Identity::require_student`; admins may act on anyone's.
+const PERMISSIONS: &[(Permission, &[Role])] = &[
+    (
+        Permission::ReadCatalogue,
+        &[Role::Admin, Role::Tutor, Role::Student, Role::Anonymous],
+    ),
+    (
+        Permission::RegisterTutor,
+        &[Role::Admin, Role::Tutor, Role::Student, Role::Anonymous],
+    ),
+    (Permission::UpdateTutor, &[Role::Admin, Role::Tutor]),
+    (
+        Permission::RegisterStudent,
+        &[Role::Admin, Role::Tutor, Role::Student, Role::Anonymous],
+    ),
+    (Permission::DeleteTutor, &[Role::Admin]),
+    (Permission::WriteTopic, &[Role::Admin, Role::Tutor]),
+    (Permission::ManageTrash, &[Role::Admin]),
+    (Permission::TransferCatalogue, &[Role::Admin]),
+    (Permission::ReadStudents, &[Role::Admin, Role::Tutor]),
+    (Permission::ManageStudents, &[Role::Admin, Role::Tutor]),
+    (Permission::DeleteStudent, &[Role::Admin]),
+    (Permission::ManageBookings, &[Role::Admin, Role::Tutor]),
+    (Permission::BookSlot, &[Role::Student]),
+    (
+        Permission::CancelBooking,
+        &[Role::Admin, Role::Tutor, Role::Student],
+    ),
+    (Permission::WriteReview, &[Role::Student]),
+    (Permission::ModerateReviews, &[Role::Admin]),
+];
+
+// `sub` is a tutor id, or a student id when `role` is student
+#[derive(Serialize, Deserialize, Debug)]
+struct Claims {
+    sub: String,
+    iat: i64,
+    exp: i64,
+    kind: TokenKind,
+    role: Role,
+}
+
+// The authenticated caller, resolved from an `Authorization: Bearer` access token.
+// `subject_id` is the id of a tutor, or of a student when `role` is `Student`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Identity {
+    pub subject_id: i32,
+    pub role: Role,
+}
+
+// Route middleware that turns away callers whose role lacks `permission`
+#[derive(Debug, Clone, Copy)]
+pub struct RequirePermission(pub Permission);
+
+pub struct RequirePermissionMiddleware<S> {
+    service: S,
+    permission: Permission,
+}
+
+impl JwtKeys {
+    pub fn new(secret: &[u8]) -> Self {
+        JwtKeys {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    pub fn issue_tokens(&self, subject_id: i32, role: Role) -> Result<TokenResponse, AppErrorType> {
+        Ok(TokenResponse {
+            access_token: self.sign(
+                subject_id,
+                role,
+                TokenKind::Access,
+                ACCESS_TOKEN_TTL_SECONDS,
+            )?,
+            refresh_token: self.sign(
+                subject_id,
+                role,
+                TokenKind::Refresh,
+                REFRESH_TOKEN_TTL_SECONDS,
+            )?,
+            token_type: "Bearer".into(),
+            expires_in: ACCESS_TOKEN_TTL_SECONDS,
+        })
+    }
+
+    // Returns the tutor or student a refresh token was issued to
+    pub fn verify_refresh_token(&self, token: &str) -> Result<Identity, AppErrorType> {
+        self.verify(token, TokenKind::Refresh)
+    }
+
+    fn sign(
+        &self,
+        subject_id: i32,
+        role: Role,
+        kind: TokenKind,
+        ttl_seconds: i64,
+    ) -> Result<String, AppErrorType> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: subject_id.to_string(),
+            iat: now,
+            exp: now + ttl_seconds,
+            kind,
+            role,
+        };
+        encode(&Header::default(), &claims, &self.encoding)
+            .map_err(|err| AppErrorType::ActixError(format!("Unable to sign token: {}", err)))
+    }
+
+    fn verify(&self, token: &str, kind: TokenKind) -> Result<Identity, AppErrorType> {
+        let claims = decode::<Claims>(token, &self.decoding, &Validation::default())
+            .map_err(|_err| AppErrorType::UnauthorizedError("Invalid or expired token".into()))?
+            .claims;
+        if claims.kind != kind {
+            return Err(AppErrorType::UnauthorizedError(
+                "Invalid or expired token".into(),
+            ));
+        }
+        let subject_id = claims
+            .sub
+            .parse()
+            .map_err(|_err| AppErrorType::UnauthorizedError("Invalid or expired token".into()))?;
+        Ok(Identity {
+            subject_id,
+            role: claims.role,
+        })
+    }
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Tutor => "tutor",
+            Role::Student => "student",
+            Role::Anonymous => "anonymous",
+        }
+    }
+
+    pub fn can(self, permission: Permission) -> bool {
+        PERMISSIONS
+            .iter()
+            .any(|(granted, roles)| *granted == permission && roles.contains(&self))
+    }
+}
+
+impl FromStr for Role {
+    type Err = AppErrorType;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "admin" => Ok(Role::Admin),
+            "tutor" => Ok(Role::Tutor),
+            "student" => Ok(Role::Student),
+            "anonymous" => Ok(Role::Anonymous),
+            _ => Err(AppErrorType::DbError(format!("Unknown role: {}", role))),
+        }
+    }
+}
+
+impl Identity {
+    pub fn require_owner(&self, tutor_id: i32) -> Result<(), AppErrorType> {
+        match self.role {
+            Role::Admin => Ok(()),
+            Role::Tutor if self.subject_id == tutor_id => Ok(()),
+            _ => Err(AppErrorType::ForbiddenError(
+                "Tutors can only change their own profile and topics".into(),
+            )),
+        }
+    }
+
+    // The caller's student id, for operations a student can only perform for themselves
+    pub fn require_student(&self) -> Result<i32, AppErrorType> {
+        match self.role {
+            Role::Student => Ok(self.subject_id),
+            _ => Err(AppErrorType::ForbiddenError(
+                "Only a student can perform this operation".into(),
+            )),
+        }
+    }
+}
+
+impl FromRequest for Identity {
+    type Error = AppErrorType;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        // A permission guard on the route will already have checked the token
+        if let Some(identity) = req.extensions().get::<Identity>() {
+            return ready(Ok(*identity));
+        }
+        ready(identify(req).and_then(|identity| {
+            identity
+                .ok_or_else(|| AppErrorType::UnauthorizedError("A bearer token is required".into()))
+        }))
+    }
+}
+
+// None when the request carries no credentials at all, i.e. an anonymous caller
+fn identify(req: &HttpRequest) -> Result<Option<Identity>, AppErrorType> {
+    let app_state = req
+        .app_data::<web::Data<AppState>>()
+        .ok_or_else(|| AppErrorType::ActixError("App state is not configured".into()))?;
+    let authorization = match req.headers().get(header::AUTHORIZATION) {
+        Some(authorization) => authorization,
+        None => return Ok(None),
+    };
+    let token = authorization
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| AppErrorType::UnauthorizedError("A bearer token is required".into()))?;
+
+    app_state
+        .jwt
+        .verify(token.trim(), TokenKind::Access)
+        .map(Some)
+}
+
+fn authorize(req: &HttpRequest, permission: Permission) -> Result<Option<Identity>, AppErrorType> {
+    let identity = identify(req)?;
+    let role = identity.map_or(Role::Anonymous, |identity| identity.role);
+    if role.can(permission) {
+        return Ok(identity);
+    }
+
+    Err(match identity {
+        None => AppErrorType::UnauthorizedError("A bearer token is required".into()),
+        Some(_) => AppErrorType::ForbiddenError(format!(
+            "The {} role may not perform this operation",
+            role.as_str()
+        )),
+    })
+}
+
+impl<S, B: 'static> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequirePermissionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePermissionMiddleware {
+            service,
+            permission: self.0,
+        }))
+    }
+}
+
+impl<S, B: 'static> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        match authorize(req.request(), self.permission) {
+            Ok(identity) => {
+                if let Some(identity) = identity {
+                    req.extensions_mut().insert(identity);
+                }
+                let response = self.service.call(req);
+                Box::pin(async move { response.await.map(ServiceResponse::map_into_left_body) })
+            }
+            Err(err) => {
+                let response = req.error_response(err).map_into_right_body();
+                Box::pin(async move { Ok(response) })
+            }
+        }
+    }
+}
+
+// Argon2id with the crate's default cost parameters and a random salt, in PHC string format
+pub fn hash_password(password: &str) -> Result<String, AppErrorType> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| AppErrorType::ActixError(format!("Unable to hash password: {}", err)))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
@@ -0,0 +1,392 @@
+use super::record_rows;
+use crate::errors::AppErrorType;
+use crate::models::availability::{
+    Availability, AvailabilityException, Booking, NewBooking, Slot, TimeWindow, WeeklyAvailability,
+    WeeklyWindow,
+};
+use crate::models::session::Interval;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use sqlx::postgres::{PgConnection, PgPool, Postgres};
+use sqlx::Transaction;
+use tracing::field::Empty;
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_availability_db(
+    pool: &PgPool,
+    tutor_id: i32,
+) -> Result<Availability, AppErrorType> {
+    let mut conn = pool.acquire().await?;
+    live_tutor(&mut conn, tutor_id).await?;
+
+    read_availability(&mut conn, tutor_id).await
+}
+
+#[tracing::instrument(skip(pool, weekly), fields(db.rows = Empty))]
+pub async fn put_weekly_availability_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    weekly: WeeklyAvailability,
+) -> Result<Availability, AppErrorType> {
+    let mut tx = pool.begin().await?;
+    lock_tutor(&mut tx, tutor_id).await?;
+
+    sqlx::query!(
+        "INSERT INTO availability (tutor_id, time_zone, updated_at) VALUES ($1, $2, $3)
+        ON CONFLICT (tutor_id) DO UPDATE SET time_zone = $2, updated_at = $3",
+        tutor_id,
+        weekly.time_zone,
+        Utc::now().naive_utc()
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM availability_window WHERE tutor_id = $1",
+        tutor_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let weekdays: Vec<i16> = weekly.windows.iter().map(|window| window.weekday).collect();
+    let (starts, ends): (Vec<NaiveTime>, Vec<NaiveTime>) = weekly
+        .windows
+        .iter()
+        .map(|window| (window.starts_at, window.ends_at))
+        .unzip();
+    sqlx::query!(
+        "INSERT INTO availability_window (tutor_id, weekday, starts_at, ends_at)
+        SELECT $1, window_row.weekday, window_row.starts_at, window_row.ends_at
+        FROM UNNEST($2::int2[], $3::time[], $4::time[]) AS window_row(weekday, starts_at, ends_at)",
+        tutor_id,
+        &weekdays,
+        &starts,
+        &ends
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let availability = read_availability(&mut tx, tutor_id).await?;
+    tx.commit().await?;
+
+    Ok(availability)
+}
+
+// A day off is stored as a single row without times
+#[tracing::instrument(skip(pool, windows), fields(db.rows = Empty))]
+pub async fn put_availability_exception_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    date: NaiveDate,
+    mut windows: Vec<TimeWindow>,
+) -> Result<AvailabilityException, AppErrorType> {
+    windows.sort_by_key(|window| window.starts_at);
+    let mut tx = pool.begin().await?;
+    lock_tutor(&mut tx, tutor_id).await?;
+
+    sqlx::query!(
+        "DELETE FROM availability_exception WHERE tutor_id = $1 AND on_date = $2",
+        tutor_id,
+        date
+    )
+    .execute(&mut tx)
+    .await?;
+    if windows.is_empty() {
+        sqlx::query!(
+            "INSERT INTO availability_exception (tutor_id, on_date) VALUES ($1, $2)",
+            tutor_id,
+            date
+        )
+        .execute(&mut tx)
+        .await?;
+    } else {
+        let (starts, ends): (Vec<NaiveTime>, Vec<NaiveTime>) = windows
+            .iter()
+            .map(|window| (window.starts_at, window.ends_at))
+            .unzip();
+        sqlx::query!(
+            "INSERT INTO availability_exception (tutor_id, on_date, starts_at, ends_at)
+            SELECT $1, $2, window_row.starts_at, window_row.ends_at
+            FROM UNNEST($3::time[], $4::time[]) AS window_row(starts_at, ends_at)",
+            tutor_id,
+            date,
+            &starts,
+            &ends
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+    record_rows(windows.len().max(1) as u64);
+
+    Ok(AvailabilityException { date, windows })
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn delete_availability_exception_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    date: NaiveDate,
+) -> Result<String, AppErrorType> {
+    let deleted = sqlx::query!(
+        "DELETE FROM availability_exception WHERE tutor_id = $1 AND on_date = $2",
+        tutor_id,
+        date
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if deleted == 0 {
+        return Err(AppErrorType::NotFoundError(
+            "Availability exception not found".into(),
+        ));
+    }
+    record_rows(deleted);
+
+    Ok(format!("Availability exception on {} deleted", date))
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_free_slots_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    range: Interval,
+    length: Duration,
+) -> Result<Vec<Slot>, AppErrorType> {
+    let mut conn = pool.acquire().await?;
+    live_tutor(&mut conn, tutor_id).await?;
+    let availability = read_availability(&mut conn, tutor_id).await?;
+
+    // A slot starting near the end of the range can run past it
+    let busy: Vec<Interval> = sqlx::query!(
+        r#"SELECT starts_at AS "starts_at!", ends_at AS "ends_at!" FROM booking
+            WHERE tutor_id = $1 AND starts_at < $3 AND ends_at > $2
+        UNION ALL
+        SELECT starts_at, ends_at FROM session_occurrence
+            WHERE tutor_id = $1 AND starts_at < $3 AND ends_at > $2"#,
+        tutor_id,
+        range.starts_at,
+        range.ends_at + length
+    )
+    .fetch_all(&mut conn)
+    .await?
+    .into_iter()
+    .map(|row| Interval {
+        starts_at: row.starts_at,
+        ends_at: row.ends_at,
+    })
+    .collect();
+    record_rows(busy.len() as u64);
+
+    Ok(availability.free_slots(range, length, &busy, Utc::now().naive_utc()))
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_bookings_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    range: Interval,
+) -> Result<Vec<Booking>, AppErrorType> {
+    let mut conn = pool.acquire().await?;
+    live_tutor(&mut conn, tutor_id).await?;
+
+    let bookings = sqlx::query_as!(
+        Booking,
+        "SELECT id, tutor_id, student_id, starts_at, ends_at, created_at FROM booking
+        WHERE tutor_id = $1 AND starts_at < $3 AND ends_at > $2
+        ORDER BY starts_at",
+        tutor_id,
+        range.starts_at,
+        range.ends_at
+    )
+    .fetch_all(&mut conn)
+    .await?;
+    record_rows(bookings.len() as u64);
+
+    Ok(bookings)
+}
+
+// The `booking_no_overlap` constraint is what rules out double-booking. Locking the tutor
+// row also orders bookings against session changes, which live in another table.
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn book_slot_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    student_id: i32,
+    booking: NewBooking,
+) -> Result<Booking, AppErrorType> {
+    let interval = booking.interval(Utc::now().naive_utc())?;
+    let mut tx = pool.begin().await?;
+    lock_tutor(&mut tx, tutor_id).await?;
+
+    if !read_availability(&mut tx, tutor_id).await?.covers(interval) {
+        return Err(AppErrorType::ConflictError(
+            "The time is outside the tutor's availability".into(),
+        ));
+    }
+    let in_session = sqlx::query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM session_occurrence
+            WHERE tutor_id = $1 AND starts_at < $3 AND ends_at > $2
+        ) AS "exists!""#,
+        tutor_id,
+        interval.starts_at,
+        interval.ends_at
+    )
+    .fetch_one(&mut tx)
+    .await?
+    .exists;
+    if in_session {
+        return Err(AppErrorType::exclusion_violation());
+    }
+
+    // An unknown student fails the foreign key
+    let booking = sqlx::query_as!(
+        Booking,
+        "INSERT INTO booking (tutor_id, student_id, starts_at, ends_at) VALUES ($1, $2, $3, $4)
+        RETURNING id, tutor_id, student_id, starts_at, ends_at, created_at",
+        tutor_id,
+        student_id,
+        interval.starts_at,
+        interval.ends_at
+    )
+    .fetch_one(&mut tx)
+    .await?;
+    tx.commit().await?;
+    record_rows(1);
+
+    Ok(booking)
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn cancel_booking_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    booking_id: i32,
+    student_id: Option<i32>,
+) -> Result<String, AppErrorType> {
+    let booking_row = sqlx::query!(
+        "DELETE FROM booking WHERE id = $1 AND tutor_id = $2 AND ($3::int4 IS NULL OR student_id = $3)
+        RETURNING id",
+        booking_id,
+        tutor_id,
+        student_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Booking id not found"))?;
+    record_rows(1);
+
+    Ok(format!("Booking with id: {} cancelled", booking_row.id))
+}
+
+// Taken by every transaction that books or schedules the tutor's time
+pub(super) async fn lock_tutor(
+    tx: &mut Transaction<'_, Postgres>,
+    tutor_id: i32,
+) -> Result<(), AppErrorType> {
+    sqlx::query!(
+        "SELECT id FROM tutor WHERE id = $1 AND deleted_at IS NULL FOR NO KEY UPDATE",
+        tutor_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Tutor id not found"))?;
+
+    Ok(())
+}
+
+// Whether any of the tutor's bookings overlaps one of `occurrences`
+pub(super) async fn overlaps_bookings(
+    tx: &mut Transaction<'_, Postgres>,
+    tutor_id: i32,
+    occurrences: &[Interval],
+) -> Result<bool, AppErrorType> {
+    let (starts, ends): (Vec<NaiveDateTime>, Vec<NaiveDateTime>) = occurrences
+        .iter()
+        .map(|occurrence| (occurrence.starts_at, occurrence.ends_at))
+        .unzip();
+    let overlaps = sqlx::query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM booking
+            JOIN UNNEST($2::timestamp[], $3::timestamp[]) AS occurrence(starts_at, ends_at)
+                ON booking.starts_at < occurrence.ends_at AND booking.ends_at > occurrence.starts_at
+            WHERE booking.tutor_id = $1
+        ) AS "exists!""#,
+        tutor_id,
+        &starts,
+        &ends
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .exists;
+
+    Ok(overlaps)
+}
+
+async fn live_tutor(conn: &mut PgConnection, tutor_id: i32) -> Result<(), AppErrorType> {
+    sqlx::query!(
+        "SELECT id FROM tutor WHERE id = $1 AND deleted_at IS NULL",
+        tutor_id
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Tutor id not found"))?;
+
+    Ok(())
+}
+
+async fn read_availability(
+    conn: &mut PgConnection,
+    tutor_id: i32,
+) -> Result<Availability, AppErrorType> {
+    let mut availability = Availability::none(tutor_id);
+    if let Some(row) = sqlx::query!(
+        "SELECT time_zone FROM availability WHERE tutor_id = $1",
+        tutor_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    {
+        availability.time_zone = row.time_zone;
+    }
+
+    availability.windows = sqlx::query_as!(
+        WeeklyWindow,
+        "SELECT weekday, starts_at, ends_at FROM availability_window
+        WHERE tutor_id = $1 ORDER BY weekday, starts_at",
+        tutor_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let exception_rows = sqlx::query!(
+        "SELECT on_date, starts_at, ends_at FROM availability_exception
+        WHERE tutor_id = $1 ORDER BY on_date, starts_at",
+        tutor_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    record_rows((availability.windows.len() + exception_rows.len()) as u64 + 1);
+    for row in exception_rows {
+        if availability
+            .exceptions
+            .last()
+            .map(|exception| exception.date)
+            != Some(row.on_date)
+        {
+            availability.exceptions.push(AvailabilityException {
+                date: row.on_date,
+                windows: Vec::new(),
+            });
+        }
+        if let (Some(starts_at), Some(ends_at)) = (row.starts_at, row.ends_at) {
+            availability
+                .exceptions
+                .last_mut()
+                .unwrap()
+                .windows
+                .push(TimeWindow { starts_at, ends_at });
+        }
+    }
+
+    Ok(availability)
+}
@@ -0,0 +1,238 @@
+use super::record_rows;
+use crate::errors::AppErrorType;
+use crate::models::catalogue::{
+    row_errors, CatalogueRecord, ImportItem, ImportMode, ImportOutcome, ImportReport, ImportRow,
+    RecordKind,
+};
+use crate::models::topic::UpdateTopic;
+use crate::models::tutor::UpdateTutor;
+use chrono::Utc;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::{Acquire, Transaction};
+use tracing::field::Empty;
+
+// Tutors first, so importing the file again finds each topic's tutor
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+pub async fn export_catalogue_db(pool: &PgPool) -> Result<Vec<CatalogueRecord>, AppErrorType> {
+    let tutors = sqlx::query!(
+        "SELECT email, first_name, last_name FROM tutor WHERE deleted_at IS NULL ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let topics = sqlx::query!(
+        "SELECT tutor.email, topic.title, topic.topic_description, topic.format, topic.duration, topic.topic_level, topic.capacity
+        FROM topic JOIN tutor ON tutor.id = topic.tutor_id
+        WHERE topic.deleted_at IS NULL AND tutor.deleted_at IS NULL
+        ORDER BY topic.tutor_id, topic.id"
+    )
+    .fetch_all(pool)
+    .await?;
+    record_rows((tutors.len() + topics.len()) as u64);
+
+    let tutors = tutors.into_iter().map(|tutor| CatalogueRecord {
+        kind: RecordKind::Tutor,
+        email: tutor.email,
+        first_name: Some(tutor.first_name),
+        last_name: Some(tutor.last_name),
+        title: None,
+        topic_description: None,
+        format: None,
+        duration: None,
+        topic_level: None,
+        capacity: None,
+    });
+    let topics = topics.into_iter().map(|topic| CatalogueRecord {
+        kind: RecordKind::Topic,
+        email: topic.email,
+        first_name: None,
+        last_name: None,
+        title: Some(topic.title),
+        topic_description: topic.topic_description,
+        format: topic.format,
+        duration: topic.duration,
+        topic_level: topic.topic_level,
+        capacity: topic.capacity,
+    });
+    Ok(tutors.chain(topics).collect())
+}
+
+// One transaction for the whole file, with a savepoint per row so a failed row can be
+// rolled back on its own. `atomic` then rolls everything back if any row was rejected.
+#[tracing::instrument(skip(pool, rows), fields(db.rows = Empty))]
+pub async fn import_catalogue_db(
+    pool: &PgPool,
+    rows: Vec<ImportRow>,
+    mode: ImportMode,
+) -> Result<ImportReport, AppErrorType> {
+    let mut tx = pool.begin().await?;
+    let mut reports = Vec::with_capacity(rows.len());
+
+    for row in &rows {
+        let result = match &row.item {
+            Ok(item) => {
+                let mut savepoint = tx.begin().await?;
+                match import_item(&mut savepoint, item).await {
+                    Ok(written) => {
+                        savepoint.commit().await?;
+                        Ok(written)
+                    }
+                    Err(err) => {
+                        savepoint.rollback().await?;
+                        Err(row_errors(err))
+                    }
+                }
+            }
+            Err(errors) => Err(errors.clone()),
+        };
+        reports.push(row.report(result));
+    }
+
+    let committed = mode.commits(&reports);
+    if committed {
+        tx.commit().await?;
+    } else {
+        tx.rollback().await?;
+    }
+    let report = ImportReport::new(mode, committed, reports);
+    record_rows((report.created + report.updated) as u64);
+
+    Ok(report)
+}
+
+async fn import_item(
+    tx: &mut Transaction<'_, Postgres>,
+    item: &ImportItem,
+) -> Result<(ImportOutcome, i32), AppErrorType> {
+    match item {
+        ImportItem::Tutor(tutor) => import_tutor(tx, tutor).await,
+        ImportItem::Topic { tutor_email, topic } => import_topic(tx, tutor_email, topic).await,
+    }
+}
+
+// Imported tutors have no password, so they cannot log in until one is set
+async fn import_tutor(
+    tx: &mut Transaction<'_, Postgres>,
+    tutor: &UpdateTutor,
+) -> Result<(ImportOutcome, i32), AppErrorType> {
+    let existing = sqlx::query!(
+        "SELECT id, first_name, last_name, email FROM tutor
+        WHERE lower(email) = lower($1) AND deleted_at IS NULL FOR UPDATE",
+        tutor.email
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    match existing {
+        None => {
+            let created = sqlx::query!(
+                "INSERT INTO tutor (first_name, last_name, email) VALUES ($1, $2, $3) RETURNING id",
+                tutor.first_name,
+                tutor.last_name,
+                tutor.email
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            Ok((ImportOutcome::Created, created.id))
+        }
+        Some(existing)
+            if existing.first_name == tutor.first_name
+                && existing.last_name == tutor.last_name
+                && existing.email == tutor.email =>
+        {
+            Ok((ImportOutcome::Skipped, existing.id))
+        }
+        Some(existing) => {
+            sqlx::query!(
+                "UPDATE tutor SET first_name = $1, last_name = $2, email = $3, version = version + 1
+                WHERE id = $4",
+                tutor.first_name,
+                tutor.last_name,
+                tutor.email,
+                existing.id
+            )
+            .execute(&mut *tx)
+            .await?;
+            Ok((ImportOutcome::Updated, existing.id))
+        }
+    }
+}
+
+// Titles are matched case-insensitively; if a tutor has several topics with the same
+// title, the oldest one is updated
+async fn import_topic(
+    tx: &mut Transaction<'_, Postgres>,
+    tutor_email: &str,
+    topic: &UpdateTopic,
+) -> Result<(ImportOutcome, i32), AppErrorType> {
+    let tutor = sqlx::query!(
+        "SELECT id FROM tutor WHERE lower(email) = lower($1) AND deleted_at IS NULL FOR SHARE",
+        tutor_email
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| {
+        AppErrorType::UnprocessableError(format!("No tutor with email {}", tutor_email))
+    })?;
+
+    let existing = sqlx::query!(
+        "SELECT id, title, topic_description, format, duration, topic_level, capacity FROM topic
+        WHERE tutor_id = $1 AND lower(title) = lower($2) AND deleted_at IS NULL
+        ORDER BY id LIMIT 1 FOR UPDATE",
+        tutor.id,
+        topic.title
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let current_time = Utc::now().naive_utc();
+
+    match existing {
+        None => {
+            let created = sqlx::query!(
+                "INSERT INTO topic (
+                    tutor_id, title, topic_description, format, duration, topic_level, capacity, created_at, updated_at, search_vector)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8, topic_search_vector($2, $3))
+                RETURNING id",
+                tutor.id,
+                topic.title,
+                topic.topic_description,
+                topic.format,
+                topic.duration,
+                topic.topic_level,
+                topic.capacity,
+                current_time
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            Ok((ImportOutcome::Created, created.id))
+        }
+        Some(existing)
+            if existing.title == topic.title
+                && existing.topic_description == topic.topic_description
+                && existing.format == topic.format
+                && existing.duration == topic.duration
+                && existing.topic_level == topic.topic_level
+                && existing.capacity == topic.capacity =>
+        {
+            Ok((ImportOutcome::Skipped, existing.id))
+        }
+        Some(existing) => {
+            sqlx::query!(
+                "UPDATE topic SET title = $1, topic_description = $2, format = $3, duration = $4, topic_level = $5,
+                    capacity = $6, updated_at = $7, search_vector = topic_search_vector($1, $2), version = version + 1
+                WHERE id = $8",
+                topic.title,
+                topic.topic_description,
+                topic.format,
+                topic.duration,
+                topic.topic_level,
+                topic.capacity,
+                current_time,
+                existing.id
+            )
+            .execute(&mut *tx)
+            .await?;
+            Ok((ImportOutcome::Updated, existing.id))
+        }
+    }
+}
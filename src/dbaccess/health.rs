@@ -0,0 +1,59 @@
+use crate::models::health::{DatabaseHealth, HealthStatus, PoolStats};
+use sqlx::postgres::PgPool;
+use std::time::{Duration, Instant};
+
+pub fn pool_stats_db(pool: &PgPool) -> PoolStats {
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+    PoolStats {
+        size,
+        idle,
+        in_use: size.saturating_sub(idle),
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn database_health_db(pool: &PgPool, timeout: Duration) -> DatabaseHealth {
+    let pool_stats = pool_stats_db(pool);
+
+    // Reading the migration version doubles as the ping
+    let started = Instant::now();
+    let version = actix_rt::time::timeout(
+        timeout,
+        sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT max(version) FROM _sqlx_migrations WHERE success",
+        )
+        .fetch_one(pool),
+    )
+    .await;
+    let latency_ms = Some(started.elapsed().as_millis() as u64);
+
+    let (status, error, migration_version) = match version {
+        Ok(Ok(version)) => (HealthStatus::Ok, None, version),
+        Ok(Err(err)) => {
+            tracing::warn!(error = %err, "database ping failed");
+            (
+                HealthStatus::Degraded,
+                Some("Database ping failed".to_string()),
+                None,
+            )
+        }
+        Err(_elapsed) => (
+            HealthStatus::Degraded,
+            Some(format!(
+                "Database did not answer within {}ms",
+                timeout.as_millis()
+            )),
+            None,
+        ),
+    };
+
+    DatabaseHealth {
+        status,
+        backend: "postgres".into(),
+        latency_ms,
+        error,
+        pool: Some(pool_stats),
+        migration_version,
+    }
+}
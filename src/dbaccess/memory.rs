@@ -0,0 +1,1823 @@
+use super::{
+    check_version, AvailabilityRepository, CatalogueRepository, HealthRepository, ReviewRepository,
+    RowStream, SessionRepository, StudentRepository, TopicRepository, TrashRepository,
+    TutorRepository,
+};
+use crate::errors::AppErrorType;
+use crate::models::auth::{StudentCredentials, TutorCredentials};
+use crate::models::availability::{
+    Availability, AvailabilityException, Booking, NewBooking, Slot, TimeWindow, WeeklyAvailability,
+    WeeklyWindow,
+};
+use crate::models::calendar::{Calendar, CalendarEvent};
+use crate::models::catalogue::{
+    row_errors, CatalogueRecord, ImportItem, ImportMode, ImportOutcome, ImportReport, ImportRow,
+    RecordKind,
+};
+use crate::models::health::{DatabaseHealth, HealthStatus, PoolStats};
+use crate::models::page::{Page, PageRequest, SortKey};
+use crate::models::review::{average_rating, check_enrolled, Review, WriteReview};
+use crate::models::session::{Interval, Occurrence, Session, SessionSchedule};
+use crate::models::student::{
+    check_capacity, enrollment_order, CreateStudent, EnrolledStudent, EnrolledTopic, Enrollment,
+    Roster, Student,
+};
+use crate::models::topic::{
+    CreateTopic, Topic, TopicFilter, TopicPatch, TopicSearchHit, UpdateTopic,
+};
+use crate::models::trash::{PurgeSummary, Trash, TrashedTopic, TrashedTutor, TutorRestoration};
+use crate::models::tutor::{
+    CreateTutor, TopicRef, Tutor, TutorDeletion, TutorFilter, TutorPatch, UpdateTutor,
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use futures::stream::{self, StreamExt};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+// Mirrors the behaviour of the Postgres queries in `tutor.rs` and `topic.rs`,
+// including their error variants, so handlers can be exercised without a database.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    store: Mutex<Store>,
+}
+
+#[derive(Default, Clone)]
+struct Store {
+    tutors: BTreeMap<i32, Tutor>,
+    topics: BTreeMap<i32, Topic>,
+    // Soft-deleted rows with their `deleted_at`
+    trashed_tutors: BTreeMap<i32, (Tutor, NaiveDateTime)>,
+    trashed_topics: BTreeMap<i32, (Topic, NaiveDateTime)>,
+    password_hashes: BTreeMap<i32, String>,
+    students: BTreeMap<i32, Student>,
+    student_password_hashes: BTreeMap<i32, String>,
+    // `enrolled_at` by (topic_id, student_id)
+    enrollments: BTreeMap<(i32, i32), NaiveDateTime>,
+    // Each session with its occurrences
+    sessions: BTreeMap<i32, (Session, Vec<Interval>)>,
+    // Time zone and weekly windows by tutor_id
+    availability: BTreeMap<i32, (String, Vec<WeeklyWindow>)>,
+    availability_exceptions: BTreeMap<(i32, NaiveDate), Vec<TimeWindow>>,
+    bookings: BTreeMap<i32, Booking>,
+    reviews: BTreeMap<i32, Review>,
+    last_tutor_id: i32,
+    last_topic_id: i32,
+    last_student_id: i32,
+    last_session_id: i32,
+    last_booking_id: i32,
+    last_review_id: i32,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        InMemoryRepository::default()
+    }
+
+    fn store(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl TutorRepository for InMemoryRepository {
+    async fn get_all_tutors(
+        &self,
+        filter: &TutorFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Tutor>, AppErrorType> {
+        let sort = filter.sort_keys()?;
+
+        paginate(
+            self.filter_tutors(filter),
+            &sort,
+            page,
+            |tutor| tutor.id,
+            tutor_column,
+        )
+    }
+
+    fn stream_tutors(&self, filter: &TutorFilter) -> Result<RowStream<Tutor>, AppErrorType> {
+        let sort = filter.sort_keys()?;
+        let mut tutors = self.filter_tutors(filter);
+        sort_rows(&mut tutors, &sort, tutor_column);
+
+        Ok(stream::iter(tutors.into_iter().map(Ok)).boxed())
+    }
+
+    async fn post_new_tutor(
+        &self,
+        new_tutor: CreateTutor,
+        password_hash: String,
+    ) -> Result<Tutor, AppErrorType> {
+        let mut store = self.store();
+        if store.email_taken(&new_tutor.email, None) {
+            return Err(AppErrorType::unique_violation());
+        }
+        store.last_tutor_id += 1;
+        let tutor = Tutor {
+            id: store.last_tutor_id,
+            first_name: new_tutor.first_name,
+            last_name: new_tutor.last_name,
+            email: new_tutor.email,
+            average_rating: None,
+            review_count: 0,
+            version: 1,
+        };
+        store.tutors.insert(tutor.id, tutor.clone());
+        store.password_hashes.insert(tutor.id, password_hash);
+
+        Ok(tutor)
+    }
+
+    async fn get_tutor_details(&self, tutor_id: i32) -> Result<Tutor, AppErrorType> {
+        self.store()
+            .tutors
+            .get(&tutor_id)
+            .cloned()
+            .ok_or_else(|| AppErrorType::NotFoundError("Tutor id not found".into()))
+    }
+
+    async fn update_tutor_details(
+        &self,
+        tutor_id: i32,
+        update_tutor: UpdateTutor,
+        if_match: Option<&[i32]>,
+    ) -> Result<Tutor, AppErrorType> {
+        self.store().write_tutor(tutor_id, if_match, |tutor| {
+            tutor.first_name = update_tutor.first_name;
+            tutor.last_name = update_tutor.last_name;
+            tutor.email = update_tutor.email;
+        })
+    }
+
+    async fn patch_tutor(
+        &self,
+        tutor_id: i32,
+        patch: TutorPatch,
+        if_match: Option<&[i32]>,
+    ) -> Result<Tutor, AppErrorType> {
+        self.store()
+            .write_tutor(tutor_id, if_match, |tutor| patch.apply(tutor))
+    }
+
+    async fn delete_tutor(
+        &self,
+        tutor_id: i32,
+        dry_run: bool,
+    ) -> Result<TutorDeletion, AppErrorType> {
+        let mut store = self.store();
+        if !store.tutors.contains_key(&tutor_id) {
+            return Err(AppErrorType::NotFoundError("Tutor id not found".into()));
+        }
+
+        // BTreeMap iteration is in id order, matching the sorted Postgres result
+        let topics: Vec<TopicRef> = store
+            .topics
+            .values()
+            .filter(|topic| topic.tutor_id == tutor_id)
+            .map(|topic| TopicRef {
+                id: topic.id,
+                title: topic.title.clone(),
+            })
+            .collect();
+
+        if !dry_run {
+            let deleted_at = Utc::now().naive_utc();
+            for topic in &topics {
+                let topic = store.topics.remove(&topic.id).unwrap();
+                store.trashed_topics.insert(topic.id, (topic, deleted_at));
+            }
+            let tutor = store.tutors.remove(&tutor_id).unwrap();
+            store.trashed_tutors.insert(tutor_id, (tutor, deleted_at));
+            store.refresh_ratings(tutor_id);
+        }
+
+        Ok(TutorDeletion {
+            tutor_id,
+            dry_run,
+            topic_count: topics.len(),
+            topics,
+        })
+    }
+
+    async fn get_tutor_credentials(
+        &self,
+        email: &str,
+    ) -> Result<Option<TutorCredentials>, AppErrorType> {
+        let store = self.store();
+        let credentials = store
+            .tutors
+            .values()
+            .find(|tutor| tutor.email.to_lowercase() == email.to_lowercase())
+            .map(|tutor| TutorCredentials {
+                tutor_id: tutor.id,
+                password_hash: store.password_hashes.get(&tutor.id).cloned(),
+                role: "tutor".into(),
+            });
+
+        Ok(credentials)
+    }
+
+    // Roles are only ever granted in the database, so every in-memory tutor is a plain tutor
+    async fn get_tutor_role(&self, tutor_id: i32) -> Result<String, AppErrorType> {
+        self.store()
+            .tutors
+            .get(&tutor_id)
+            .map(|_tutor| "tutor".to_string())
+            .ok_or_else(|| AppErrorType::NotFoundError("Tutor id not found".into()))
+    }
+}
+
+impl Store {
+    // Mirrors the unique index on lower(email)
+    fn email_taken(&self, email: &str, except_tutor_id: Option<i32>) -> bool {
+        self.tutors.values().any(|tutor| {
+            Some(tutor.id) != except_tutor_id && tutor.email.to_lowercase() == email.to_lowercase()
+        })
+    }
+
+    // Shared by PUT and PATCH: checks `if_match`, applies `change` and bumps the version
+    fn write_tutor(
+        &mut self,
+        tutor_id: i32,
+        if_match: Option<&[i32]>,
+        change: impl FnOnce(&mut Tutor),
+    ) -> Result<Tutor, AppErrorType> {
+        let mut tutor = self
+            .tutors
+            .get(&tutor_id)
+            .cloned()
+            .ok_or_else(|| AppErrorType::NotFoundError("Tutor id not found".into()))?;
+        check_version(if_match, tutor.version)?;
+        change(&mut tutor);
+        if self.email_taken(&tutor.email, Some(tutor_id)) {
+            return Err(AppErrorType::unique_violation());
+        }
+
+        tutor.version += 1;
+        self.tutors.insert(tutor_id, tutor.clone());
+        Ok(tutor)
+    }
+
+    fn write_topic(
+        &mut self,
+        tutor_id: i32,
+        topic_id: i32,
+        if_match: Option<&[i32]>,
+        change: impl FnOnce(&mut Topic),
+    ) -> Result<Topic, AppErrorType> {
+        let topic = self
+            .topics
+            .get_mut(&topic_id)
+            .filter(|topic| topic.tutor_id == tutor_id)
+            .ok_or_else(|| AppErrorType::NotFoundError("Topic id not found".into()))?;
+        check_version(if_match, topic.version)?;
+        change(topic);
+
+        topic.updated_at = Some(Utc::now().naive_utc());
+        topic.version += 1;
+        Ok(topic.clone())
+    }
+}
+
+#[async_trait]
+impl TopicRepository for InMemoryRepository {
+    async fn get_all_topics(
+        &self,
+        filter: &TopicFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Topic>, AppErrorType> {
+        let sort = filter.sort_keys(SortKey::asc("id"))?;
+
+        paginate(
+            self.filter_topics(filter),
+            &sort,
+            page,
+            |topic| topic.id,
+            topic_column,
+        )
+    }
+
+    async fn get_topics_for_tutor(
+        &self,
+        tutor_id: i32,
+        filter: &TopicFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Topic>, AppErrorType> {
+        let filter = TopicFilter {
+            tutor_id: Some(tutor_id),
+            ..filter.clone()
+        };
+        let sort = filter.sort_keys(SortKey::desc("id"))?;
+
+        paginate(
+            self.filter_topics(&filter),
+            &sort,
+            page,
+            |topic| topic.id,
+            topic_column,
+        )
+    }
+
+    fn stream_topics(&self, filter: &TopicFilter) -> Result<RowStream<Topic>, AppErrorType> {
+        let sort = filter.sort_keys(SortKey::asc("id"))?;
+        let mut topics = self.filter_topics(filter);
+        sort_rows(&mut topics, &sort, topic_column);
+
+        Ok(stream::iter(topics.into_iter().map(Ok)).boxed())
+    }
+
+    fn stream_topics_for_tutor(
+        &self,
+        tutor_id: i32,
+        filter: &TopicFilter,
+    ) -> Result<RowStream<Topic>, AppErrorType> {
+        let filter = TopicFilter {
+            tutor_id: Some(tutor_id),
+            ..filter.clone()
+        };
+        let sort = filter.sort_keys(SortKey::desc("id"))?;
+        let mut topics = self.filter_topics(&filter);
+        sort_rows(&mut topics, &sort, topic_column);
+
+        Ok(stream::iter(topics.into_iter().map(Ok)).boxed())
+    }
+
+    async fn search_topics(
+        &self,
+        terms: &str,
+        page: &PageRequest,
+    ) -> Result<Page<TopicSearchHit>, AppErrorType> {
+        page.keyset_order(&[SortKey::desc("rank")])?;
+        let terms: Vec<String> = search_words(terms).map(normalize_word).collect();
+
+        let mut hits: Vec<TopicSearchHit> = self
+            .store()
+            .topics
+            .values()
+            .filter_map(|topic| {
+                let description = topic.topic_description.as_deref().unwrap_or_default();
+                let title_hits = count_matches(&topic.title, &terms);
+                let description_hits = count_matches(description, &terms);
+                // Every term has to appear somewhere, like websearch_to_tsquery's implicit AND
+                let all_found = terms.iter().all(|term| {
+                    search_words(&topic.title)
+                        .chain(search_words(description))
+                        .any(|word| normalize_word(word) == *term)
+                });
+                (!terms.is_empty() && all_found).then(|| TopicSearchHit {
+                    topic: topic.clone(),
+                    // Same A/B weights Postgres uses for title and description
+                    rank: title_hits as f32 + 0.4 * description_hits as f32,
+                    title_highlight: highlight(&topic.title, &terms),
+                    snippet: topic
+                        .topic_description
+                        .as_deref()
+                        .map(|description| highlight(description, &terms)),
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.rank.total_cmp(&a.rank).then(a.topic.id.cmp(&b.topic.id)));
+
+        let total = page.include_total.then_some(hits.len() as i64);
+        let hits = hits
+            .into_iter()
+            .skip(page.offset as usize)
+            .take(page.fetch_limit() as usize)
+            .collect();
+        Ok(Page::from_offset_rows(hits, page, total))
+    }
+
+    async fn get_topic_details(&self, topic_id: i32) -> Result<Topic, AppErrorType> {
+        self.store().topics.get(&topic_id).cloned().ok_or_else(|| {
+            AppErrorType::NotFoundError(format!("No topic found for topic_id: {}", topic_id))
+        })
+    }
+
+    async fn post_new_topic(&self, new_topic: CreateTopic) -> Result<Topic, AppErrorType> {
+        let mut store = self.store();
+        if !store.tutors.contains_key(&new_topic.tutor_id) {
+            return Err(AppErrorType::foreign_key_violation());
+        }
+
+        let current_time = Utc::now().naive_utc();
+        store.last_topic_id += 1;
+        let topic = Topic {
+            id: store.last_topic_id,
+            tutor_id: new_topic.tutor_id,
+            title: new_topic.title,
+            topic_description: new_topic.topic_description,
+            format: new_topic.format,
+            duration: new_topic.duration,
+            topic_level: new_topic.topic_level,
+            capacity: new_topic.capacity,
+            average_rating: None,
+            review_count: 0,
+            created_at: Some(current_time),
+            updated_at: Some(current_time),
+            version: 1,
+        };
+        store.topics.insert(topic.id, topic.clone());
+
+        Ok(topic)
+    }
+
+    async fn update_topic_details(
+        &self,
+        tutor_id: i32,
+        topic_id: i32,
+        update_topic: UpdateTopic,
+        if_match: Option<&[i32]>,
+    ) -> Result<Topic, AppErrorType> {
+        self.store()
+            .write_topic(tutor_id, topic_id, if_match, |topic| {
+                topic.title = update_topic.title;
+                topic.topic_description = update_topic.topic_description;
+                topic.format = update_topic.format;
+                topic.duration = update_topic.duration;
+                topic.topic_level = update_topic.topic_level;
+                topic.capacity = update_topic.capacity;
+            })
+    }
+
+    async fn patch_topic(
+        &self,
+        tutor_id: i32,
+        topic_id: i32,
+        patch: TopicPatch,
+        if_match: Option<&[i32]>,
+    ) -> Result<Topic, AppErrorType> {
+        self.store()
+            .write_topic(tutor_id, topic_id, if_match, |topic| patch.apply(topic))
+    }
+
+    async fn delete_topic(&self, tutor_id: i32, topic_id: i32) -> Result<String, AppErrorType> {
+        let mut store = self.store();
+        match store.topics.get(&topic_id) {
+            Some(topic) if topic.tutor_id == tutor_id => {
+                let topic = store.topics.remove(&topic_id).unwrap();
+                store
+                    .trashed_topics
+                    .insert(topic_id, (topic, Utc::now().naive_utc()));
+                store.refresh_ratings(tutor_id);
+                Ok(format!("Topic with id: {} deleted", topic_id))
+            }
+            _ => Err(AppErrorType::NotFoundError("Topic id not found".into())),
+        }
+    }
+}
+
+#[async_trait]
+impl TrashRepository for InMemoryRepository {
+    async fn list_trash(&self, retention: chrono::Duration) -> Result<Trash, AppErrorType> {
+        let store = self.store();
+        let mut tutors: Vec<TrashedTutor> = store
+            .trashed_tutors
+            .values()
+            .map(|(tutor, deleted_at)| TrashedTutor {
+                id: tutor.id,
+                first_name: tutor.first_name.clone(),
+                last_name: tutor.last_name.clone(),
+                email: tutor.email.clone(),
+                deleted_at: *deleted_at,
+                purge_at: *deleted_at + retention,
+            })
+            .collect();
+        let mut topics: Vec<TrashedTopic> = store
+            .trashed_topics
+            .values()
+            .map(|(topic, deleted_at)| TrashedTopic {
+                id: topic.id,
+                tutor_id: topic.tutor_id,
+                title: topic.title.clone(),
+                deleted_at: *deleted_at,
+                purge_at: *deleted_at + retention,
+            })
+            .collect();
+        // Same order as `list_trash_db`
+        tutors.sort_by_key(|tutor| (tutor.deleted_at, tutor.id));
+        topics.sort_by_key(|topic| (topic.deleted_at, topic.id));
+
+        Ok(Trash { tutors, topics })
+    }
+
+    async fn restore_tutor(&self, tutor_id: i32) -> Result<TutorRestoration, AppErrorType> {
+        let mut store = self.store();
+        let Some((tutor, deleted_at)) = store.trashed_tutors.get(&tutor_id).cloned() else {
+            return Err(AppErrorType::NotFoundError(
+                "Tutor id not found in trash".into(),
+            ));
+        };
+        // The email may have been registered again while the tutor was in the trash
+        if store.email_taken(&tutor.email, None) {
+            return Err(AppErrorType::unique_violation());
+        }
+
+        store.trashed_tutors.remove(&tutor_id);
+        store.tutors.insert(tutor_id, tutor);
+        let restored: Vec<i32> = store
+            .trashed_topics
+            .iter()
+            .filter(|(_, (topic, topic_deleted_at))| {
+                topic.tutor_id == tutor_id && *topic_deleted_at == deleted_at
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        let topics: Vec<TopicRef> = restored
+            .into_iter()
+            .map(|id| {
+                let (topic, _) = store.trashed_topics.remove(&id).unwrap();
+                let topic_ref = TopicRef {
+                    id,
+                    title: topic.title.clone(),
+                };
+                store.topics.insert(id, topic);
+                topic_ref
+            })
+            .collect();
+        store.refresh_ratings(tutor_id);
+
+        Ok(TutorRestoration {
+            tutor: store.tutors[&tutor_id].clone(),
+            topic_count: topics.len(),
+            topics,
+        })
+    }
+
+    async fn restore_topic(&self, topic_id: i32) -> Result<Topic, AppErrorType> {
+        let mut store = self.store();
+        let Some((topic, _)) = store.trashed_topics.get(&topic_id) else {
+            return Err(AppErrorType::NotFoundError(
+                "Topic id not found in trash".into(),
+            ));
+        };
+        if store.trashed_tutors.contains_key(&topic.tutor_id) {
+            return Err(AppErrorType::ConflictError(
+                "The topic's tutor is in the trash; restore the tutor first".into(),
+            ));
+        }
+
+        let (topic, _) = store.trashed_topics.remove(&topic_id).unwrap();
+        let tutor_id = topic.tutor_id;
+        store.topics.insert(topic_id, topic.clone());
+        store.refresh_ratings(tutor_id);
+        Ok(topic)
+    }
+
+    async fn purge_trash(&self, cutoff: NaiveDateTime) -> Result<PurgeSummary, AppErrorType> {
+        let mut store = self.store();
+        let Store {
+            topics,
+            trashed_tutors,
+            trashed_topics,
+            password_hashes,
+            enrollments,
+            sessions,
+            availability,
+            availability_exceptions,
+            bookings,
+            reviews,
+            ..
+        } = &mut *store;
+
+        let purged_tutors: Vec<i32> = trashed_tutors
+            .iter()
+            .filter(|(_, (_, deleted_at))| *deleted_at < cutoff)
+            .map(|(id, _)| *id)
+            .collect();
+        let topics_before = trashed_topics.len();
+        // A trashed topic's ratings are already off its tutor's totals
+        trashed_topics.retain(|_, (topic, deleted_at)| {
+            *deleted_at >= cutoff && !purged_tutors.contains(&topic.tutor_id)
+        });
+        for tutor_id in &purged_tutors {
+            trashed_tutors.remove(tutor_id);
+            password_hashes.remove(tutor_id);
+            availability.remove(tutor_id);
+        }
+        availability_exceptions.retain(|(tutor_id, _), _| !purged_tutors.contains(tutor_id));
+        bookings.retain(|_, booking| !purged_tutors.contains(&booking.tutor_id));
+        enrollments.retain(|(topic_id, _), _| {
+            topics.contains_key(topic_id) || trashed_topics.contains_key(topic_id)
+        });
+        sessions.retain(|_, (session, _)| {
+            topics.contains_key(&session.topic_id) || trashed_topics.contains_key(&session.topic_id)
+        });
+        reviews.retain(|_, review| {
+            topics.contains_key(&review.topic_id) || trashed_topics.contains_key(&review.topic_id)
+        });
+        Ok(PurgeSummary {
+            tutors: purged_tutors.len() as u64,
+            topics: (topics_before - trashed_topics.len()) as u64,
+        })
+    }
+}
+
+#[async_trait]
+impl CatalogueRepository for InMemoryRepository {
+    async fn export_catalogue(&self) -> Result<Vec<CatalogueRecord>, AppErrorType> {
+        let store = self.store();
+        let tutors = store.tutors.values().map(|tutor| CatalogueRecord {
+            kind: RecordKind::Tutor,
+            email: tutor.email.clone(),
+            first_name: Some(tutor.first_name.clone()),
+            last_name: Some(tutor.last_name.clone()),
+            title: None,
+            topic_description: None,
+            format: None,
+            duration: None,
+            topic_level: None,
+            capacity: None,
+        });
+        // Same order as `export_catalogue_db`
+        let mut topics: Vec<&Topic> = store.topics.values().collect();
+        topics.sort_by_key(|topic| (topic.tutor_id, topic.id));
+        let topics = topics.into_iter().map(|topic| CatalogueRecord {
+            kind: RecordKind::Topic,
+            email: store.tutors[&topic.tutor_id].email.clone(),
+            first_name: None,
+            last_name: None,
+            title: Some(topic.title.clone()),
+            topic_description: topic.topic_description.clone(),
+            format: topic.format.clone(),
+            duration: topic.duration.clone(),
+            topic_level: topic.topic_level.clone(),
+            capacity: topic.capacity,
+        });
+
+        Ok(tutors.chain(topics).collect())
+    }
+
+    // Rows are applied to a copy of the store, which replaces it only if the import commits
+    async fn import_catalogue(
+        &self,
+        rows: Vec<ImportRow>,
+        mode: ImportMode,
+    ) -> Result<ImportReport, AppErrorType> {
+        let mut store = self.store();
+        let mut staged = store.clone();
+        let reports: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                let result = match &row.item {
+                    Ok(item) => staged.import_item(item).map_err(row_errors),
+                    Err(errors) => Err(errors.clone()),
+                };
+                row.report(result)
+            })
+            .collect();
+
+        let committed = mode.commits(&reports);
+        if committed {
+            *store = staged;
+        }
+        Ok(ImportReport::new(mode, committed, reports))
+    }
+}
+
+impl Store {
+    fn tutor_by_email(&self, email: &str) -> Option<&Tutor> {
+        self.tutors
+            .values()
+            .find(|tutor| tutor.email.to_lowercase() == email.to_lowercase())
+    }
+
+    // Mirrors `import_tutor` and `import_topic`; a rejected row changes nothing
+    fn import_item(&mut self, item: &ImportItem) -> Result<(ImportOutcome, i32), AppErrorType> {
+        match item {
+            ImportItem::Tutor(update) => match self.tutor_by_email(&update.email).cloned() {
+                None => {
+                    self.last_tutor_id += 1;
+                    let tutor = Tutor {
+                        id: self.last_tutor_id,
+                        first_name: update.first_name.clone(),
+                        last_name: update.last_name.clone(),
+                        email: update.email.clone(),
+                        average_rating: None,
+                        review_count: 0,
+                        version: 1,
+                    };
+                    self.tutors.insert(tutor.id, tutor);
+                    Ok((ImportOutcome::Created, self.last_tutor_id))
+                }
+                Some(tutor)
+                    if tutor.first_name == update.first_name
+                        && tutor.last_name == update.last_name
+                        && tutor.email == update.email =>
+                {
+                    Ok((ImportOutcome::Skipped, tutor.id))
+                }
+                Some(tutor) => {
+                    self.write_tutor(tutor.id, None, |tutor| {
+                        tutor.first_name = update.first_name.clone();
+                        tutor.last_name = update.last_name.clone();
+                        tutor.email = update.email.clone();
+                    })?;
+                    Ok((ImportOutcome::Updated, tutor.id))
+                }
+            },
+            ImportItem::Topic {
+                tutor_email,
+                topic: update,
+            } => {
+                let tutor_id = self
+                    .tutor_by_email(tutor_email)
+                    .map(|tutor| tutor.id)
+                    .ok_or_else(|| {
+                        AppErrorType::UnprocessableError(format!(
+                            "No tutor with email {}",
+                            tutor_email
+                        ))
+                    })?;
+                let existing = self
+                    .topics
+                    .values()
+                    .find(|topic| {
+                        topic.tutor_id == tutor_id
+                            && topic.title.to_lowercase() == update.title.to_lowercase()
+                    })
+                    .cloned();
+
+                match existing {
+                    None => {
+                        let current_time = Utc::now().naive_utc();
+                        self.last_topic_id += 1;
+                        let topic = Topic {
+                            id: self.last_topic_id,
+                            tutor_id,
+                            title: update.title.clone(),
+                            topic_description: update.topic_description.clone(),
+                            format: update.format.clone(),
+                            duration: update.duration.clone(),
+                            topic_level: update.topic_level.clone(),
+                            capacity: update.capacity,
+                            average_rating: None,
+                            review_count: 0,
+                            created_at: Some(current_time),
+                            updated_at: Some(current_time),
+                            version: 1,
+                        };
+                        self.topics.insert(topic.id, topic);
+                        Ok((ImportOutcome::Created, self.last_topic_id))
+                    }
+                    Some(topic)
+                        if topic.title == update.title
+                            && topic.topic_description == update.topic_description
+                            && topic.format == update.format
+                            && topic.duration == update.duration
+                            && topic.topic_level == update.topic_level
+                            && topic.capacity == update.capacity =>
+                    {
+                        Ok((ImportOutcome::Skipped, topic.id))
+                    }
+                    Some(topic) => {
+                        self.write_topic(tutor_id, topic.id, None, |topic| {
+                            topic.title = update.title.clone();
+                            topic.topic_description = update.topic_description.clone();
+                            topic.format = update.format.clone();
+                            topic.duration = update.duration.clone();
+                            topic.topic_level = update.topic_level.clone();
+                            topic.capacity = update.capacity;
+                        })?;
+                        Ok((ImportOutcome::Updated, topic.id))
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StudentRepository for InMemoryRepository {
+    async fn get_all_students(&self, page: &PageRequest) -> Result<Page<Student>, AppErrorType> {
+        let students = self.store().students.values().cloned().collect();
+
+        paginate(
+            students,
+            &[SortKey::asc("id")],
+            page,
+            |student| student.id,
+            |student, _column| ColumnValue::Int(student.id),
+        )
+    }
+
+    async fn post_new_student(
+        &self,
+        new_student: CreateStudent,
+        password_hash: Option<String>,
+    ) -> Result<Student, AppErrorType> {
+        let mut store = self.store();
+        let email = new_student.email.to_lowercase();
+        if store
+            .students
+            .values()
+            .any(|student| student.email.to_lowercase() == email)
+        {
+            return Err(AppErrorType::unique_violation());
+        }
+        store.last_student_id += 1;
+        let student = Student {
+            id: store.last_student_id,
+            first_name: new_student.first_name,
+            last_name: new_student.last_name,
+            email: new_student.email,
+        };
+        store.students.insert(student.id, student.clone());
+        if let Some(password_hash) = password_hash {
+            store
+                .student_password_hashes
+                .insert(student.id, password_hash);
+        }
+
+        Ok(student)
+    }
+
+    async fn get_student_details(&self, student_id: i32) -> Result<Student, AppErrorType> {
+        self.store()
+            .students
+            .get(&student_id)
+            .cloned()
+            .ok_or_else(|| AppErrorType::NotFoundError("Student id not found".into()))
+    }
+
+    async fn delete_student(&self, student_id: i32) -> Result<String, AppErrorType> {
+        let mut store = self.store();
+        store
+            .students
+            .remove(&student_id)
+            .ok_or_else(|| AppErrorType::NotFoundError("Student id not found".into()))?;
+        store.student_password_hashes.remove(&student_id);
+        store
+            .enrollments
+            .retain(|(_, enrolled_student_id), _| *enrolled_student_id != student_id);
+        store
+            .bookings
+            .retain(|_, booking| booking.student_id != student_id);
+        let rated_tutors: BTreeSet<i32> = store
+            .reviews
+            .values()
+            .filter(|review| review.student_id == student_id)
+            .filter_map(|review| store.topic_tutor(review.topic_id))
+            .collect();
+        store
+            .reviews
+            .retain(|_, review| review.student_id != student_id);
+        for tutor_id in rated_tutors {
+            store.refresh_ratings(tutor_id);
+        }
+
+        Ok(format!("Student with id: {} deleted", student_id))
+    }
+
+    async fn enroll_student(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, AppErrorType> {
+        let mut store = self.store();
+        let capacity = store
+            .topics
+            .get(&topic_id)
+            .ok_or_else(|| AppErrorType::NotFoundError("Topic id not found".into()))?
+            .capacity;
+        if store.enrollments.contains_key(&(topic_id, student_id)) {
+            return Err(AppErrorType::ConflictError(
+                "The student is already enrolled in this topic".into(),
+            ));
+        }
+        let enrolled = store
+            .enrollments
+            .keys()
+            .filter(|(enrolled_topic_id, _)| *enrolled_topic_id == topic_id)
+            .count();
+        check_capacity(capacity, enrolled as i64)?;
+        if !store.students.contains_key(&student_id) {
+            return Err(AppErrorType::foreign_key_violation());
+        }
+
+        let enrolled_at = Utc::now().naive_utc();
+        store
+            .enrollments
+            .insert((topic_id, student_id), enrolled_at);
+
+        Ok(Enrollment {
+            topic_id,
+            student_id,
+            enrolled_at,
+        })
+    }
+
+    async fn unenroll_student(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+    ) -> Result<String, AppErrorType> {
+        self.store()
+            .enrollments
+            .remove(&(topic_id, student_id))
+            .ok_or_else(|| AppErrorType::NotFoundError("Enrollment not found".into()))?;
+
+        Ok(format!(
+            "Student with id: {} unenrolled from topic {}",
+            student_id, topic_id
+        ))
+    }
+
+    async fn get_topic_roster(
+        &self,
+        topic_id: i32,
+        page: &PageRequest,
+    ) -> Result<Roster, AppErrorType> {
+        let store = self.store();
+        let topic = store
+            .topics
+            .get(&topic_id)
+            .ok_or_else(|| AppErrorType::NotFoundError("Topic id not found".into()))?;
+
+        let students: Vec<EnrolledStudent> = store
+            .enrollments
+            .iter()
+            .filter(|((enrolled_topic_id, _), _)| *enrolled_topic_id == topic_id)
+            .map(|((_, student_id), enrolled_at)| EnrolledStudent {
+                student: store.students[student_id].clone(),
+                enrolled_at: *enrolled_at,
+            })
+            .collect();
+
+        Ok(Roster {
+            topic_id,
+            capacity: topic.capacity,
+            enrolled: students.len() as i64,
+            students: paginate(
+                students,
+                &enrollment_order(),
+                page,
+                |enrolled| enrolled.student.id,
+                |enrolled, column| match column {
+                    "enrolled_at" => ColumnValue::Time(Some(enrolled.enrolled_at)),
+                    _ => ColumnValue::Int(enrolled.student.id),
+                },
+            )?,
+        })
+    }
+
+    async fn get_topics_for_student(
+        &self,
+        student_id: i32,
+        page: &PageRequest,
+    ) -> Result<Page<EnrolledTopic>, AppErrorType> {
+        let store = self.store();
+        if !store.students.contains_key(&student_id) {
+            return Err(AppErrorType::NotFoundError("Student id not found".into()));
+        }
+
+        let topics: Vec<EnrolledTopic> = store
+            .enrollments
+            .iter()
+            .filter(|((_, enrolled_student_id), _)| *enrolled_student_id == student_id)
+            .filter_map(|((topic_id, _), enrolled_at)| {
+                store.topics.get(topic_id).map(|topic| EnrolledTopic {
+                    topic: topic.clone(),
+                    enrolled_at: *enrolled_at,
+                })
+            })
+            .collect();
+
+        paginate(
+            topics,
+            &enrollment_order(),
+            page,
+            |enrolled| enrolled.topic.id,
+            |enrolled, column| match column {
+                "enrolled_at" => ColumnValue::Time(Some(enrolled.enrolled_at)),
+                _ => ColumnValue::Int(enrolled.topic.id),
+            },
+        )
+    }
+
+    async fn get_student_credentials(
+        &self,
+        email: &str,
+    ) -> Result<Option<StudentCredentials>, AppErrorType> {
+        let store = self.store();
+        let credentials = store
+            .students
+            .values()
+            .find(|student| student.email.to_lowercase() == email.to_lowercase())
+            .map(|student| StudentCredentials {
+                student_id: student.id,
+                password_hash: store.student_password_hashes.get(&student.id).cloned(),
+            });
+
+        Ok(credentials)
+    }
+}
+
+#[async_trait]
+impl SessionRepository for InMemoryRepository {
+    async fn get_sessions_for_topic(&self, topic_id: i32) -> Result<Vec<Session>, AppErrorType> {
+        let store = self.store();
+        store.live_topic_tutor(topic_id)?;
+
+        Ok(store
+            .sessions
+            .values()
+            .filter(|(session, _)| session.topic_id == topic_id)
+            .map(|(session, _)| session.clone())
+            .collect())
+    }
+
+    async fn post_new_session(
+        &self,
+        topic_id: i32,
+        schedule: SessionSchedule,
+    ) -> Result<Session, AppErrorType> {
+        let occurrences = schedule.occurrences()?;
+        let mut store = self.store();
+        let tutor_id = store.live_topic_tutor(topic_id)?;
+        store.check_overlaps(tutor_id, None, &occurrences)?;
+
+        let current_time = Utc::now().naive_utc();
+        store.last_session_id += 1;
+        let session = Session {
+            id: store.last_session_id,
+            topic_id,
+            tutor_id,
+            starts_at: schedule.starts_at,
+            ends_at: schedule.ends_at,
+            time_zone: schedule.time_zone,
+            recurrence: schedule.recurrence,
+            created_at: current_time,
+            updated_at: current_time,
+        };
+        store
+            .sessions
+            .insert(session.id, (session.clone(), occurrences));
+
+        Ok(session)
+    }
+
+    async fn update_session(
+        &self,
+        topic_id: i32,
+        session_id: i32,
+        schedule: SessionSchedule,
+    ) -> Result<Session, AppErrorType> {
+        let occurrences = schedule.occurrences()?;
+        let mut store = self.store();
+        let tutor_id = store.live_topic_tutor(topic_id)?;
+        match store.sessions.get(&session_id) {
+            Some((session, _)) if session.topic_id == topic_id => {}
+            _ => return Err(AppErrorType::NotFoundError("Session id not found".into())),
+        }
+        store.check_overlaps(tutor_id, Some(session_id), &occurrences)?;
+
+        let (session, stored) = store.sessions.get_mut(&session_id).unwrap();
+        session.starts_at = schedule.starts_at;
+        session.ends_at = schedule.ends_at;
+        session.time_zone = schedule.time_zone;
+        session.recurrence = schedule.recurrence;
+        session.updated_at = Utc::now().naive_utc();
+        *stored = occurrences;
+
+        Ok(session.clone())
+    }
+
+    async fn delete_session(&self, topic_id: i32, session_id: i32) -> Result<String, AppErrorType> {
+        let mut store = self.store();
+        match store.sessions.get(&session_id) {
+            Some((session, _)) if session.topic_id == topic_id => {
+                store.sessions.remove(&session_id);
+                Ok(format!("Session with id: {} deleted", session_id))
+            }
+            _ => Err(AppErrorType::NotFoundError("Session id not found".into())),
+        }
+    }
+
+    async fn get_occurrences_for_topic(
+        &self,
+        topic_id: i32,
+        interval: Interval,
+    ) -> Result<Vec<Occurrence>, AppErrorType> {
+        let store = self.store();
+        store.live_topic_tutor(topic_id)?;
+
+        let mut occurrences: Vec<Occurrence> = store
+            .sessions
+            .values()
+            .filter(|(session, _)| session.topic_id == topic_id)
+            .flat_map(|(session, occurrences)| {
+                occurrences
+                    .iter()
+                    .filter(|occurrence| occurrence.overlaps(&interval))
+                    .map(|occurrence| session.occurrence(*occurrence))
+            })
+            .collect();
+        occurrences.sort_by_key(|occurrence| (occurrence.starts_at, occurrence.session_id));
+
+        Ok(occurrences)
+    }
+
+    async fn get_calendar_for_tutor(&self, tutor_id: i32) -> Result<Calendar, AppErrorType> {
+        let store = self.store();
+        let tutor = store
+            .tutors
+            .get(&tutor_id)
+            .ok_or_else(|| AppErrorType::NotFoundError("Tutor id not found".into()))?;
+
+        Ok(Calendar {
+            name: format!("{} {}", tutor.first_name, tutor.last_name),
+            events: store.calendar_events(|topic| topic.tutor_id == tutor_id),
+        })
+    }
+
+    async fn get_calendar_for_topic(&self, topic_id: i32) -> Result<Calendar, AppErrorType> {
+        let store = self.store();
+        let topic = store
+            .topics
+            .get(&topic_id)
+            .ok_or_else(|| AppErrorType::NotFoundError("Topic id not found".into()))?;
+
+        Ok(Calendar {
+            name: topic.title.clone(),
+            events: store.calendar_events(|topic| topic.id == topic_id),
+        })
+    }
+}
+
+impl Store {
+    // The tutor of a topic, whether or not it is in the trash
+    fn topic_tutor(&self, topic_id: i32) -> Option<i32> {
+        self.topics
+            .get(&topic_id)
+            .or_else(|| self.trashed_topics.get(&topic_id).map(|(topic, _)| topic))
+            .map(|topic| topic.tutor_id)
+    }
+
+    // Recomputes the totals of the tutor and their topics from the reviews. The tutor's cover
+    // only their live topics. Like the running totals in Postgres, only those whose totals
+    // moved get a new version.
+    fn refresh_ratings(&mut self, tutor_id: i32) {
+        let mut topic_totals: BTreeMap<i32, (i64, i64)> = BTreeMap::new();
+        for review in self.reviews.values() {
+            let totals = topic_totals.entry(review.topic_id).or_default();
+            totals.0 += i64::from(review.rating);
+            totals.1 += 1;
+        }
+
+        let mut tutor_totals = (0, 0);
+        let topics = self.topics.values_mut().map(|topic| (topic, true)).chain(
+            self.trashed_topics
+                .values_mut()
+                .map(|(topic, _)| (topic, false)),
+        );
+        for (topic, live) in topics.filter(|(topic, _)| topic.tutor_id == tutor_id) {
+            let (rating_sum, review_count) =
+                topic_totals.get(&topic.id).copied().unwrap_or_default();
+            if live {
+                tutor_totals.0 += rating_sum;
+                tutor_totals.1 += review_count;
+            }
+            set_rating(
+                &mut topic.average_rating,
+                &mut topic.review_count,
+                &mut topic.version,
+                (rating_sum, review_count),
+            );
+        }
+        let tutor = self.tutors.get_mut(&tutor_id).or_else(|| {
+            self.trashed_tutors
+                .get_mut(&tutor_id)
+                .map(|(tutor, _)| tutor)
+        });
+        if let Some(tutor) = tutor {
+            set_rating(
+                &mut tutor.average_rating,
+                &mut tutor.review_count,
+                &mut tutor.version,
+                tutor_totals,
+            );
+        }
+    }
+
+    // The sessions of the live topics that match, in id order
+    fn calendar_events(&self, matches: impl Fn(&Topic) -> bool) -> Vec<CalendarEvent> {
+        self.sessions
+            .values()
+            .filter_map(|(session, occurrences)| {
+                let topic = self
+                    .topics
+                    .get(&session.topic_id)
+                    .filter(|topic| matches(topic))?;
+                Some(CalendarEvent {
+                    session: session.clone(),
+                    topic: topic.clone(),
+                    occurrences: occurrences.clone(),
+                })
+            })
+            .collect()
+    }
+
+    fn live_topic_tutor(&self, topic_id: i32) -> Result<i32, AppErrorType> {
+        self.topics
+            .get(&topic_id)
+            .map(|topic| topic.tutor_id)
+            .ok_or_else(|| AppErrorType::NotFoundError("Topic id not found".into()))
+    }
+
+    // The checks the `session_occurrence_no_overlap` constraint and `check_bookings` make in
+    // Postgres
+    fn check_overlaps(
+        &self,
+        tutor_id: i32,
+        except_session_id: Option<i32>,
+        occurrences: &[Interval],
+    ) -> Result<(), AppErrorType> {
+        let overlapping = self
+            .sessions
+            .values()
+            .filter(|(session, _)| {
+                session.tutor_id == tutor_id && Some(session.id) != except_session_id
+            })
+            .flat_map(|(_, existing)| existing.iter().copied())
+            .chain(self.tutor_bookings(tutor_id))
+            .any(|existing| {
+                occurrences
+                    .iter()
+                    .any(|occurrence| occurrence.overlaps(&existing))
+            });
+        if overlapping {
+            return Err(AppErrorType::exclusion_violation());
+        }
+        Ok(())
+    }
+
+    fn live_tutor(&self, tutor_id: i32) -> Result<(), AppErrorType> {
+        if !self.tutors.contains_key(&tutor_id) {
+            return Err(AppErrorType::NotFoundError("Tutor id not found".into()));
+        }
+        Ok(())
+    }
+
+    fn tutor_bookings(&self, tutor_id: i32) -> impl Iterator<Item = Interval> + '_ {
+        self.bookings
+            .values()
+            .filter(move |booking| booking.tutor_id == tutor_id)
+            .map(Booking::interval)
+    }
+
+    fn tutor_occurrences(&self, tutor_id: i32) -> impl Iterator<Item = Interval> + '_ {
+        self.sessions
+            .values()
+            .filter(move |(session, _)| session.tutor_id == tutor_id)
+            .flat_map(|(_, occurrences)| occurrences.iter().copied())
+    }
+
+    fn availability(&self, tutor_id: i32) -> Availability {
+        let mut availability = Availability::none(tutor_id);
+        if let Some((time_zone, windows)) = self.availability.get(&tutor_id) {
+            availability.time_zone = time_zone.clone();
+            availability.windows = windows.clone();
+        }
+        availability.exceptions = self
+            .availability_exceptions
+            .range((tutor_id, NaiveDate::MIN)..=(tutor_id, NaiveDate::MAX))
+            .map(|((_, date), windows)| AvailabilityException {
+                date: *date,
+                windows: windows.clone(),
+            })
+            .collect();
+        availability
+    }
+}
+
+#[async_trait]
+impl AvailabilityRepository for InMemoryRepository {
+    async fn get_availability(&self, tutor_id: i32) -> Result<Availability, AppErrorType> {
+        let store = self.store();
+        store.live_tutor(tutor_id)?;
+
+        Ok(store.availability(tutor_id))
+    }
+
+    async fn put_weekly_availability(
+        &self,
+        tutor_id: i32,
+        weekly: WeeklyAvailability,
+    ) -> Result<Availability, AppErrorType> {
+        let mut store = self.store();
+        store.live_tutor(tutor_id)?;
+
+        let mut windows = weekly.windows;
+        windows.sort_by_key(|window| (window.weekday, window.starts_at));
+        store
+            .availability
+            .insert(tutor_id, (weekly.time_zone, windows));
+
+        Ok(store.availability(tutor_id))
+    }
+
+    async fn put_availability_exception(
+        &self,
+        tutor_id: i32,
+        date: NaiveDate,
+        mut windows: Vec<TimeWindow>,
+    ) -> Result<AvailabilityException, AppErrorType> {
+        let mut store = self.store();
+        store.live_tutor(tutor_id)?;
+
+        windows.sort_by_key(|window| window.starts_at);
+        store
+            .availability_exceptions
+            .insert((tutor_id, date), windows.clone());
+
+        Ok(AvailabilityException { date, windows })
+    }
+
+    async fn delete_availability_exception(
+        &self,
+        tutor_id: i32,
+        date: NaiveDate,
+    ) -> Result<String, AppErrorType> {
+        self.store()
+            .availability_exceptions
+            .remove(&(tutor_id, date))
+            .ok_or_else(|| {
+                AppErrorType::NotFoundError("Availability exception not found".into())
+            })?;
+
+        Ok(format!("Availability exception on {} deleted", date))
+    }
+
+    async fn get_free_slots(
+        &self,
+        tutor_id: i32,
+        range: Interval,
+        length: chrono::Duration,
+    ) -> Result<Vec<Slot>, AppErrorType> {
+        let store = self.store();
+        store.live_tutor(tutor_id)?;
+
+        let busy: Vec<Interval> = store
+            .tutor_bookings(tutor_id)
+            .chain(store.tutor_occurrences(tutor_id))
+            .collect();
+        Ok(store
+            .availability(tutor_id)
+            .free_slots(range, length, &busy, Utc::now().naive_utc()))
+    }
+
+    async fn get_bookings(
+        &self,
+        tutor_id: i32,
+        range: Interval,
+    ) -> Result<Vec<Booking>, AppErrorType> {
+        let store = self.store();
+        store.live_tutor(tutor_id)?;
+
+        let mut bookings: Vec<Booking> = store
+            .bookings
+            .values()
+            .filter(|booking| booking.tutor_id == tutor_id && booking.interval().overlaps(&range))
+            .cloned()
+            .collect();
+        bookings.sort_by_key(|booking| booking.starts_at);
+
+        Ok(bookings)
+    }
+
+    // The store lock makes the checks and the insert one step, as the tutor lock and the
+    // exclusion constraint do in Postgres
+    async fn book_slot(
+        &self,
+        tutor_id: i32,
+        student_id: i32,
+        booking: NewBooking,
+    ) -> Result<Booking, AppErrorType> {
+        let current_time = Utc::now().naive_utc();
+        let interval = booking.interval(current_time)?;
+        let mut store = self.store();
+        store.live_tutor(tutor_id)?;
+
+        if !store.availability(tutor_id).covers(interval) {
+            return Err(AppErrorType::ConflictError(
+                "The time is outside the tutor's availability".into(),
+            ));
+        }
+        if store
+            .tutor_occurrences(tutor_id)
+            .chain(store.tutor_bookings(tutor_id))
+            .any(|busy| busy.overlaps(&interval))
+        {
+            return Err(AppErrorType::exclusion_violation());
+        }
+        if !store.students.contains_key(&student_id) {
+            return Err(AppErrorType::foreign_key_violation());
+        }
+
+        store.last_booking_id += 1;
+        let booking = Booking {
+            id: store.last_booking_id,
+            tutor_id,
+            student_id,
+            starts_at: interval.starts_at,
+            ends_at: interval.ends_at,
+            created_at: current_time,
+        };
+        store.bookings.insert(booking.id, booking.clone());
+
+        Ok(booking)
+    }
+
+    async fn cancel_booking(
+        &self,
+        tutor_id: i32,
+        booking_id: i32,
+        student_id: Option<i32>,
+    ) -> Result<String, AppErrorType> {
+        let mut store = self.store();
+        match store.bookings.get(&booking_id) {
+            Some(booking)
+                if booking.tutor_id == tutor_id
+                    && student_id.is_none_or(|student_id| booking.student_id == student_id) =>
+            {
+                store.bookings.remove(&booking_id);
+                Ok(format!("Booking with id: {} cancelled", booking_id))
+            }
+            _ => Err(AppErrorType::NotFoundError("Booking id not found".into())),
+        }
+    }
+}
+
+#[async_trait]
+impl ReviewRepository for InMemoryRepository {
+    async fn get_reviews_for_topic(
+        &self,
+        topic_id: i32,
+        page: &PageRequest,
+    ) -> Result<Page<Review>, AppErrorType> {
+        let store = self.store();
+        store.live_topic_tutor(topic_id)?;
+        let reviews = store
+            .reviews
+            .values()
+            .filter(|review| review.topic_id == topic_id)
+            .cloned()
+            .collect();
+
+        paginate(
+            reviews,
+            &[SortKey::asc("id")],
+            page,
+            |review| review.id,
+            |review, _column| ColumnValue::Int(review.id),
+        )
+    }
+
+    async fn put_review(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+        review: WriteReview,
+    ) -> Result<Review, AppErrorType> {
+        let mut store = self.store();
+        let tutor_id = store.live_topic_tutor(topic_id)?;
+        check_enrolled(store.enrollments.contains_key(&(topic_id, student_id)))?;
+
+        let current_time = Utc::now().naive_utc();
+        let existing = store
+            .reviews
+            .values()
+            .find(|existing| existing.topic_id == topic_id && existing.student_id == student_id)
+            .map(|existing| (existing.id, existing.created_at));
+        let (id, created_at) = existing.unwrap_or_else(|| {
+            store.last_review_id += 1;
+            (store.last_review_id, current_time)
+        });
+        let review = Review {
+            id,
+            topic_id,
+            student_id,
+            rating: review.rating,
+            body: review.body,
+            created_at,
+            updated_at: current_time,
+        };
+        store.reviews.insert(id, review.clone());
+        store.refresh_ratings(tutor_id);
+
+        Ok(review)
+    }
+
+    async fn delete_review(&self, topic_id: i32, student_id: i32) -> Result<String, AppErrorType> {
+        let mut store = self.store();
+        let tutor_id = store.live_topic_tutor(topic_id)?;
+        let review_id = store
+            .reviews
+            .values()
+            .find(|review| review.topic_id == topic_id && review.student_id == student_id)
+            .map(|review| review.id)
+            .ok_or_else(|| AppErrorType::NotFoundError("Review not found".into()))?;
+        store.reviews.remove(&review_id);
+        store.refresh_ratings(tutor_id);
+
+        Ok(format!("Review with id: {} deleted", review_id))
+    }
+}
+
+#[async_trait]
+impl HealthRepository for InMemoryRepository {
+    // There is no pool or schema; the store is healthy as long as its lock is usable
+    async fn database_health(&self, _timeout: Duration) -> DatabaseHealth {
+        let healthy = !self.store.is_poisoned();
+        DatabaseHealth {
+            status: if healthy {
+                HealthStatus::Ok
+            } else {
+                HealthStatus::Degraded
+            },
+            backend: "memory".into(),
+            latency_ms: Some(0),
+            error: (!healthy).then(|| "The in-memory store is poisoned".to_string()),
+            pool: None,
+            migration_version: None,
+        }
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+}
+
+impl InMemoryRepository {
+    fn filter_tutors(&self, filter: &TutorFilter) -> Vec<Tutor> {
+        let prefix = filter.last_name_prefix.as_deref().map(str::to_lowercase);
+        self.store()
+            .tutors
+            .values()
+            .filter(|tutor| {
+                prefix
+                    .as_deref()
+                    .is_none_or(|prefix| tutor.last_name.to_lowercase().starts_with(prefix))
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn filter_topics(&self, filter: &TopicFilter) -> Vec<Topic> {
+        self.store()
+            .topics
+            .values()
+            .filter(|topic| filter.tutor_id.is_none_or(|id| topic.tutor_id == id))
+            .filter(|topic| {
+                filter
+                    .topic_level
+                    .as_ref()
+                    .is_none_or(|level| topic.topic_level.as_ref() == Some(level))
+            })
+            .filter(|topic| {
+                filter
+                    .format
+                    .as_ref()
+                    .is_none_or(|format| topic.format.as_ref() == Some(format))
+            })
+            .filter(|topic| {
+                filter
+                    .created_after
+                    .is_none_or(|after| topic.created_at.is_some_and(|at| at >= after))
+            })
+            .filter(|topic| {
+                filter
+                    .created_before
+                    .is_none_or(|before| topic.created_at.is_some_and(|at| at < before))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+fn search_words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+// A very small stand-in for the english stemmer: case-folded, plural "s" dropped
+fn normalize_word(word: &str) -> String {
+    let word = word.to_lowercase();
+    match word.strip_suffix('s') {
+        Some(stem) if stem.len() > 2 => stem.to_string(),
+        _ => word,
+    }
+}
+
+fn count_matches(text: &str, terms: &[String]) -> usize {
+    search_words(text)
+        .filter(|word| terms.contains(&normalize_word(word)))
+        .count()
+}
+
+fn highlight(text: &str, terms: &[String]) -> String {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut word_start = None;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_alphanumeric() && index < text.len(), word_start) {
+            (true, None) => word_start = Some(index),
+            (false, Some(start)) => {
+                let word = &text[start..index];
+                if terms.contains(&normalize_word(word)) {
+                    highlighted.push_str(&format!("<b>{}</b>", word));
+                } else {
+                    highlighted.push_str(word);
+                }
+                word_start = None;
+            }
+            _ => {}
+        }
+        if word_start.is_none() && index < text.len() {
+            highlighted.push(c);
+        }
+    }
+    highlighted
+}
+
+// Sortable column values; `None` plays the part of SQL NULL
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum ColumnValue {
+    Int(i32),
+    Text(Option<String>),
+    Time(Option<NaiveDateTime>),
+}
+
+fn tutor_column(tutor: &Tutor, column: &str) -> ColumnValue {
+    match column {
+        "first_name" => ColumnValue::Text(Some(tutor.first_name.clone())),
+        "last_name" => ColumnValue::Text(Some(tutor.last_name.clone())),
+        "email" => ColumnValue::Text(Some(tutor.email.clone())),
+        _ => ColumnValue::Int(tutor.id),
+    }
+}
+
+fn topic_column(topic: &Topic, column: &str) -> ColumnValue {
+    match column {
+        "tutor_id" => ColumnValue::Int(topic.tutor_id),
+        "title" => ColumnValue::Text(Some(topic.title.clone())),
+        "format" => ColumnValue::Text(topic.format.clone()),
+        "topic_level" => ColumnValue::Text(topic.topic_level.clone()),
+        "created_at" => ColumnValue::Time(topic.created_at),
+        "updated_at" => ColumnValue::Time(topic.updated_at),
+        _ => ColumnValue::Int(topic.id),
+    }
+}
+
+impl ColumnValue {
+    fn is_null(&self) -> bool {
+        matches!(self, ColumnValue::Text(None) | ColumnValue::Time(None))
+    }
+}
+
+// Postgres puts NULLs last when ascending and first when descending
+fn compare_columns(a: &ColumnValue, b: &ColumnValue) -> Ordering {
+    match (a.is_null(), b.is_null()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.cmp(b),
+    }
+}
+
+// Same ordering, keyset and offset rules as the ORDER BY/LIMIT/OFFSET queries in Postgres
+fn sort_rows<T>(rows: &mut [T], sort: &[SortKey], column_of: impl Fn(&T, &str) -> ColumnValue) {
+    rows.sort_by(|a, b| {
+        sort.iter()
+            .map(|key| {
+                let ordering =
+                    compare_columns(&column_of(a, key.column), &column_of(b, key.column));
+                if key.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+fn paginate<T>(
+    mut rows: Vec<T>,
+    sort: &[SortKey],
+    page: &PageRequest,
+    id_of: impl Fn(&T) -> i32,
+    column_of: impl Fn(&T, &str) -> ColumnValue,
+) -> Result<Page<T>, AppErrorType> {
+    let keyset = page.keyset_order(sort)?;
+    let total = page.include_total.then_some(rows.len() as i64);
+
+    sort_rows(&mut rows, sort, column_of);
+    if let (Some(descending), Some(after_id)) = (keyset, page.after_id) {
+        rows.retain(|row| {
+            if descending {
+                id_of(row) < after_id
+            } else {
+                id_of(row) > after_id
+            }
+        });
+    }
+    let rows: Vec<T> = rows
+        .into_iter()
+        .skip(page.offset as usize)
+        .take(page.fetch_limit() as usize)
+        .collect();
+
+    Ok(match keyset {
+        Some(_) => Page::from_rows(rows, page, total, id_of),
+        None => Page::from_offset_rows(rows, page, total),
+    })
+}
+
+fn set_rating(
+    average: &mut Option<f64>,
+    count: &mut i32,
+    version: &mut i32,
+    (rating_sum, review_count): (i64, i64),
+) {
+    let new_average = average_rating(rating_sum, review_count);
+    if i64::from(*count) != review_count || *average != new_average {
+        *average = new_average;
+        *count = review_count as i32;
+        *version += 1;
+    }
+}
+
+// Argon2 hash of "tutor-password", the seed tutors' password in src/sql_scripts/seed.sql
+#[cfg(test)]
+pub const SEED_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$NEN/FmCnhzP4E+l8QS/3Ng$jaF7YvaF/92HiT0Ms5BT9hpp2faNr9+/YcId4q4k/rg";
+
+// Argon2 hash of "student-password", the seed students' password
+#[cfg(test)]
+pub const SEED_STUDENT_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$oCo6y+R6MSamRE3JCKVFuA$JWmob/22n9frB8auk9k7Ina1OIDbuOYcXLrvxuN1QXA";
+
+#[cfg(test)]
+impl InMemoryRepository {
+    // Same rows as src/sql_scripts/seed.sql
+    pub async fn with_seed_data() -> Self {
+        let repo = InMemoryRepository::new();
+        for (first_name, last_name, email) in [
+            ("Mark", "Smith", "mark@fakemail.com"),
+            ("Frank", "Jones", "fjones23@fakemail.com"),
+            ("Bob", "Lopez", "bob.lopez@fakemail.com"),
+        ] {
+            repo.post_new_tutor(
+                CreateTutor {
+                    first_name: first_name.into(),
+                    last_name: last_name.into(),
+                    email: email.into(),
+                    password: String::new(),
+                },
+                SEED_PASSWORD_HASH.into(),
+            )
+            .await
+            .unwrap();
+        }
+        for (tutor_id, title, format, topic_level) in [
+            (1, "Traits", None, Some("Beginner")),
+            (2, "Lifetimes", Some("ebook"), None),
+            (3, "Concurrency", Some("video"), None),
+            (3, "Strings", Some("video"), None),
+        ] {
+            repo.post_new_topic(CreateTopic {
+                title: title.into(),
+                tutor_id,
+                topic_description: None,
+                format: format.map(String::from),
+                duration: None,
+                topic_level: topic_level.map(String::from),
+                capacity: None,
+            })
+            .await
+            .unwrap();
+        }
+        for (first_name, last_name, email) in [
+            ("Alice", "Nguyen", "alice.nguyen@fakemail.com"),
+            ("Raj", "Patel", "raj.patel@fakemail.com"),
+        ] {
+            repo.post_new_student(
+                CreateStudent {
+                    first_name: first_name.into(),
+                    last_name: last_name.into(),
+                    email: email.into(),
+                },
+                Some(SEED_STUDENT_PASSWORD_HASH.into()),
+            )
+            .await
+            .unwrap();
+        }
+        repo.enroll_student(1, 1).await.unwrap();
+        repo
+    }
+}
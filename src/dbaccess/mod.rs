@@ -1,2 +1,314 @@
+pub mod availability;
+pub mod catalogue;
+pub mod health;
+pub mod memory;
+pub mod postgres;
+pub mod review;
+pub mod session;
+pub mod student;
 pub mod topic;
+pub mod trash;
 pub mod tutor;
+
+use crate::errors::AppErrorType;
+use crate::models::auth::{StudentCredentials, TutorCredentials};
+use crate::models::availability::{
+    Availability, AvailabilityException, Booking, NewBooking, Slot, TimeWindow, WeeklyAvailability,
+};
+use crate::models::calendar::Calendar;
+use crate::models::catalogue::{CatalogueRecord, ImportMode, ImportReport, ImportRow};
+use crate::models::health::{DatabaseHealth, PoolStats};
+use crate::models::page::{Page, PageRequest};
+use crate::models::review::{Review, WriteReview};
+use crate::models::session::{Interval, Occurrence, Session, SessionSchedule};
+use crate::models::student::{CreateStudent, EnrolledTopic, Enrollment, Roster, Student};
+use crate::models::topic::{
+    CreateTopic, Topic, TopicFilter, TopicPatch, TopicSearchHit, UpdateTopic,
+};
+use crate::models::trash::{PurgeSummary, Trash, TutorRestoration};
+use crate::models::tutor::{
+    CreateTutor, Tutor, TutorDeletion, TutorFilter, TutorPatch, UpdateTutor,
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use futures::stream::BoxStream;
+use std::time::Duration;
+
+// A whole listing, read from the database as the consumer polls for rows
+pub type RowStream<T> = BoxStream<'static, Result<T, AppErrorType>>;
+
+// Each `*_db` function runs in its own span; sqlx logs every statement it sends, with the
+// SQL and row counts, as an event inside that span. This adds the total the call produced.
+fn record_rows(rows: u64) {
+    tracing::Span::current().record("db.rows", rows);
+}
+
+// Conditional updates: `if_match` lists the versions the client last saw, or is `None` when
+// the update should apply whatever the current version is
+fn check_version(if_match: Option<&[i32]>, current: i32) -> Result<(), AppErrorType> {
+    match if_match {
+        Some(versions) if !versions.contains(&current) => Err(AppErrorType::precondition_failed()),
+        _ => Ok(()),
+    }
+}
+
+// A conditional UPDATE that matched no row lost a race with another write, which may also
+// have been a delete; the handler tells the two apart when it fetches the current version
+fn lost_update(if_match: Option<&[i32]>, not_found_message: &str) -> AppErrorType {
+    match if_match {
+        Some(_) => AppErrorType::precondition_failed(),
+        None => AppErrorType::NotFoundError(not_found_message.into()),
+    }
+}
+
+#[async_trait]
+pub trait TutorRepository: Send + Sync {
+    async fn get_all_tutors(
+        &self,
+        filter: &TutorFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Tutor>, AppErrorType>;
+    // Every tutor `get_all_tutors` would page through, in the same order
+    fn stream_tutors(&self, filter: &TutorFilter) -> Result<RowStream<Tutor>, AppErrorType>;
+    async fn post_new_tutor(
+        &self,
+        new_tutor: CreateTutor,
+        password_hash: String,
+    ) -> Result<Tutor, AppErrorType>;
+    async fn get_tutor_details(&self, tutor_id: i32) -> Result<Tutor, AppErrorType>;
+    async fn update_tutor_details(
+        &self,
+        tutor_id: i32,
+        update_tutor: UpdateTutor,
+        if_match: Option<&[i32]>,
+    ) -> Result<Tutor, AppErrorType>;
+    async fn patch_tutor(
+        &self,
+        tutor_id: i32,
+        patch: TutorPatch,
+        if_match: Option<&[i32]>,
+    ) -> Result<Tutor, AppErrorType>;
+    async fn delete_tutor(
+        &self,
+        tutor_id: i32,
+        dry_run: bool,
+    ) -> Result<TutorDeletion, AppErrorType>;
+    async fn get_tutor_credentials(
+        &self,
+        email: &str,
+    ) -> Result<Option<TutorCredentials>, AppErrorType>;
+    async fn get_tutor_role(&self, tutor_id: i32) -> Result<String, AppErrorType>;
+}
+
+#[async_trait]
+pub trait TopicRepository: Send + Sync {
+    async fn get_all_topics(
+        &self,
+        filter: &TopicFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Topic>, AppErrorType>;
+    async fn get_topics_for_tutor(
+        &self,
+        tutor_id: i32,
+        filter: &TopicFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Topic>, AppErrorType>;
+    // Every topic the two listings above would page through, in the same order
+    fn stream_topics(&self, filter: &TopicFilter) -> Result<RowStream<Topic>, AppErrorType>;
+    fn stream_topics_for_tutor(
+        &self,
+        tutor_id: i32,
+        filter: &TopicFilter,
+    ) -> Result<RowStream<Topic>, AppErrorType>;
+    async fn search_topics(
+        &self,
+        terms: &str,
+        page: &PageRequest,
+    ) -> Result<Page<TopicSearchHit>, AppErrorType>;
+    async fn get_topic_details(&self, topic_id: i32) -> Result<Topic, AppErrorType>;
+    async fn post_new_topic(&self, new_topic: CreateTopic) -> Result<Topic, AppErrorType>;
+    async fn update_topic_details(
+        &self,
+        tutor_id: i32,
+        topic_id: i32,
+        update_topic: UpdateTopic,
+        if_match: Option<&[i32]>,
+    ) -> Result<Topic, AppErrorType>;
+    async fn patch_topic(
+        &self,
+        tutor_id: i32,
+        topic_id: i32,
+        patch: TopicPatch,
+        if_match: Option<&[i32]>,
+    ) -> Result<Topic, AppErrorType>;
+    async fn delete_topic(&self, tutor_id: i32, topic_id: i32) -> Result<String, AppErrorType>;
+}
+
+// Students and their enrollments in topics. Trashed topics take no enrollments and are left
+// out of a student's topics, but keep theirs so a restore brings them back.
+#[async_trait]
+pub trait StudentRepository: Send + Sync {
+    // Ordered by id
+    async fn get_all_students(&self, page: &PageRequest) -> Result<Page<Student>, AppErrorType>;
+    async fn post_new_student(
+        &self,
+        new_student: CreateStudent,
+        password_hash: Option<String>,
+    ) -> Result<Student, AppErrorType>;
+    async fn get_student_details(&self, student_id: i32) -> Result<Student, AppErrorType>;
+    // Removes the student and their enrollments for good
+    async fn delete_student(&self, student_id: i32) -> Result<String, AppErrorType>;
+    // Fails with a conflict when the student is already enrolled or the topic is full
+    async fn enroll_student(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, AppErrorType>;
+    async fn unenroll_student(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+    ) -> Result<String, AppErrorType>;
+    async fn get_topic_roster(
+        &self,
+        topic_id: i32,
+        page: &PageRequest,
+    ) -> Result<Roster, AppErrorType>;
+    async fn get_topics_for_student(
+        &self,
+        student_id: i32,
+        page: &PageRequest,
+    ) -> Result<Page<EnrolledTopic>, AppErrorType>;
+    // Looked up by email, ignoring case
+    async fn get_student_credentials(
+        &self,
+        email: &str,
+    ) -> Result<Option<StudentCredentials>, AppErrorType>;
+}
+
+// Scheduled sessions of topics. Every occurrence of a session is stored, so the database
+// rejects any that overlap another session of the same tutor.
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    async fn get_sessions_for_topic(&self, topic_id: i32) -> Result<Vec<Session>, AppErrorType>;
+    async fn post_new_session(
+        &self,
+        topic_id: i32,
+        schedule: SessionSchedule,
+    ) -> Result<Session, AppErrorType>;
+    // Replaces the schedule, and with it every occurrence
+    async fn update_session(
+        &self,
+        topic_id: i32,
+        session_id: i32,
+        schedule: SessionSchedule,
+    ) -> Result<Session, AppErrorType>;
+    async fn delete_session(&self, topic_id: i32, session_id: i32) -> Result<String, AppErrorType>;
+    // Occurrences overlapping `interval`, in start order
+    async fn get_occurrences_for_topic(
+        &self,
+        topic_id: i32,
+        interval: Interval,
+    ) -> Result<Vec<Occurrence>, AppErrorType>;
+    // Every session of the tutor's topics that are not in the trash
+    async fn get_calendar_for_tutor(&self, tutor_id: i32) -> Result<Calendar, AppErrorType>;
+    async fn get_calendar_for_topic(&self, topic_id: i32) -> Result<Calendar, AppErrorType>;
+}
+
+#[async_trait]
+pub trait AvailabilityRepository: Send + Sync {
+    async fn get_availability(&self, tutor_id: i32) -> Result<Availability, AppErrorType>;
+    // Replaces the time zone and weekly windows; exceptions are kept
+    async fn put_weekly_availability(
+        &self,
+        tutor_id: i32,
+        weekly: WeeklyAvailability,
+    ) -> Result<Availability, AppErrorType>;
+    async fn put_availability_exception(
+        &self,
+        tutor_id: i32,
+        date: NaiveDate,
+        windows: Vec<TimeWindow>,
+    ) -> Result<AvailabilityException, AppErrorType>;
+    async fn delete_availability_exception(
+        &self,
+        tutor_id: i32,
+        date: NaiveDate,
+    ) -> Result<String, AppErrorType>;
+    // Slots within the tutor's availability that no booking or session occurrence overlaps
+    async fn get_free_slots(
+        &self,
+        tutor_id: i32,
+        range: Interval,
+        length: chrono::Duration,
+    ) -> Result<Vec<Slot>, AppErrorType>;
+    // Bookings overlapping `range`, in start order
+    async fn get_bookings(
+        &self,
+        tutor_id: i32,
+        range: Interval,
+    ) -> Result<Vec<Booking>, AppErrorType>;
+    // Must never let two bookings of the same tutor overlap, however calls interleave
+    async fn book_slot(
+        &self,
+        tutor_id: i32,
+        student_id: i32,
+        booking: NewBooking,
+    ) -> Result<Booking, AppErrorType>;
+    // With `student_id`, only a booking of that student's is cancelled
+    async fn cancel_booking(
+        &self,
+        tutor_id: i32,
+        booking_id: i32,
+        student_id: Option<i32>,
+    ) -> Result<String, AppErrorType>;
+}
+
+// Every write keeps the `average_rating` and `review_count` of the topic and its tutor in step
+// with the reviews, so reads never aggregate them
+#[async_trait]
+pub trait ReviewRepository: Send + Sync {
+    async fn get_reviews_for_topic(
+        &self,
+        topic_id: i32,
+        page: &PageRequest,
+    ) -> Result<Page<Review>, AppErrorType>;
+    // Creates the student's review of the topic, or replaces the one they already wrote
+    async fn put_review(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+        review: WriteReview,
+    ) -> Result<Review, AppErrorType>;
+    async fn delete_review(&self, topic_id: i32, student_id: i32) -> Result<String, AppErrorType>;
+}
+
+#[async_trait]
+pub trait HealthRepository: Send + Sync {
+    // Must return within `timeout` even when the database is unreachable
+    async fn database_health(&self, timeout: Duration) -> DatabaseHealth;
+    // None for backends without a connection pool
+    fn pool_stats(&self) -> Option<PoolStats>;
+}
+
+// Soft-deleted tutors and topics. Every other repository method ignores them.
+#[async_trait]
+pub trait TrashRepository: Send + Sync {
+    // `retention` is how long items stay in the trash, used to report when each is purged
+    async fn list_trash(&self, retention: chrono::Duration) -> Result<Trash, AppErrorType>;
+    async fn restore_tutor(&self, tutor_id: i32) -> Result<TutorRestoration, AppErrorType>;
+    async fn restore_topic(&self, topic_id: i32) -> Result<Topic, AppErrorType>;
+    async fn purge_trash(&self, cutoff: NaiveDateTime) -> Result<PurgeSummary, AppErrorType>;
+}
+
+// Bulk export and import of tutors with their topics, keyed by email rather than id
+#[async_trait]
+pub trait CatalogueRepository: Send + Sync {
+    async fn export_catalogue(&self) -> Result<Vec<CatalogueRecord>, AppErrorType>;
+    // Rows that failed parsing or validation are reported as rejected alongside the rest
+    async fn import_catalogue(
+        &self,
+        rows: Vec<ImportRow>,
+        mode: ImportMode,
+    ) -> Result<ImportReport, AppErrorType>;
+}
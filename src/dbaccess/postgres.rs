@@ -0,0 +1,596 @@
+use super::availability::*;
+use super::catalogue::*;
+use super::health::*;
+use super::review::*;
+use super::session::*;
+use super::student::*;
+use super::topic::*;
+use super::trash::*;
+use super::tutor::*;
+use super::{
+    AvailabilityRepository, CatalogueRepository, HealthRepository, ReviewRepository, RowStream,
+    SessionRepository, StudentRepository, TopicRepository, TrashRepository, TutorRepository,
+};
+use crate::errors::AppErrorType;
+use crate::metrics::{timed_query, timed_stream};
+use crate::models::auth::{StudentCredentials, TutorCredentials};
+use crate::models::availability::{
+    Availability, AvailabilityException, Booking, NewBooking, Slot, TimeWindow, WeeklyAvailability,
+};
+use crate::models::calendar::Calendar;
+use crate::models::catalogue::{CatalogueRecord, ImportMode, ImportReport, ImportRow};
+use crate::models::health::{DatabaseHealth, PoolStats};
+use crate::models::page::{Page, PageRequest};
+use crate::models::review::{Review, WriteReview};
+use crate::models::session::{Interval, Occurrence, Session, SessionSchedule};
+use crate::models::student::{CreateStudent, EnrolledTopic, Enrollment, Roster, Student};
+use crate::models::topic::{
+    CreateTopic, Topic, TopicFilter, TopicPatch, TopicSearchHit, UpdateTopic,
+};
+use crate::models::trash::{PurgeSummary, Trash, TutorRestoration};
+use crate::models::tutor::{
+    CreateTutor, Tutor, TutorDeletion, TutorFilter, TutorPatch, UpdateTutor,
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::postgres::PgPool;
+use std::time::Duration;
+
+pub struct PgRepository {
+    pool: PgPool,
+}
+
+impl PgRepository {
+    pub fn new(pool: PgPool) -> Self {
+        PgRepository { pool }
+    }
+}
+
+#[async_trait]
+impl TutorRepository for PgRepository {
+    async fn get_all_tutors(
+        &self,
+        filter: &TutorFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Tutor>, AppErrorType> {
+        timed_query(
+            "get_all_tutors_db",
+            get_all_tutors_db(&self.pool, filter, page),
+        )
+        .await
+    }
+
+    fn stream_tutors(&self, filter: &TutorFilter) -> Result<RowStream<Tutor>, AppErrorType> {
+        stream_tutors_db(self.pool.clone(), filter.clone())
+            .map(|rows| timed_stream("stream_tutors_db", rows))
+    }
+
+    async fn post_new_tutor(
+        &self,
+        new_tutor: CreateTutor,
+        password_hash: String,
+    ) -> Result<Tutor, AppErrorType> {
+        timed_query(
+            "post_new_tutor_db",
+            post_new_tutor_db(&self.pool, new_tutor, password_hash),
+        )
+        .await
+    }
+
+    async fn get_tutor_details(&self, tutor_id: i32) -> Result<Tutor, AppErrorType> {
+        timed_query(
+            "get_tutor_details_db",
+            get_tutor_details_db(&self.pool, tutor_id),
+        )
+        .await
+    }
+
+    async fn update_tutor_details(
+        &self,
+        tutor_id: i32,
+        update_tutor: UpdateTutor,
+        if_match: Option<&[i32]>,
+    ) -> Result<Tutor, AppErrorType> {
+        timed_query(
+            "update_tutor_details_db",
+            update_tutor_details_db(&self.pool, tutor_id, update_tutor, if_match),
+        )
+        .await
+    }
+
+    async fn patch_tutor(
+        &self,
+        tutor_id: i32,
+        patch: TutorPatch,
+        if_match: Option<&[i32]>,
+    ) -> Result<Tutor, AppErrorType> {
+        timed_query(
+            "patch_tutor_db",
+            patch_tutor_db(&self.pool, tutor_id, patch, if_match),
+        )
+        .await
+    }
+
+    async fn delete_tutor(
+        &self,
+        tutor_id: i32,
+        dry_run: bool,
+    ) -> Result<TutorDeletion, AppErrorType> {
+        timed_query(
+            "delete_tutor_db",
+            delete_tutor_db(&self.pool, tutor_id, dry_run),
+        )
+        .await
+    }
+
+    async fn get_tutor_credentials(
+        &self,
+        email: &str,
+    ) -> Result<Option<TutorCredentials>, AppErrorType> {
+        timed_query(
+            "get_tutor_credentials_db",
+            get_tutor_credentials_db(&self.pool, email),
+        )
+        .await
+    }
+
+    async fn get_tutor_role(&self, tutor_id: i32) -> Result<String, AppErrorType> {
+        timed_query("get_tutor_role_db", get_tutor_role_db(&self.pool, tutor_id)).await
+    }
+}
+
+#[async_trait]
+impl TopicRepository for PgRepository {
+    async fn get_all_topics(
+        &self,
+        filter: &TopicFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Topic>, AppErrorType> {
+        timed_query(
+            "get_all_topics_db",
+            get_all_topics_db(&self.pool, filter, page),
+        )
+        .await
+    }
+
+    async fn get_topics_for_tutor(
+        &self,
+        tutor_id: i32,
+        filter: &TopicFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Topic>, AppErrorType> {
+        timed_query(
+            "get_topics_for_tutor_db",
+            get_topics_for_tutor_db(&self.pool, tutor_id, filter, page),
+        )
+        .await
+    }
+
+    fn stream_topics(&self, filter: &TopicFilter) -> Result<RowStream<Topic>, AppErrorType> {
+        stream_topics_db(self.pool.clone(), filter.clone())
+            .map(|rows| timed_stream("stream_topics_db", rows))
+    }
+
+    fn stream_topics_for_tutor(
+        &self,
+        tutor_id: i32,
+        filter: &TopicFilter,
+    ) -> Result<RowStream<Topic>, AppErrorType> {
+        stream_topics_for_tutor_db(self.pool.clone(), tutor_id, filter.clone())
+            .map(|rows| timed_stream("stream_topics_for_tutor_db", rows))
+    }
+
+    async fn search_topics(
+        &self,
+        terms: &str,
+        page: &PageRequest,
+    ) -> Result<Page<TopicSearchHit>, AppErrorType> {
+        timed_query(
+            "search_topics_db",
+            search_topics_db(&self.pool, terms, page),
+        )
+        .await
+    }
+
+    async fn get_topic_details(&self, topic_id: i32) -> Result<Topic, AppErrorType> {
+        timed_query(
+            "get_topic_details_db",
+            get_topic_details_db(&self.pool, topic_id),
+        )
+        .await
+    }
+
+    async fn post_new_topic(&self, new_topic: CreateTopic) -> Result<Topic, AppErrorType> {
+        timed_query(
+            "post_new_topic_db",
+            post_new_topic_db(&self.pool, new_topic),
+        )
+        .await
+    }
+
+    async fn update_topic_details(
+        &self,
+        tutor_id: i32,
+        topic_id: i32,
+        update_topic: UpdateTopic,
+        if_match: Option<&[i32]>,
+    ) -> Result<Topic, AppErrorType> {
+        timed_query(
+            "update_topic_details_db",
+            update_topic_details_db(&self.pool, tutor_id, topic_id, update_topic, if_match),
+        )
+        .await
+    }
+
+    async fn patch_topic(
+        &self,
+        tutor_id: i32,
+        topic_id: i32,
+        patch: TopicPatch,
+        if_match: Option<&[i32]>,
+    ) -> Result<Topic, AppErrorType> {
+        timed_query(
+            "patch_topic_db",
+            patch_topic_db(&self.pool, tutor_id, topic_id, patch, if_match),
+        )
+        .await
+    }
+
+    async fn delete_topic(&self, tutor_id: i32, topic_id: i32) -> Result<String, AppErrorType> {
+        timed_query(
+            "delete_topic_db",
+            delete_topic_db(&self.pool, tutor_id, topic_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl HealthRepository for PgRepository {
+    async fn database_health(&self, timeout: Duration) -> DatabaseHealth {
+        database_health_db(&self.pool, timeout).await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(pool_stats_db(&self.pool))
+    }
+}
+
+#[async_trait]
+impl TrashRepository for PgRepository {
+    async fn list_trash(&self, retention: chrono::Duration) -> Result<Trash, AppErrorType> {
+        timed_query("list_trash_db", list_trash_db(&self.pool, retention)).await
+    }
+
+    async fn restore_tutor(&self, tutor_id: i32) -> Result<TutorRestoration, AppErrorType> {
+        timed_query("restore_tutor_db", restore_tutor_db(&self.pool, tutor_id)).await
+    }
+
+    async fn restore_topic(&self, topic_id: i32) -> Result<Topic, AppErrorType> {
+        timed_query("restore_topic_db", restore_topic_db(&self.pool, topic_id)).await
+    }
+
+    async fn purge_trash(&self, cutoff: NaiveDateTime) -> Result<PurgeSummary, AppErrorType> {
+        timed_query("purge_trash_db", purge_trash_db(&self.pool, cutoff)).await
+    }
+}
+
+#[async_trait]
+impl CatalogueRepository for PgRepository {
+    async fn export_catalogue(&self) -> Result<Vec<CatalogueRecord>, AppErrorType> {
+        timed_query("export_catalogue_db", export_catalogue_db(&self.pool)).await
+    }
+
+    async fn import_catalogue(
+        &self,
+        rows: Vec<ImportRow>,
+        mode: ImportMode,
+    ) -> Result<ImportReport, AppErrorType> {
+        timed_query(
+            "import_catalogue_db",
+            import_catalogue_db(&self.pool, rows, mode),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl StudentRepository for PgRepository {
+    async fn get_all_students(&self, page: &PageRequest) -> Result<Page<Student>, AppErrorType> {
+        timed_query("get_all_students_db", get_all_students_db(&self.pool, page)).await
+    }
+
+    async fn post_new_student(
+        &self,
+        new_student: CreateStudent,
+        password_hash: Option<String>,
+    ) -> Result<Student, AppErrorType> {
+        timed_query(
+            "post_new_student_db",
+            post_new_student_db(&self.pool, new_student, password_hash),
+        )
+        .await
+    }
+
+    async fn get_student_details(&self, student_id: i32) -> Result<Student, AppErrorType> {
+        timed_query(
+            "get_student_details_db",
+            get_student_details_db(&self.pool, student_id),
+        )
+        .await
+    }
+
+    async fn delete_student(&self, student_id: i32) -> Result<String, AppErrorType> {
+        timed_query(
+            "delete_student_db",
+            delete_student_db(&self.pool, student_id),
+        )
+        .await
+    }
+
+    async fn enroll_student(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, AppErrorType> {
+        timed_query(
+            "enroll_student_db",
+            enroll_student_db(&self.pool, topic_id, student_id),
+        )
+        .await
+    }
+
+    async fn unenroll_student(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+    ) -> Result<String, AppErrorType> {
+        timed_query(
+            "unenroll_student_db",
+            unenroll_student_db(&self.pool, topic_id, student_id),
+        )
+        .await
+    }
+
+    async fn get_topic_roster(
+        &self,
+        topic_id: i32,
+        page: &PageRequest,
+    ) -> Result<Roster, AppErrorType> {
+        timed_query(
+            "get_topic_roster_db",
+            get_topic_roster_db(&self.pool, topic_id, page),
+        )
+        .await
+    }
+
+    async fn get_topics_for_student(
+        &self,
+        student_id: i32,
+        page: &PageRequest,
+    ) -> Result<Page<EnrolledTopic>, AppErrorType> {
+        timed_query(
+            "get_topics_for_student_db",
+            get_topics_for_student_db(&self.pool, student_id, page),
+        )
+        .await
+    }
+
+    async fn get_student_credentials(
+        &self,
+        email: &str,
+    ) -> Result<Option<StudentCredentials>, AppErrorType> {
+        timed_query(
+            "get_student_credentials_db",
+            get_student_credentials_db(&self.pool, email),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl SessionRepository for PgRepository {
+    async fn get_sessions_for_topic(&self, topic_id: i32) -> Result<Vec<Session>, AppErrorType> {
+        timed_query(
+            "get_sessions_for_topic_db",
+            get_sessions_for_topic_db(&self.pool, topic_id),
+        )
+        .await
+    }
+
+    async fn post_new_session(
+        &self,
+        topic_id: i32,
+        schedule: SessionSchedule,
+    ) -> Result<Session, AppErrorType> {
+        timed_query(
+            "post_new_session_db",
+            post_new_session_db(&self.pool, topic_id, schedule),
+        )
+        .await
+    }
+
+    async fn update_session(
+        &self,
+        topic_id: i32,
+        session_id: i32,
+        schedule: SessionSchedule,
+    ) -> Result<Session, AppErrorType> {
+        timed_query(
+            "update_session_db",
+            update_session_db(&self.pool, topic_id, session_id, schedule),
+        )
+        .await
+    }
+
+    async fn delete_session(&self, topic_id: i32, session_id: i32) -> Result<String, AppErrorType> {
+        timed_query(
+            "delete_session_db",
+            delete_session_db(&self.pool, topic_id, session_id),
+        )
+        .await
+    }
+
+    async fn get_occurrences_for_topic(
+        &self,
+        topic_id: i32,
+        interval: Interval,
+    ) -> Result<Vec<Occurrence>, AppErrorType> {
+        timed_query(
+            "get_occurrences_for_topic_db",
+            get_occurrences_for_topic_db(&self.pool, topic_id, interval),
+        )
+        .await
+    }
+
+    async fn get_calendar_for_tutor(&self, tutor_id: i32) -> Result<Calendar, AppErrorType> {
+        timed_query(
+            "get_calendar_for_tutor_db",
+            get_calendar_for_tutor_db(&self.pool, tutor_id),
+        )
+        .await
+    }
+
+    async fn get_calendar_for_topic(&self, topic_id: i32) -> Result<Calendar, AppErrorType> {
+        timed_query(
+            "get_calendar_for_topic_db",
+            get_calendar_for_topic_db(&self.pool, topic_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl AvailabilityRepository for PgRepository {
+    async fn get_availability(&self, tutor_id: i32) -> Result<Availability, AppErrorType> {
+        timed_query(
+            "get_availability_db",
+            get_availability_db(&self.pool, tutor_id),
+        )
+        .await
+    }
+
+    async fn put_weekly_availability(
+        &self,
+        tutor_id: i32,
+        weekly: WeeklyAvailability,
+    ) -> Result<Availability, AppErrorType> {
+        timed_query(
+            "put_weekly_availability_db",
+            put_weekly_availability_db(&self.pool, tutor_id, weekly),
+        )
+        .await
+    }
+
+    async fn put_availability_exception(
+        &self,
+        tutor_id: i32,
+        date: NaiveDate,
+        windows: Vec<TimeWindow>,
+    ) -> Result<AvailabilityException, AppErrorType> {
+        timed_query(
+            "put_availability_exception_db",
+            put_availability_exception_db(&self.pool, tutor_id, date, windows),
+        )
+        .await
+    }
+
+    async fn delete_availability_exception(
+        &self,
+        tutor_id: i32,
+        date: NaiveDate,
+    ) -> Result<String, AppErrorType> {
+        timed_query(
+            "delete_availability_exception_db",
+            delete_availability_exception_db(&self.pool, tutor_id, date),
+        )
+        .await
+    }
+
+    async fn get_free_slots(
+        &self,
+        tutor_id: i32,
+        range: Interval,
+        length: chrono::Duration,
+    ) -> Result<Vec<Slot>, AppErrorType> {
+        timed_query(
+            "get_free_slots_db",
+            get_free_slots_db(&self.pool, tutor_id, range, length),
+        )
+        .await
+    }
+
+    async fn get_bookings(
+        &self,
+        tutor_id: i32,
+        range: Interval,
+    ) -> Result<Vec<Booking>, AppErrorType> {
+        timed_query(
+            "get_bookings_db",
+            get_bookings_db(&self.pool, tutor_id, range),
+        )
+        .await
+    }
+
+    async fn book_slot(
+        &self,
+        tutor_id: i32,
+        student_id: i32,
+        booking: NewBooking,
+    ) -> Result<Booking, AppErrorType> {
+        timed_query(
+            "book_slot_db",
+            book_slot_db(&self.pool, tutor_id, student_id, booking),
+        )
+        .await
+    }
+
+    async fn cancel_booking(
+        &self,
+        tutor_id: i32,
+        booking_id: i32,
+        student_id: Option<i32>,
+    ) -> Result<String, AppErrorType> {
+        timed_query(
+            "cancel_booking_db",
+            cancel_booking_db(&self.pool, tutor_id, booking_id, student_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl ReviewRepository for PgRepository {
+    async fn get_reviews_for_topic(
+        &self,
+        topic_id: i32,
+        page: &PageRequest,
+    ) -> Result<Page<Review>, AppErrorType> {
+        timed_query(
+            "get_reviews_for_topic_db",
+            get_reviews_for_topic_db(&self.pool, topic_id, page),
+        )
+        .await
+    }
+
+    async fn put_review(
+        &self,
+        topic_id: i32,
+        student_id: i32,
+        review: WriteReview,
+    ) -> Result<Review, AppErrorType> {
+        timed_query(
+            "put_review_db",
+            put_review_db(&self.pool, topic_id, student_id, review),
+        )
+        .await
+    }
+
+    async fn delete_review(&self, topic_id: i32, student_id: i32) -> Result<String, AppErrorType> {
+        timed_query(
+            "delete_review_db",
+            delete_review_db(&self.pool, topic_id, student_id),
+        )
+        .await
+    }
+}
@@ -0,0 +1,229 @@
+use super::record_rows;
+use crate::errors::AppErrorType;
+use crate::models::page::{Page, PageRequest, SortKey};
+use crate::models::review::{check_enrolled, RatingChange, Review, WriteReview};
+use chrono::Utc;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+use tracing::field::Empty;
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_reviews_for_topic_db(
+    pool: &PgPool,
+    topic_id: i32,
+    page: &PageRequest,
+) -> Result<Page<Review>, AppErrorType> {
+    page.keyset_order(&[SortKey::asc("id")])?;
+    let topic = sqlx::query!(
+        "SELECT review_count FROM topic WHERE id = $1 AND deleted_at IS NULL",
+        topic_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found"))?;
+
+    let reviews = sqlx::query_as!(
+        Review,
+        "SELECT id, topic_id, student_id, rating, body, created_at, updated_at FROM review
+        WHERE topic_id = $1 AND ($2::int4 IS NULL OR id > $2) ORDER BY id LIMIT $3 OFFSET $4",
+        topic_id,
+        page.after_id,
+        page.fetch_limit(),
+        page.offset
+    )
+    .fetch_all(pool)
+    .await?;
+    record_rows(reviews.len() as u64 + 1);
+
+    // The running count is exact, so the total needs no count(*)
+    let total = page.include_total.then_some(topic.review_count as i64);
+
+    Ok(Page::from_rows(reviews, page, total, |review| review.id))
+}
+
+// The topic row is locked before the review is read, so concurrent writes for the same topic
+// apply their changes to the totals one after another. The tutor row is locked after it.
+#[tracing::instrument(skip(pool, review), fields(db.rows = Empty))]
+pub async fn put_review_db(
+    pool: &PgPool,
+    topic_id: i32,
+    student_id: i32,
+    review: WriteReview,
+) -> Result<Review, AppErrorType> {
+    let mut tx = pool.begin().await?;
+    let topic = sqlx::query!(
+        "SELECT tutor_id FROM topic WHERE id = $1 AND deleted_at IS NULL FOR NO KEY UPDATE",
+        topic_id
+    )
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found"))?;
+    let enrolled = sqlx::query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM enrollment WHERE topic_id = $1 AND student_id = $2
+        ) AS "exists!""#,
+        topic_id,
+        student_id
+    )
+    .fetch_one(&mut tx)
+    .await?
+    .exists;
+    check_enrolled(enrolled)?;
+    let old_rating = sqlx::query!(
+        "SELECT rating FROM review WHERE topic_id = $1 AND student_id = $2",
+        topic_id,
+        student_id
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .map(|row| row.rating);
+
+    let review = sqlx::query_as!(
+        Review,
+        "INSERT INTO review (topic_id, student_id, rating, body, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $5)
+        ON CONFLICT (topic_id, student_id)
+            DO UPDATE SET rating = EXCLUDED.rating, body = EXCLUDED.body, updated_at = EXCLUDED.updated_at
+        RETURNING id, topic_id, student_id, rating, body, created_at, updated_at",
+        topic_id,
+        student_id,
+        review.rating,
+        review.body,
+        Utc::now().naive_utc()
+    )
+    .fetch_one(&mut tx)
+    .await?;
+    let change = RatingChange::between(old_rating, Some(review.rating));
+    change_ratings(&mut tx, topic_id, topic.tutor_id, change).await?;
+    tx.commit().await?;
+    record_rows(1);
+
+    Ok(review)
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn delete_review_db(
+    pool: &PgPool,
+    topic_id: i32,
+    student_id: i32,
+) -> Result<String, AppErrorType> {
+    let mut tx = pool.begin().await?;
+    let topic = sqlx::query!(
+        "SELECT tutor_id FROM topic WHERE id = $1 AND deleted_at IS NULL FOR NO KEY UPDATE",
+        topic_id
+    )
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found"))?;
+    let review = sqlx::query!(
+        "DELETE FROM review WHERE topic_id = $1 AND student_id = $2 RETURNING id, rating",
+        topic_id,
+        student_id
+    )
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Review not found"))?;
+    let change = RatingChange::between(Some(review.rating), None);
+    change_ratings(&mut tx, topic_id, topic.tutor_id, change).await?;
+    tx.commit().await?;
+    record_rows(1);
+
+    Ok(format!("Review with id: {} deleted", review.id))
+}
+
+// Takes a deleted student's ratings off the totals; those of trashed topics are already off
+// their tutors'. The caller holds the student row, so no review of theirs can be added
+// meanwhile. Every write to both locks a topic before its tutor; with several of each, this
+// one takes them in id order.
+pub(super) async fn remove_student_reviews(
+    tx: &mut Transaction<'_, Postgres>,
+    student_id: i32,
+) -> Result<(), AppErrorType> {
+    let topics = sqlx::query!(
+        "SELECT id, tutor_id FROM topic
+        WHERE id IN (SELECT topic_id FROM review WHERE student_id = $1)
+        ORDER BY id FOR NO KEY UPDATE",
+        student_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    if topics.is_empty() {
+        return Ok(());
+    }
+    let mut tutor_ids: Vec<i32> = topics.iter().map(|topic| topic.tutor_id).collect();
+    tutor_ids.sort_unstable();
+    tutor_ids.dedup();
+    sqlx::query!(
+        "SELECT id FROM tutor WHERE id = ANY($1) ORDER BY id FOR NO KEY UPDATE",
+        &tutor_ids
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let removed = sqlx::query!(
+        "WITH removed AS (DELETE FROM review WHERE student_id = $1 RETURNING topic_id, rating)
+        UPDATE topic SET rating_sum = topic.rating_sum - removed.rating,
+            review_count = topic.review_count - 1, version = topic.version + 1
+        FROM removed WHERE topic.id = removed.topic_id
+        RETURNING topic.tutor_id, removed.rating, topic.deleted_at IS NULL AS \"live!\"",
+        student_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    record_rows(removed.len() as u64);
+    let (tutors, ratings): (Vec<i32>, Vec<i16>) = removed
+        .into_iter()
+        .filter(|row| row.live)
+        .map(|row| (row.tutor_id, row.rating))
+        .unzip();
+    sqlx::query!(
+        "UPDATE tutor SET rating_sum = tutor.rating_sum - removed.rating_sum,
+            review_count = tutor.review_count - removed.review_count, version = tutor.version + 1
+        FROM (
+            SELECT tutor_id, SUM(rating) AS rating_sum, COUNT(*) AS review_count
+            FROM UNNEST($1::int4[], $2::int2[]) AS review(tutor_id, rating) GROUP BY tutor_id
+        ) removed
+        WHERE tutor.id = removed.tutor_id",
+        &tutors,
+        &ratings
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+// A change to the average is a change to the representation, so it bumps both versions and
+// with them the ETags. Editing only a review's text leaves them alone.
+async fn change_ratings(
+    tx: &mut Transaction<'_, Postgres>,
+    topic_id: i32,
+    tutor_id: i32,
+    change: RatingChange,
+) -> Result<(), AppErrorType> {
+    if change == RatingChange::default() {
+        return Ok(());
+    }
+    sqlx::query!(
+        "UPDATE topic SET rating_sum = rating_sum + $2, review_count = review_count + $3,
+            version = version + 1
+        WHERE id = $1",
+        topic_id,
+        change.rating_sum,
+        change.review_count
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE tutor SET rating_sum = rating_sum + $2, review_count = review_count + $3,
+            version = version + 1
+        WHERE id = $1",
+        tutor_id,
+        change.rating_sum,
+        change.review_count
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
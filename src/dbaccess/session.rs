@@ -0,0 +1,319 @@
+use super::availability::{lock_tutor, overlaps_bookings};
+use super::record_rows;
+use super::topic::TOPIC_COLUMNS;
+use crate::errors::AppErrorType;
+use crate::models::calendar::{Calendar, CalendarEvent};
+use crate::models::session::{Interval, Occurrence, Session, SessionSchedule};
+use crate::models::topic::Topic;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+use std::collections::HashMap;
+use tracing::field::Empty;
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_sessions_for_topic_db(
+    pool: &PgPool,
+    topic_id: i32,
+) -> Result<Vec<Session>, AppErrorType> {
+    live_topic_tutor(pool, topic_id).await?;
+
+    let sessions = sqlx::query_as!(
+        Session,
+        "SELECT id, topic_id, tutor_id, starts_at, ends_at, time_zone, recurrence, created_at, updated_at
+        FROM session WHERE topic_id = $1 ORDER BY id",
+        topic_id
+    )
+    .fetch_all(pool)
+    .await?;
+    record_rows(sessions.len() as u64);
+
+    Ok(sessions)
+}
+
+// The occurrences are inserted in the same transaction as the session, so an overlap with
+// another session of the tutor rolls back both. Bookings are in another table, so they are
+// checked under the tutor lock that `book_slot_db` also takes.
+#[tracing::instrument(skip(pool, schedule), fields(db.rows = Empty))]
+pub async fn post_new_session_db(
+    pool: &PgPool,
+    topic_id: i32,
+    schedule: SessionSchedule,
+) -> Result<Session, AppErrorType> {
+    let occurrences = schedule.occurrences()?;
+    let mut tx = pool.begin().await?;
+    let tutor_id = live_topic_tutor(&mut tx, topic_id).await?;
+    check_bookings(&mut tx, tutor_id, &occurrences).await?;
+    let current_time = Utc::now().naive_utc();
+
+    let session = sqlx::query_as!(
+        Session,
+        "INSERT INTO session (topic_id, tutor_id, starts_at, ends_at, time_zone, recurrence, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $7)
+        RETURNING id, topic_id, tutor_id, starts_at, ends_at, time_zone, recurrence, created_at, updated_at",
+        topic_id,
+        tutor_id,
+        schedule.starts_at,
+        schedule.ends_at,
+        schedule.time_zone,
+        schedule.recurrence,
+        current_time
+    )
+    .fetch_one(&mut tx)
+    .await?;
+    insert_occurrences(&mut tx, &session, &occurrences).await?;
+    tx.commit().await?;
+    record_rows(occurrences.len() as u64 + 1);
+
+    Ok(session)
+}
+
+#[tracing::instrument(skip(pool, schedule), fields(db.rows = Empty))]
+pub async fn update_session_db(
+    pool: &PgPool,
+    topic_id: i32,
+    session_id: i32,
+    schedule: SessionSchedule,
+) -> Result<Session, AppErrorType> {
+    let occurrences = schedule.occurrences()?;
+    let mut tx = pool.begin().await?;
+    let tutor_id = live_topic_tutor(&mut tx, topic_id).await?;
+    check_bookings(&mut tx, tutor_id, &occurrences).await?;
+
+    let session = sqlx::query_as!(
+        Session,
+        "UPDATE session SET starts_at = $3, ends_at = $4, time_zone = $5, recurrence = $6, updated_at = $7
+        WHERE id = $1 AND topic_id = $2
+        RETURNING id, topic_id, tutor_id, starts_at, ends_at, time_zone, recurrence, created_at, updated_at",
+        session_id,
+        topic_id,
+        schedule.starts_at,
+        schedule.ends_at,
+        schedule.time_zone,
+        schedule.recurrence,
+        Utc::now().naive_utc()
+    )
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Session id not found"))?;
+    sqlx::query!(
+        "DELETE FROM session_occurrence WHERE session_id = $1",
+        session_id
+    )
+    .execute(&mut tx)
+    .await?;
+    insert_occurrences(&mut tx, &session, &occurrences).await?;
+    tx.commit().await?;
+    record_rows(occurrences.len() as u64 + 1);
+
+    Ok(session)
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn delete_session_db(
+    pool: &PgPool,
+    topic_id: i32,
+    session_id: i32,
+) -> Result<String, AppErrorType> {
+    let session_row = sqlx::query!(
+        "DELETE FROM session WHERE id = $1 AND topic_id = $2 RETURNING id",
+        session_id,
+        topic_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Session id not found"))?;
+    record_rows(1);
+
+    Ok(format!("Session with id: {} deleted", session_row.id))
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_occurrences_for_topic_db(
+    pool: &PgPool,
+    topic_id: i32,
+    interval: Interval,
+) -> Result<Vec<Occurrence>, AppErrorType> {
+    live_topic_tutor(pool, topic_id).await?;
+
+    let occurrences: Vec<Occurrence> = sqlx::query!(
+        r#"SELECT session.id, session.topic_id, session.tutor_id, session.starts_at, session.ends_at,
+            session.time_zone, session.recurrence, session.created_at, session.updated_at,
+            occurrence.starts_at AS "occurrence_starts_at", occurrence.ends_at AS "occurrence_ends_at"
+        FROM session_occurrence occurrence JOIN session ON session.id = occurrence.session_id
+        WHERE session.topic_id = $1 AND occurrence.starts_at < $3 AND occurrence.ends_at > $2
+        ORDER BY occurrence.starts_at, session.id"#,
+        topic_id,
+        interval.starts_at,
+        interval.ends_at
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        let session = Session {
+            id: row.id,
+            topic_id: row.topic_id,
+            tutor_id: row.tutor_id,
+            starts_at: row.starts_at,
+            ends_at: row.ends_at,
+            time_zone: row.time_zone,
+            recurrence: row.recurrence,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        };
+        session.occurrence(Interval {
+            starts_at: row.occurrence_starts_at,
+            ends_at: row.occurrence_ends_at,
+        })
+    })
+    .collect();
+    record_rows(occurrences.len() as u64);
+
+    Ok(occurrences)
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_calendar_for_tutor_db(
+    pool: &PgPool,
+    tutor_id: i32,
+) -> Result<Calendar, AppErrorType> {
+    let tutor = sqlx::query!(
+        "SELECT first_name, last_name FROM tutor WHERE id = $1 AND deleted_at IS NULL",
+        tutor_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Tutor id not found"))?;
+
+    let topics = sqlx::query_as::<_, Topic>(&format!(
+        "SELECT {} FROM topic WHERE tutor_id = $1 AND deleted_at IS NULL",
+        TOPIC_COLUMNS
+    ))
+    .bind(tutor_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Calendar {
+        name: format!("{} {}", tutor.first_name, tutor.last_name),
+        events: calendar_events(pool, topics).await?,
+    })
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_calendar_for_topic_db(
+    pool: &PgPool,
+    topic_id: i32,
+) -> Result<Calendar, AppErrorType> {
+    let topic = sqlx::query_as::<_, Topic>(&format!(
+        "SELECT {} FROM topic WHERE id = $1 AND deleted_at IS NULL",
+        TOPIC_COLUMNS
+    ))
+    .bind(topic_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found"))?;
+
+    Ok(Calendar {
+        name: topic.title.clone(),
+        events: calendar_events(pool, vec![topic]).await?,
+    })
+}
+
+// The sessions of `topics` in id order, each with all of its occurrences
+async fn calendar_events(
+    pool: &PgPool,
+    topics: Vec<Topic>,
+) -> Result<Vec<CalendarEvent>, AppErrorType> {
+    let topic_ids: Vec<i32> = topics.iter().map(|topic| topic.id).collect();
+    let sessions = sqlx::query_as!(
+        Session,
+        "SELECT id, topic_id, tutor_id, starts_at, ends_at, time_zone, recurrence, created_at, updated_at
+        FROM session WHERE topic_id = ANY($1) ORDER BY id",
+        &topic_ids
+    )
+    .fetch_all(pool)
+    .await?;
+    let session_ids: Vec<i32> = sessions.iter().map(|session| session.id).collect();
+    let occurrence_rows = sqlx::query!(
+        "SELECT session_id, starts_at, ends_at FROM session_occurrence
+        WHERE session_id = ANY($1) ORDER BY session_id, starts_at",
+        &session_ids
+    )
+    .fetch_all(pool)
+    .await?;
+    record_rows((topics.len() + sessions.len() + occurrence_rows.len()) as u64);
+
+    let mut occurrences: HashMap<i32, Vec<Interval>> = HashMap::new();
+    for row in occurrence_rows {
+        occurrences
+            .entry(row.session_id)
+            .or_default()
+            .push(Interval {
+                starts_at: row.starts_at,
+                ends_at: row.ends_at,
+            });
+    }
+    let topics: HashMap<i32, Topic> = topics.into_iter().map(|topic| (topic.id, topic)).collect();
+
+    Ok(sessions
+        .into_iter()
+        .map(|session| CalendarEvent {
+            topic: topics[&session.topic_id].clone(),
+            occurrences: occurrences.remove(&session.id).unwrap_or_default(),
+            session,
+        })
+        .collect())
+}
+
+// Sessions belong to topics that are not in the trash
+async fn live_topic_tutor<'c>(
+    executor: impl sqlx::PgExecutor<'c>,
+    topic_id: i32,
+) -> Result<i32, AppErrorType> {
+    sqlx::query!(
+        "SELECT tutor_id FROM topic WHERE id = $1 AND deleted_at IS NULL",
+        topic_id
+    )
+    .fetch_one(executor)
+    .await
+    .map(|topic| topic.tutor_id)
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found"))
+}
+
+async fn check_bookings(
+    tx: &mut Transaction<'_, Postgres>,
+    tutor_id: i32,
+    occurrences: &[Interval],
+) -> Result<(), AppErrorType> {
+    lock_tutor(tx, tutor_id).await?;
+    if overlaps_bookings(tx, tutor_id, occurrences).await? {
+        return Err(AppErrorType::exclusion_violation());
+    }
+
+    Ok(())
+}
+
+async fn insert_occurrences(
+    tx: &mut Transaction<'_, Postgres>,
+    session: &Session,
+    occurrences: &[Interval],
+) -> Result<(), AppErrorType> {
+    let (starts, ends): (Vec<NaiveDateTime>, Vec<NaiveDateTime>) = occurrences
+        .iter()
+        .map(|occurrence| (occurrence.starts_at, occurrence.ends_at))
+        .unzip();
+    sqlx::query!(
+        "INSERT INTO session_occurrence (session_id, tutor_id, starts_at, ends_at)
+        SELECT $1, $2, occurrence.starts_at, occurrence.ends_at
+        FROM UNNEST($3::timestamp[], $4::timestamp[]) AS occurrence(starts_at, ends_at)",
+        session.id,
+        session.tutor_id,
+        &starts,
+        &ends
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
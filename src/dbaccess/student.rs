@@ -0,0 +1,289 @@
+use super::record_rows;
+use super::review::remove_student_reviews;
+use super::topic::TOPIC_COLUMNS;
+use crate::errors::AppErrorType;
+use crate::models::auth::StudentCredentials;
+use crate::models::page::{Page, PageRequest, SortKey};
+use crate::models::student::{
+    check_capacity, enrollment_order, CreateStudent, EnrolledStudent, EnrolledTopic, Enrollment,
+    Roster, Student,
+};
+use sqlx::postgres::PgPool;
+use tracing::field::Empty;
+
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+pub async fn get_all_students_db(
+    pool: &PgPool,
+    page: &PageRequest,
+) -> Result<Page<Student>, AppErrorType> {
+    page.keyset_order(&[SortKey::asc("id")])?;
+
+    let students = sqlx::query_as!(
+        Student,
+        "SELECT id, first_name, last_name, email FROM student
+        WHERE ($1::int4 IS NULL OR id > $1) ORDER BY id LIMIT $2 OFFSET $3",
+        page.after_id,
+        page.fetch_limit(),
+        page.offset
+    )
+    .fetch_all(pool)
+    .await?;
+    record_rows(students.len() as u64);
+
+    let total = if page.include_total {
+        Some(
+            sqlx::query!(r#"SELECT count(*) AS "count!" FROM student"#)
+                .fetch_one(pool)
+                .await?
+                .count,
+        )
+    } else {
+        None
+    };
+
+    Ok(Page::from_rows(students, page, total, |student| student.id))
+}
+
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+pub async fn post_new_student_db(
+    pool: &PgPool,
+    new_student: CreateStudent,
+    password_hash: Option<String>,
+) -> Result<Student, AppErrorType> {
+    let student = sqlx::query_as!(
+        Student,
+        "INSERT INTO student (first_name, last_name, email, password_hash) VALUES ($1, $2, $3, $4)
+        RETURNING id, first_name, last_name, email",
+        new_student.first_name,
+        new_student.last_name,
+        new_student.email,
+        password_hash
+    )
+    .fetch_one(pool)
+    .await?;
+    record_rows(1);
+
+    Ok(student)
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_student_details_db(pool: &PgPool, id: i32) -> Result<Student, AppErrorType> {
+    let student = sqlx::query_as!(
+        Student,
+        "SELECT id, first_name, last_name, email FROM student WHERE id = $1",
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Student id not found"))?;
+    record_rows(1);
+
+    Ok(student)
+}
+
+// The student's reviews would go with them by cascade, so their ratings come off the
+// running totals first
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn delete_student_db(pool: &PgPool, student_id: i32) -> Result<String, AppErrorType> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        "SELECT id FROM student WHERE id = $1 FOR UPDATE",
+        student_id
+    )
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Student id not found"))?;
+    remove_student_reviews(&mut tx, student_id).await?;
+
+    let student_row = sqlx::query!("DELETE FROM student WHERE id = $1 RETURNING id", student_id)
+        .fetch_one(&mut tx)
+        .await?;
+    tx.commit().await?;
+    record_rows(1);
+
+    Ok(format!("Student with id: {} deleted", student_row.id))
+}
+
+// Locking the topic row queues concurrent enrollments in the same topic, so each one counts
+// the enrollments committed before it and the topic can never end up over capacity
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn enroll_student_db(
+    pool: &PgPool,
+    topic_id: i32,
+    student_id: i32,
+) -> Result<Enrollment, AppErrorType> {
+    let mut tx = pool.begin().await?;
+
+    let topic = sqlx::query!(
+        "SELECT capacity FROM topic WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        topic_id
+    )
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found"))?;
+
+    let enrollments = sqlx::query!(
+        r#"SELECT count(*) AS "enrolled!", COALESCE(bool_or(student_id = $2), false) AS "already_enrolled!"
+        FROM enrollment WHERE topic_id = $1"#,
+        topic_id,
+        student_id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+    if enrollments.already_enrolled {
+        return Err(AppErrorType::ConflictError(
+            "The student is already enrolled in this topic".into(),
+        ));
+    }
+    check_capacity(topic.capacity, enrollments.enrolled)?;
+
+    // An unknown student fails the foreign key
+    let enrollment = sqlx::query_as!(
+        Enrollment,
+        "INSERT INTO enrollment (topic_id, student_id) VALUES ($1, $2)
+        RETURNING topic_id, student_id, enrolled_at",
+        topic_id,
+        student_id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+    tx.commit().await?;
+    record_rows(1);
+
+    Ok(enrollment)
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn unenroll_student_db(
+    pool: &PgPool,
+    topic_id: i32,
+    student_id: i32,
+) -> Result<String, AppErrorType> {
+    sqlx::query!(
+        "DELETE FROM enrollment WHERE topic_id = $1 AND student_id = $2 RETURNING topic_id",
+        topic_id,
+        student_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Enrollment not found"))?;
+    record_rows(1);
+
+    Ok(format!(
+        "Student with id: {} unenrolled from topic {}",
+        student_id, topic_id
+    ))
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_topic_roster_db(
+    pool: &PgPool,
+    topic_id: i32,
+    page: &PageRequest,
+) -> Result<Roster, AppErrorType> {
+    page.keyset_order(&enrollment_order())?;
+    let topic = sqlx::query!(
+        r#"SELECT capacity, (SELECT count(*) FROM enrollment WHERE topic_id = $1) AS "enrolled!"
+        FROM topic WHERE id = $1 AND deleted_at IS NULL"#,
+        topic_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found"))?;
+
+    let students: Vec<EnrolledStudent> = sqlx::query!(
+        "SELECT student.id, student.first_name, student.last_name, student.email, enrollment.enrolled_at
+        FROM enrollment JOIN student ON student.id = enrollment.student_id
+        WHERE enrollment.topic_id = $1
+        ORDER BY enrollment.enrolled_at, student.id LIMIT $2 OFFSET $3",
+        topic_id,
+        page.fetch_limit(),
+        page.offset
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| EnrolledStudent {
+        student: Student {
+            id: row.id,
+            first_name: row.first_name,
+            last_name: row.last_name,
+            email: row.email,
+        },
+        enrolled_at: row.enrolled_at,
+    })
+    .collect();
+    record_rows(students.len() as u64 + 1);
+
+    // The roster always carries the count, so the total needs no second query
+    let total = page.include_total.then_some(topic.enrolled);
+
+    Ok(Roster {
+        topic_id,
+        capacity: topic.capacity,
+        enrolled: topic.enrolled,
+        students: Page::from_offset_rows(students, page, total),
+    })
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_topics_for_student_db(
+    pool: &PgPool,
+    student_id: i32,
+    page: &PageRequest,
+) -> Result<Page<EnrolledTopic>, AppErrorType> {
+    page.keyset_order(&enrollment_order())?;
+    sqlx::query!("SELECT id FROM student WHERE id = $1", student_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|err| AppErrorType::from_lookup(err, "Student id not found"))?;
+
+    let topics = sqlx::query_as::<_, EnrolledTopic>(&format!(
+        "SELECT {}, enrollment.enrolled_at
+        FROM enrollment JOIN topic ON topic.id = enrollment.topic_id
+        WHERE enrollment.student_id = $1 AND topic.deleted_at IS NULL
+        ORDER BY enrollment.enrolled_at, topic.id LIMIT $2 OFFSET $3",
+        TOPIC_COLUMNS
+    ))
+    .bind(student_id)
+    .bind(page.fetch_limit())
+    .bind(page.offset)
+    .fetch_all(pool)
+    .await?;
+    record_rows(topics.len() as u64);
+
+    let total = if page.include_total {
+        Some(
+            sqlx::query!(
+                r#"SELECT count(*) AS "count!"
+                FROM enrollment JOIN topic ON topic.id = enrollment.topic_id
+                WHERE enrollment.student_id = $1 AND topic.deleted_at IS NULL"#,
+                student_id
+            )
+            .fetch_one(pool)
+            .await?
+            .count,
+        )
+    } else {
+        None
+    };
+
+    Ok(Page::from_offset_rows(topics, page, total))
+}
+
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+pub async fn get_student_credentials_db(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<StudentCredentials>, AppErrorType> {
+    let credentials = sqlx::query_as!(
+        StudentCredentials,
+        "SELECT id AS student_id, password_hash FROM student WHERE lower(email) = lower($1)",
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    record_rows(credentials.is_some() as u64);
+
+    Ok(credentials)
+}
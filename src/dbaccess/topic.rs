@@ -1,160 +1,383 @@
+use super::{lost_update, record_rows, RowStream};
 use crate::errors::AppErrorType;
-use crate::models::topic::{CreateTopic, Topic, UpdateTopic};
-use actix_web::App;
-use chrono::{format, NaiveDateTime, Utc};
-use sqlx::postgres::PgPool;
-
-use super::tutor;
-
-pub async fn get_all_topics_db(pool: &PgPool) -> Result<Vec<Topic>, AppErrorType> {
-    let topic_rows = sqlx::query!("SELECT * FROM topic").fetch_all(pool).await?;
-
-    let topics: Vec<Topic> = topic_rows
-        .iter()
-        .map(|topic_row| Topic {
-            id: topic_row.id,
-            tutor_id: topic_row.tutor_id,
-            title: topic_row.title.clone(),
-            topic_description: topic_row.topic_description.clone(),
-            format: topic_row.format.clone(),
-            duration: topic_row.duration.clone(),
-            topic_level: topic_row.topic_level.clone(),
-            created_at: topic_row.created_at,
-            updated_at: topic_row.updated_at,
-        })
-        .collect();
-
-    match topics.len() {
-        0 => Err(AppErrorType::NotFoundError("No topics found".into())),
-        _ => Ok(topics),
-    }
+use crate::models::page::{order_by_sql, Page, PageRequest, SortKey};
+use crate::models::topic::{
+    CreateTopic, Topic, TopicFilter, TopicPatch, TopicSearchHit, UpdateTopic,
+};
+use async_stream::try_stream;
+use chrono::Utc;
+use futures::TryStreamExt;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::{QueryBuilder, Row, Transaction};
+use tracing::field::Empty;
+
+// Everything but `search_vector`, which only the search query reads
+pub(super) const TOPIC_COLUMNS: &str =
+    "id, tutor_id, title, topic_description, format, duration, topic_level, capacity, average_rating, review_count, created_at, updated_at, version";
+
+pub async fn get_all_topics_db(
+    pool: &PgPool,
+    filter: &TopicFilter,
+    page: &PageRequest,
+) -> Result<Page<Topic>, AppErrorType> {
+    let sort = filter.sort_keys(SortKey::asc("id"))?;
+
+    list_topics_db(pool, filter, &sort, page).await
 }
 
 pub async fn get_topics_for_tutor_db(
     pool: &PgPool,
     tutor_id: i32,
-) -> Result<Vec<Topic>, AppErrorType> {
-    let topic_rows = sqlx::query_as!(
-        Topic,
-        "SELECT * FROM topic where tutor_id = $1 order by id desc",
-        tutor_id
+    filter: &TopicFilter,
+    page: &PageRequest,
+) -> Result<Page<Topic>, AppErrorType> {
+    let filter = TopicFilter {
+        tutor_id: Some(tutor_id),
+        ..filter.clone()
+    };
+    // Newest first unless the client asks otherwise
+    let sort = filter.sort_keys(SortKey::desc("id"))?;
+
+    list_topics_db(pool, &filter, &sort, page).await
+}
+
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+async fn list_topics_db(
+    pool: &PgPool,
+    filter: &TopicFilter,
+    sort: &[SortKey],
+    page: &PageRequest,
+) -> Result<Page<Topic>, AppErrorType> {
+    let keyset = page.keyset_order(sort)?;
+
+    let mut query = QueryBuilder::new(format!(
+        "SELECT {} FROM topic WHERE deleted_at IS NULL",
+        TOPIC_COLUMNS
+    ));
+    push_topic_filters(&mut query, filter);
+    if let (Some(descending), Some(after_id)) = (keyset, page.after_id) {
+        query
+            .push(if descending {
+                " AND id < "
+            } else {
+                " AND id > "
+            })
+            .push_bind(after_id);
+    }
+    query
+        .push(" ORDER BY ")
+        .push(order_by_sql(sort))
+        .push(" LIMIT ")
+        .push_bind(page.fetch_limit())
+        .push(" OFFSET ")
+        .push_bind(page.offset);
+    let topic_rows = query.build_query_as::<Topic>().fetch_all(pool).await?;
+    record_rows(topic_rows.len() as u64);
+
+    let total = if page.include_total {
+        let mut count_query =
+            QueryBuilder::new("SELECT count(*) FROM topic WHERE deleted_at IS NULL");
+        push_topic_filters(&mut count_query, filter);
+        Some(count_query.build().fetch_one(pool).await?.get::<i64, _>(0))
+    } else {
+        None
+    };
+
+    Ok(match keyset {
+        Some(_) => Page::from_rows(topic_rows, page, total, |topic| topic.id),
+        None => Page::from_offset_rows(topic_rows, page, total),
+    })
+}
+
+pub fn stream_topics_db(
+    pool: PgPool,
+    filter: TopicFilter,
+) -> Result<RowStream<Topic>, AppErrorType> {
+    let sort = filter.sort_keys(SortKey::asc("id"))?;
+
+    Ok(topic_rows(pool, filter, sort))
+}
+
+pub fn stream_topics_for_tutor_db(
+    pool: PgPool,
+    tutor_id: i32,
+    filter: TopicFilter,
+) -> Result<RowStream<Topic>, AppErrorType> {
+    let filter = TopicFilter {
+        tutor_id: Some(tutor_id),
+        ..filter
+    };
+    let sort = filter.sort_keys(SortKey::desc("id"))?;
+
+    Ok(topic_rows(pool, filter, sort))
+}
+
+// The query only runs once the stream is first polled, and holds its pool connection
+// until the last row has been read or the stream is dropped
+fn topic_rows(pool: PgPool, filter: TopicFilter, sort: Vec<SortKey>) -> RowStream<Topic> {
+    Box::pin(try_stream! {
+        let mut query = QueryBuilder::new(format!(
+            "SELECT {} FROM topic WHERE deleted_at IS NULL",
+            TOPIC_COLUMNS
+        ));
+        push_topic_filters(&mut query, &filter);
+        query.push(" ORDER BY ").push(order_by_sql(&sort));
+
+        let mut rows = query.build_query_as::<Topic>().fetch(&pool);
+        while let Some(topic) = rows.try_next().await? {
+            yield topic;
+        }
+    })
+}
+
+fn push_topic_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &TopicFilter) {
+    if let Some(tutor_id) = filter.tutor_id {
+        query.push(" AND tutor_id = ").push_bind(tutor_id);
+    }
+    if let Some(topic_level) = &filter.topic_level {
+        query
+            .push(" AND topic_level = ")
+            .push_bind(topic_level.clone());
+    }
+    if let Some(format) = &filter.format {
+        query.push(" AND format = ").push_bind(format.clone());
+    }
+    if let Some(created_after) = filter.created_after {
+        query.push(" AND created_at >= ").push_bind(created_after);
+    }
+    if let Some(created_before) = filter.created_before {
+        query.push(" AND created_at < ").push_bind(created_before);
+    }
+}
+
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+pub async fn search_topics_db(
+    pool: &PgPool,
+    terms: &str,
+    page: &PageRequest,
+) -> Result<Page<TopicSearchHit>, AppErrorType> {
+    // Ranked by relevance, so only offset paging applies
+    page.keyset_order(&[SortKey::desc("rank")])?;
+
+    let hits = sqlx::query_as::<_, TopicSearchHit>(
+        "SELECT id, tutor_id, title, topic_description, format, duration, topic_level, capacity, average_rating, review_count, created_at, updated_at, version,
+            ts_rank(search_vector, query) AS rank,
+            ts_headline('english', title, query, 'HighlightAll=true') AS title_highlight,
+            CASE WHEN topic_description IS NULL THEN NULL
+                ELSE ts_headline('english', topic_description, query, 'MaxFragments=2, MaxWords=20, MinWords=5')
+            END AS snippet
+        FROM topic, websearch_to_tsquery('english', $1) AS query
+        WHERE search_vector @@ query AND deleted_at IS NULL
+        ORDER BY rank DESC, id
+        LIMIT $2 OFFSET $3",
     )
+    .bind(terms)
+    .bind(page.fetch_limit())
+    .bind(page.offset)
     .fetch_all(pool)
     .await?;
+    record_rows(hits.len() as u64);
+
+    let total = if page.include_total {
+        Some(
+            sqlx::query!(
+                r#"SELECT count(*) as "count!" FROM topic
+                WHERE search_vector @@ websearch_to_tsquery('english', $1) AND deleted_at IS NULL"#,
+                terms
+            )
+            .fetch_one(pool)
+            .await?
+            .count,
+        )
+    } else {
+        None
+    };
 
-    Ok(topic_rows)
+    Ok(Page::from_offset_rows(hits, page, total))
 }
 
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
 pub async fn get_topic_details_db(pool: &PgPool, topic_id: i32) -> Result<Topic, AppErrorType> {
-    let topic_row = sqlx::query_as!(Topic, "SELECT * FROM topic where id = $1", topic_id)
-        .fetch_optional(pool)
-        .await?;
+    let topic_row = sqlx::query_as!(
+        Topic,
+        "SELECT id, tutor_id, title, topic_description, format, duration, topic_level, capacity, average_rating, review_count, created_at, updated_at, version
+        FROM topic where id = $1 AND deleted_at IS NULL",
+        topic_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    record_rows(topic_row.is_some() as u64);
 
     if let Some(topic) = topic_row {
         Ok(topic)
     } else {
-        Err(AppErrorType::NotFoundError(
-            format!("No topic found for topic_id: {}", topic_id),
-        ))
+        Err(AppErrorType::NotFoundError(format!(
+            "No topic found for topic_id: {}",
+            topic_id
+        )))
     }
 }
 
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
 pub async fn post_new_topic_db(
     pool: &PgPool,
     new_topic: CreateTopic,
 ) -> Result<Topic, AppErrorType> {
     let current_time = Utc::now().naive_utc(); // Get the current time
 
+    // The foreign key still accepts a trashed tutor, so check they are live, and share-lock
+    // the row so they cannot be trashed before the insert commits
     let topic_row = sqlx::query_as!(
         Topic,
         "INSERT INTO topic (
-            tutor_id, title, topic_description, format, duration, topic_level, created_at, updated_at)
-            values ($1,$2,$3,$4,$5,$6,$7,$8) 
-            returning tutor_id, id, title, topic_description, duration, topic_level, format, created_at, updated_at", 
-    new_topic.tutor_id, new_topic.title, new_topic.topic_description, new_topic.format, new_topic.duration, new_topic.topic_level, current_time, current_time)
-    .fetch_one(pool)
-    .await?;
+            tutor_id, title, topic_description, format, duration, topic_level, capacity, created_at, updated_at, search_vector)
+            SELECT $1,$2,$3,$4,$5,$6,$7,$8,$9, topic_search_vector($2, $3)
+            WHERE EXISTS (SELECT 1 FROM tutor WHERE id = $1 AND deleted_at IS NULL FOR SHARE)
+            returning tutor_id, id, title, topic_description, duration, topic_level, format, capacity, average_rating, review_count, created_at, updated_at, version", 
+    new_topic.tutor_id, new_topic.title, new_topic.topic_description, new_topic.format, new_topic.duration, new_topic.topic_level, new_topic.capacity, current_time, current_time)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(AppErrorType::foreign_key_violation)?;
+    record_rows(1);
 
     Ok(topic_row)
 }
 
+// `if_match` holds the versions the client last saw, from `If-Match`; `None` updates
+// unconditionally. A conditional update that matches no row is reported as a failed
+// precondition, and the handler's follow-up read turns it into a 404 if the topic is gone.
+#[tracing::instrument(skip(pool, update_topic), fields(db.rows = Empty))]
 pub async fn update_topic_details_db(
     pool: &PgPool,
     tutor_id: i32,
     topic_id: i32,
     update_topic: UpdateTopic,
+    if_match: Option<&[i32]>,
 ) -> Result<Topic, AppErrorType> {
-    let topic_row = sqlx::query_as!(
+    let topic = sqlx::query_as!(
         Topic,
-        "SELECT * FROM topic where id = $1 and tutor_id = $2",
+        "UPDATE topic SET title = $1, topic_description = $2, format = $3, duration = $4, topic_level = $5,
+            capacity = $6, updated_at = $7, search_vector = topic_search_vector($1, $2), version = version + 1
+        WHERE id = $8 AND tutor_id = $9 AND deleted_at IS NULL AND ($10::int4[] IS NULL OR version = ANY($10))
+        RETURNING id, tutor_id, title, topic_description, format, duration, topic_level, capacity, average_rating, review_count, created_at, updated_at, version",
+        update_topic.title,
+        update_topic.topic_description,
+        update_topic.format,
+        update_topic.duration,
+        update_topic.topic_level,
+        update_topic.capacity,
+        Utc::now().naive_utc(),
         topic_id,
-        tutor_id
+        tutor_id,
+        if_match
     )
-    .fetch_one(pool)
-    .await
-    .map_err(|_err| AppErrorType::NotFoundError("Topic id not found".into()))?;
-
-    let update_time = Utc::now().naive_utc();
-
-    let title: String = if let Some(title) = update_topic.title {
-        title
-    } else {
-        topic_row.title
-    };
-
-    let topic_description: String = if let Some(topic_description) = update_topic.topic_description
-    {
-        topic_description
-    } else {
-        topic_row.topic_description.unwrap_or_default()
-    };
-
-    let format: String = if let Some(format) = update_topic.format {
-        format
-    } else {
-        topic_row.format.unwrap_or_default()
-    };
-
-    let duration: String = if let Some(duration) = update_topic.duration {
-        duration
-    } else {
-        topic_row.duration.unwrap_or_default()
-    };
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| lost_update(if_match, "Topic id not found"))?;
+    record_rows(1);
 
-    let topic_level: String = if let Some(topic_level) = update_topic.topic_level {
-        topic_level
-    } else {
-        topic_row.topic_level.unwrap_or_default()
-    };
+    Ok(topic)
+}
 
-    let updated_topic_row = sqlx::query_as!(
+// Each nullable column takes a "was it sent" flag next to its value, so `null` can clear it
+// while an absent field keeps the current value
+#[tracing::instrument(skip(pool, patch), fields(db.rows = Empty))]
+pub async fn patch_topic_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    topic_id: i32,
+    patch: TopicPatch,
+    if_match: Option<&[i32]>,
+) -> Result<Topic, AppErrorType> {
+    let topic = sqlx::query_as!(
         Topic,
-        "UPDATE topic SET title = $1, topic_description = $2, format = $3, duration = $4, topic_level = $5, updated_at = $6 where id = $7 and tutor_id = $8 
-        returning tutor_id, id, title, topic_description, duration, topic_level, format, created_at, updated_at", 
-        title, topic_description, format, duration, topic_level, update_time, topic_id, tutor_id
+        "UPDATE topic SET
+            title = COALESCE($1, title),
+            topic_description = CASE WHEN $2 THEN $3 ELSE topic_description END,
+            format = CASE WHEN $4 THEN $5 ELSE format END,
+            duration = CASE WHEN $6 THEN $7 ELSE duration END,
+            topic_level = CASE WHEN $8 THEN $9 ELSE topic_level END,
+            capacity = CASE WHEN $10 THEN $11 ELSE capacity END,
+            updated_at = $12,
+            search_vector = topic_search_vector(
+                COALESCE($1, title),
+                CASE WHEN $2 THEN $3 ELSE topic_description END
+            ),
+            version = version + 1
+        WHERE id = $13 AND tutor_id = $14 AND deleted_at IS NULL AND ($15::int4[] IS NULL OR version = ANY($15))
+        RETURNING id, tutor_id, title, topic_description, format, duration, topic_level, capacity, average_rating, review_count, created_at, updated_at, version",
+        patch.title,
+        patch.topic_description.is_some(),
+        patch.topic_description.flatten(),
+        patch.format.is_some(),
+        patch.format.flatten(),
+        patch.duration.is_some(),
+        patch.duration.flatten(),
+        patch.topic_level.is_some(),
+        patch.topic_level.flatten(),
+        patch.capacity.is_some(),
+        patch.capacity.flatten(),
+        Utc::now().naive_utc(),
+        topic_id,
+        tutor_id,
+        if_match
     )
-    .fetch_one(pool)
-    .await;
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| lost_update(if_match, "Topic id not found"))?;
+    record_rows(1);
 
-    match updated_topic_row {
-        Ok(topic_row) => Ok(topic_row),
-        Err(_err) => Err(AppErrorType::NotFoundError("Topic id not found".into())),
-    }
+    Ok(topic)
 }
 
+// A trashed topic's ratings come off its tutor's totals until it is restored
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
 pub async fn delete_topic_db(
     pool: &PgPool,
     tutor_id: i32,
     topic_id: i32,
 ) -> Result<String, AppErrorType> {
+    let mut tx = pool.begin().await?;
     let topic_row = sqlx::query!(
-        "DELETE FROM topic where id = $1 and tutor_id = $2 returning id",
+        "UPDATE topic SET deleted_at = $3 where id = $1 and tutor_id = $2 and deleted_at IS NULL
+        returning id, rating_sum, review_count",
         topic_id,
-        tutor_id
+        tutor_id,
+        Utc::now().naive_utc()
     )
-    .fetch_one(pool)
+    .fetch_one(&mut tx)
     .await
-    .map_err(|_err| AppErrorType::NotFoundError("Topic id not found".into()))?;
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found"))?;
+    if topic_row.review_count > 0 {
+        sqlx::query!(
+            "UPDATE tutor SET rating_sum = rating_sum - $2, review_count = review_count - $3,
+                version = version + 1
+            WHERE id = $1",
+            tutor_id,
+            topic_row.rating_sum,
+            topic_row.review_count
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+    record_rows(1);
 
     Ok(format!("Topic with id: {} deleted", topic_row.id))
 }
+
+// Whatever writes a tutor and their topics locks the topics first, in id order, then the tutor.
+// A review write only finds the tutor through its topic, so this is the order it takes too.
+pub(super) async fn lock_tutor_topics(
+    tx: &mut Transaction<'_, Postgres>,
+    tutor_id: i32,
+) -> Result<(), AppErrorType> {
+    sqlx::query!(
+        "SELECT id FROM topic WHERE tutor_id = $1 ORDER BY id FOR NO KEY UPDATE",
+        tutor_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use super::record_rows;
+use super::topic::lock_tutor_topics;
+use crate::errors::AppErrorType;
+use crate::models::topic::Topic;
+use crate::models::trash::{PurgeSummary, Trash, TrashedTopic, TrashedTutor, TutorRestoration};
+use crate::models::tutor::{TopicRef, Tutor};
+use chrono::{Duration, NaiveDateTime};
+use sqlx::postgres::PgPool;
+use tracing::field::Empty;
+
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+pub async fn list_trash_db(pool: &PgPool, retention: Duration) -> Result<Trash, AppErrorType> {
+    let tutors = sqlx::query_as!(
+        TrashedTutor,
+        r#"SELECT id, first_name, last_name, email,
+            deleted_at AS "deleted_at!", deleted_at + $1::bigint * INTERVAL '1 second' AS "purge_at!"
+        FROM tutor WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at, id"#,
+        retention.num_seconds()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let topics = sqlx::query_as!(
+        TrashedTopic,
+        r#"SELECT id, tutor_id, title,
+            deleted_at AS "deleted_at!", deleted_at + $1::bigint * INTERVAL '1 second' AS "purge_at!"
+        FROM topic WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at, id"#,
+        retention.num_seconds()
+    )
+    .fetch_all(pool)
+    .await?;
+    record_rows((tutors.len() + topics.len()) as u64);
+
+    Ok(Trash { tutors, topics })
+}
+
+// Brings the tutor back along with the topics that were trashed with them. Topics the tutor
+// had deleted individually before that stay in the trash.
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn restore_tutor_db(
+    pool: &PgPool,
+    tutor_id: i32,
+) -> Result<TutorRestoration, AppErrorType> {
+    let mut tx = pool.begin().await?;
+
+    lock_tutor_topics(&mut tx, tutor_id).await?;
+    let trashed = sqlx::query!(
+        r#"SELECT deleted_at AS "deleted_at!" FROM tutor
+        WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE"#,
+        tutor_id
+    )
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Tutor id not found in trash"))?;
+
+    let restored = sqlx::query!(
+        "UPDATE topic SET deleted_at = NULL WHERE tutor_id = $1 AND deleted_at = $2
+        RETURNING id, title, rating_sum, review_count",
+        tutor_id,
+        trashed.deleted_at
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    // The restored topics' ratings count towards the tutor again
+    let (rating_sum, review_count) = restored.iter().fold((0, 0), |(sum, count), topic| {
+        (sum + topic.rating_sum, count + topic.review_count)
+    });
+    let tutor = sqlx::query_as!(
+        Tutor,
+        "UPDATE tutor SET deleted_at = NULL, rating_sum = rating_sum + $2,
+            review_count = review_count + $3,
+            version = CASE WHEN $3 > 0 THEN version + 1 ELSE version END
+        WHERE id = $1
+        RETURNING id, first_name, last_name, email, average_rating, review_count, version",
+        tutor_id,
+        rating_sum,
+        review_count
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let mut topics: Vec<TopicRef> = restored
+        .into_iter()
+        .map(|topic| TopicRef {
+            id: topic.id,
+            title: topic.title,
+        })
+        .collect();
+    topics.sort_by_key(|topic| topic.id);
+
+    tx.commit().await?;
+    record_rows(topics.len() as u64 + 1);
+
+    Ok(TutorRestoration {
+        tutor,
+        topic_count: topics.len(),
+        topics,
+    })
+}
+
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn restore_topic_db(pool: &PgPool, topic_id: i32) -> Result<Topic, AppErrorType> {
+    let mut tx = pool.begin().await?;
+
+    // Only the topic is locked here, so the tutor's row is taken after it like everywhere else.
+    // Trashing or restoring the tutor locks this topic first, so the tutor's state holds.
+    let trashed = sqlx::query!(
+        r#"SELECT tutor.deleted_at IS NOT NULL AS "tutor_trashed!" FROM topic
+        JOIN tutor ON tutor.id = topic.tutor_id
+        WHERE topic.id = $1 AND topic.deleted_at IS NOT NULL
+        FOR UPDATE OF topic"#,
+        topic_id
+    )
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Topic id not found in trash"))?;
+    if trashed.tutor_trashed {
+        return Err(AppErrorType::ConflictError(
+            "The topic's tutor is in the trash; restore the tutor first".into(),
+        ));
+    }
+
+    let topic = sqlx::query_as!(
+        Topic,
+        "UPDATE topic SET deleted_at = NULL WHERE id = $1
+        RETURNING id, tutor_id, title, topic_description, format, duration, topic_level, capacity, average_rating, review_count, created_at, updated_at, version",
+        topic_id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+    if topic.review_count > 0 {
+        sqlx::query!(
+            "UPDATE tutor SET rating_sum = tutor.rating_sum + topic.rating_sum,
+                review_count = tutor.review_count + topic.review_count, version = tutor.version + 1
+            FROM topic WHERE topic.id = $1 AND tutor.id = topic.tutor_id",
+            topic_id
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    record_rows(1);
+
+    Ok(topic)
+}
+
+// Hard-deletes everything trashed before `cutoff`. Topics go first because of the foreign key.
+// Their reviews go with them; a trashed topic's ratings are already off its tutor's totals.
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn purge_trash_db(
+    pool: &PgPool,
+    cutoff: NaiveDateTime,
+) -> Result<PurgeSummary, AppErrorType> {
+    let mut tx = pool.begin().await?;
+
+    let topics = sqlx::query!(
+        "DELETE FROM topic WHERE deleted_at < $1
+            OR tutor_id IN (SELECT id FROM tutor WHERE deleted_at < $1)",
+        cutoff
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    let tutors = sqlx::query!("DELETE FROM tutor WHERE deleted_at < $1", cutoff)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+    tx.commit().await?;
+    record_rows(tutors + topics);
+
+    Ok(PurgeSummary { tutors, topics })
+}
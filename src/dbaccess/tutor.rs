@@ -1,52 +1,131 @@
+use super::topic::lock_tutor_topics;
+use super::{lost_update, record_rows, RowStream};
 use crate::errors::AppErrorType;
-use crate::models::tutor::{CreateTutor, Tutor, UpdateTutor};
-use sqlx::postgres::PgPool;
-
-pub async fn get_all_tutors_db(pool: &PgPool) -> Result<Vec<Tutor>, AppErrorType> {
-    let tutor_rows = sqlx::query!("SELECT id, first_name, last_name, email FROM tutor")
-        .fetch_all(pool)
-        .await?;
-
-    let tutors: Vec<Tutor> = tutor_rows
-        .iter()
-        .map(|tutor_row| Tutor {
-            id: tutor_row.id,
-            first_name: tutor_row.first_name.clone(),
-            last_name: tutor_row.last_name.clone(),
-            email: tutor_row.email.clone(),
-        })
-        .collect();
-
-    match tutors.len() {
-        0 => Err(AppErrorType::NotFoundError("No tutors found".into())),
-        _ => Ok(tutors),
+use crate::models::auth::TutorCredentials;
+use crate::models::page::{order_by_sql, Page, PageRequest};
+use crate::models::tutor::{
+    CreateTutor, TopicRef, Tutor, TutorDeletion, TutorFilter, TutorPatch, UpdateTutor,
+};
+use async_stream::try_stream;
+use chrono::Utc;
+use futures::TryStreamExt;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::{QueryBuilder, Row};
+use tracing::field::Empty;
+
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+pub async fn get_all_tutors_db(
+    pool: &PgPool,
+    filter: &TutorFilter,
+    page: &PageRequest,
+) -> Result<Page<Tutor>, AppErrorType> {
+    let sort = filter.sort_keys()?;
+    let keyset = page.keyset_order(&sort)?;
+
+    let mut query = QueryBuilder::new(
+        "SELECT id, first_name, last_name, email, average_rating, review_count, version FROM tutor WHERE deleted_at IS NULL",
+    );
+    push_tutor_filters(&mut query, filter);
+    if let (Some(descending), Some(after_id)) = (keyset, page.after_id) {
+        query
+            .push(if descending {
+                " AND id < "
+            } else {
+                " AND id > "
+            })
+            .push_bind(after_id);
+    }
+    query
+        .push(" ORDER BY ")
+        .push(order_by_sql(&sort))
+        .push(" LIMIT ")
+        .push_bind(page.fetch_limit())
+        .push(" OFFSET ")
+        .push_bind(page.offset);
+    let tutors = query.build_query_as::<Tutor>().fetch_all(pool).await?;
+    record_rows(tutors.len() as u64);
+
+    let total = if page.include_total {
+        let mut count_query =
+            QueryBuilder::new("SELECT count(*) FROM tutor WHERE deleted_at IS NULL");
+        push_tutor_filters(&mut count_query, filter);
+        Some(count_query.build().fetch_one(pool).await?.get::<i64, _>(0))
+    } else {
+        None
+    };
+
+    Ok(match keyset {
+        Some(_) => Page::from_rows(tutors, page, total, |tutor| tutor.id),
+        None => Page::from_offset_rows(tutors, page, total),
+    })
+}
+
+// Like `topic_rows`, runs on first poll and holds a connection until it ends
+pub fn stream_tutors_db(
+    pool: PgPool,
+    filter: TutorFilter,
+) -> Result<RowStream<Tutor>, AppErrorType> {
+    let sort = filter.sort_keys()?;
+
+    Ok(Box::pin(try_stream! {
+        let mut query = QueryBuilder::new(
+            "SELECT id, first_name, last_name, email, average_rating, review_count, version FROM tutor WHERE deleted_at IS NULL",
+        );
+        push_tutor_filters(&mut query, &filter);
+        query.push(" ORDER BY ").push(order_by_sql(&sort));
+
+        let mut rows = query.build_query_as::<Tutor>().fetch(&pool);
+        while let Some(tutor) = rows.try_next().await? {
+            yield tutor;
+        }
+    }))
+}
+
+fn push_tutor_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &TutorFilter) {
+    if let Some(prefix) = &filter.last_name_prefix {
+        // Match the prefix literally, case-insensitively
+        let pattern = prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        query
+            .push(" AND last_name ILIKE ")
+            .push_bind(format!("{}%", pattern));
     }
 }
 
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
 pub async fn post_new_tutor_db(
     pool: &PgPool,
     new_tutor: CreateTutor,
+    password_hash: String,
 ) -> Result<Tutor, AppErrorType> {
     let tutor_row = sqlx::query!(
-        "INSERT INTO tutor (first_name, last_name, email) VALUES ($1, $2, $3) RETURNING id, first_name, last_name, email",
+        "INSERT INTO tutor (first_name, last_name, email, password_hash) VALUES ($1, $2, $3, $4) RETURNING id, first_name, last_name, email, average_rating, review_count, version",
         new_tutor.first_name,
         new_tutor.last_name,
-        new_tutor.email
+        new_tutor.email,
+        password_hash
     )
     .fetch_one(pool)
     .await?;
+    record_rows(1);
 
     Ok(Tutor {
         id: tutor_row.id,
         first_name: tutor_row.first_name.clone(),
         last_name: tutor_row.last_name.clone(),
         email: tutor_row.email.clone(),
+        average_rating: tutor_row.average_rating,
+        review_count: tutor_row.review_count,
+        version: tutor_row.version,
     })
 }
 
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
 pub async fn get_tutor_details_db(pool: &PgPool, id: i32) -> Result<Tutor, AppErrorType> {
     let tutor_row = sqlx::query!(
-        "SELECT id, first_name, last_name, email FROM tutor where id = $1",
+        "SELECT id, first_name, last_name, email, average_rating, review_count, version FROM tutor where id = $1 AND deleted_at IS NULL",
         id
     )
     .fetch_one(pool)
@@ -56,79 +135,150 @@ pub async fn get_tutor_details_db(pool: &PgPool, id: i32) -> Result<Tutor, AppEr
         first_name: tutor_row.first_name,
         last_name: tutor_row.last_name,
         email: tutor_row.email,
+        average_rating: tutor_row.average_rating,
+        review_count: tutor_row.review_count,
+        version: tutor_row.version,
     })
-    .map_err(|_err| AppErrorType::NotFoundError("Tutor id not found".into()))?;
+    .map_err(|err| AppErrorType::from_lookup(err, "Tutor id not found"))?;
+    record_rows(1);
 
     Ok(tutor_row)
 }
 
+// `if_match` works as in `update_topic_details_db`
+#[tracing::instrument(skip(pool, update_tutor), fields(db.rows = Empty))]
 pub async fn update_tutor_details_db(
     pool: &PgPool,
     tutor_id: i32,
     update_tutor: UpdateTutor,
+    if_match: Option<&[i32]>,
 ) -> Result<Tutor, AppErrorType> {
-    let tutor_row = sqlx::query!(
-        "SELECT id, first_name, last_name, email FROM tutor where id = $1",
-        tutor_id
+    let tutor = sqlx::query_as!(
+        Tutor,
+        "UPDATE tutor SET first_name = $1, last_name = $2, email = $3, version = version + 1
+        WHERE id = $4 AND deleted_at IS NULL AND ($5::int4[] IS NULL OR version = ANY($5))
+        RETURNING id, first_name, last_name, email, average_rating, review_count, version",
+        update_tutor.first_name,
+        update_tutor.last_name,
+        update_tutor.email,
+        tutor_id,
+        if_match
     )
-    .fetch_one(pool)
-    .await
-    .map_err(|_err| AppErrorType::NotFoundError("Tutor id not found".into()))?;
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| lost_update(if_match, "Tutor id not found"))?;
+    record_rows(1);
 
-    let new_tutor_record = Tutor {
-        id: tutor_row.id,
+    Ok(tutor)
+}
 
-        first_name: if let Some(first_name) = update_tutor.first_name {
-            first_name
-        } else {
-            tutor_row.first_name
-        },
-
-        last_name: if let Some(last_name) = update_tutor.last_name {
-            last_name
-        } else {
-            tutor_row.last_name
-        },
-
-        email: if let Some(email) = update_tutor.email {
-            email
-        } else {
-            tutor_row.email
-        },
-    };
+// Applies a merge patch in the UPDATE itself, so fields the patch leaves out keep whatever
+// value the row has at that moment
+#[tracing::instrument(skip(pool, patch), fields(db.rows = Empty))]
+pub async fn patch_tutor_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    patch: TutorPatch,
+    if_match: Option<&[i32]>,
+) -> Result<Tutor, AppErrorType> {
+    let tutor = sqlx::query_as!(
+        Tutor,
+        "UPDATE tutor SET first_name = COALESCE($1, first_name), last_name = COALESCE($2, last_name),
+            email = COALESCE($3, email), version = version + 1
+        WHERE id = $4 AND deleted_at IS NULL AND ($5::int4[] IS NULL OR version = ANY($5))
+        RETURNING id, first_name, last_name, email, average_rating, review_count, version",
+        patch.first_name,
+        patch.last_name,
+        patch.email,
+        tutor_id,
+        if_match
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| lost_update(if_match, "Tutor id not found"))?;
+    record_rows(1);
 
-    let updated_tutor_row = sqlx::query!(
-        "UPDATE tutor SET first_name = $1, last_name = $2, email = $3 where id = $4 returning id, first_name, last_name, email", 
-        new_tutor_record.first_name, new_tutor_record.last_name, new_tutor_record.email, tutor_id
+    Ok(tutor)
+}
+
+// Moves the tutor and their topics to the trash in one transaction, all with the same
+// `deleted_at` so a restore can tell which topics went with the tutor. With no live topics
+// left, the tutor's rating totals drop to zero until the restore adds them back. A dry run performs
+// the same updates and rolls them back, so it reports exactly what a real delete would remove.
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn delete_tutor_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    dry_run: bool,
+) -> Result<TutorDeletion, AppErrorType> {
+    let deleted_at = Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+
+    // Once the tutor is locked no topic can be added for them, and the update below still
+    // trashes one added before that
+    lock_tutor_topics(&mut tx, tutor_id).await?;
+    sqlx::query!(
+        "UPDATE tutor SET deleted_at = $2, rating_sum = 0, review_count = 0
+        WHERE id = $1 AND deleted_at IS NULL RETURNING id",
+        tutor_id,
+        deleted_at
     )
-    .fetch_one(pool)
+    .fetch_one(&mut tx)
     .await
-    .map(|tutor_row| Tutor {
-            id: tutor_row.id,
-            first_name: tutor_row.first_name,
-            last_name: tutor_row.last_name,
-            email: tutor_row.email,
-        }
+    .map_err(|err| AppErrorType::from_lookup(err, "Tutor id not found"))?;
+
+    let mut topics = sqlx::query_as!(
+        TopicRef,
+        "UPDATE topic SET deleted_at = $2 WHERE tutor_id = $1 AND deleted_at IS NULL RETURNING id, title",
+        tutor_id,
+        deleted_at
+    )
+    .fetch_all(&mut tx)
+    .await?;
+    topics.sort_by_key(|topic| topic.id);
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+    record_rows(topics.len() as u64 + 1);
+
+    Ok(TutorDeletion {
+        tutor_id,
+        dry_run,
+        topic_count: topics.len(),
+        topics,
+    })
+}
+
+#[tracing::instrument(skip_all, fields(db.rows = Empty))]
+pub async fn get_tutor_credentials_db(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<TutorCredentials>, AppErrorType> {
+    let credentials = sqlx::query_as!(
+        TutorCredentials,
+        "SELECT id AS tutor_id, password_hash, role FROM tutor WHERE lower(email) = lower($1) AND deleted_at IS NULL",
+        email
     )
-    .map_err(|_err| AppErrorType::NotFoundError("Tutor id not found".into()))?;
-    Ok(updated_tutor_row)
+    .fetch_optional(pool)
+    .await?;
+    record_rows(credentials.is_some() as u64);
+
+    Ok(credentials)
 }
 
-pub async fn delete_tutor_db(pool: &PgPool, tutor_id: i32) -> Result<String, AppErrorType> {
-    let related_topics_data_rows = sqlx::query!("DELETE FROM topic where tutor_id = $1", tutor_id)
-        .execute(pool)
-        .await
-        .map_err(|_err| AppErrorType::DbError("Unable to delete related data for tutor ".into()))?;
-
-    let tutor_row = sqlx::query!("DELETE FROM tutor where id = $1", tutor_id)
-        .execute(pool)
-        .await
-        .map_err(|_err| AppErrorType::DbError("Unable to delete tutor ".into()))?;
-
-    Ok(format!(
-        "Deleted {} rows from tutor and {} rows from topic, record {:#?}",
-        tutor_row.rows_affected(),
-        related_topics_data_rows.rows_affected(),
-        tutor_row
-    ))
+#[tracing::instrument(skip(pool), fields(db.rows = Empty))]
+pub async fn get_tutor_role_db(pool: &PgPool, tutor_id: i32) -> Result<String, AppErrorType> {
+    let tutor_row = sqlx::query!(
+        "SELECT role FROM tutor WHERE id = $1 AND deleted_at IS NULL",
+        tutor_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| AppErrorType::from_lookup(err, "Tutor id not found"))?;
+    record_rows(1);
+
+    Ok(tutor_row.role)
 }
@@ -1,7 +1,19 @@
-use actix_web::{error, http::StatusCode, HttpResponse, Result};
-use serde::Serialize;
+use crate::metrics::METRICS;
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error,
+    http::{header, StatusCode},
+    middleware::Next,
+    HttpResponse, Result,
+};
+use serde::{Deserialize, Serialize};
 use sqlx::Error as SqlxError;
 use std::fmt;
+use utoipa::ToSchema;
+use validator::ValidationErrors;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
 
 #[derive(Debug, Serialize)]
 pub enum AppErrorType {
@@ -9,41 +21,154 @@ pub enum AppErrorType {
     NotFoundError(String),
     ActixError(String),
     InvalidInput(String),
+    ConflictError(String),
+    UnprocessableError(String),
+    ValidationError(Vec<FieldError>),
+    UnauthorizedError(String),
+    ForbiddenError(String),
+    PayloadTooLargeError(String),
+    PreconditionFailedError(String),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+// RFC 7807 problem details, with `code` as a stable machine-readable identifier
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AppErrorResponse {
-    error_message: String,
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
 }
 
 impl AppErrorType {
-    fn error_response(&self) -> String {
+    pub fn code(&self) -> &'static str {
         match self {
-            AppErrorType::DbError(error_message) => {
-                println!("DbError: {:?}", error_message);
-                error_message.to_string()
-            }
-            AppErrorType::NotFoundError(error_message) => {
-                println!("NotFoundError: {:?}", error_message);
-                error_message.to_string()
-            }
-            AppErrorType::ActixError(error_message) => {
-                println!("ActixError: {:?}", error_message);
-                error_message.to_string()
-            }
-            AppErrorType::InvalidInput(error_message) => {
-                println!("InvalidInput: {:?}", error_message);
-                error_message.to_string()
-            }
+            AppErrorType::DbError(_) => "database_error",
+            AppErrorType::NotFoundError(_) => "not_found",
+            AppErrorType::ActixError(_) => "internal_error",
+            AppErrorType::InvalidInput(_) => "invalid_input",
+            AppErrorType::ConflictError(_) => "conflict",
+            AppErrorType::UnprocessableError(_) => "unprocessable_entity",
+            AppErrorType::ValidationError(_) => "validation_failed",
+            AppErrorType::UnauthorizedError(_) => "unauthorized",
+            AppErrorType::ForbiddenError(_) => "forbidden",
+            AppErrorType::PayloadTooLargeError(_) => "payload_too_large",
+            AppErrorType::PreconditionFailedError(_) => "precondition_failed",
+        }
+    }
+
+    // The variant name, for log events
+    pub fn variant(&self) -> &'static str {
+        match self {
+            AppErrorType::DbError(_) => "DbError",
+            AppErrorType::NotFoundError(_) => "NotFoundError",
+            AppErrorType::ActixError(_) => "ActixError",
+            AppErrorType::InvalidInput(_) => "InvalidInput",
+            AppErrorType::ConflictError(_) => "ConflictError",
+            AppErrorType::UnprocessableError(_) => "UnprocessableError",
+            AppErrorType::ValidationError(_) => "ValidationError",
+            AppErrorType::UnauthorizedError(_) => "UnauthorizedError",
+            AppErrorType::ForbiddenError(_) => "ForbiddenError",
+            AppErrorType::PayloadTooLargeError(_) => "PayloadTooLargeError",
+            AppErrorType::PreconditionFailedError(_) => "PreconditionFailedError",
+        }
+    }
+
+    pub fn unique_violation() -> Self {
+        AppErrorType::ConflictError("A record with the same unique value already exists".into())
+    }
+
+    pub fn exclusion_violation() -> Self {
+        AppErrorType::ConflictError("The time overlaps another one already scheduled".into())
+    }
+
+    pub fn foreign_key_violation() -> Self {
+        AppErrorType::UnprocessableError(
+            "The request refers to a record that does not exist or is still referenced".into(),
+        )
+    }
+
+    pub fn precondition_failed() -> Self {
+        AppErrorType::PreconditionFailedError(
+            "The resource has changed since the version named in If-Match".into(),
+        )
+    }
+
+    // Lookups by id report a missing row as 404 and anything else as a database error
+    pub fn from_lookup(error: SqlxError, not_found_message: &str) -> Self {
+        match error {
+            SqlxError::RowNotFound => AppErrorType::NotFoundError(not_found_message.into()),
+            error => error.into(),
+        }
+    }
+
+    // 5xx details are generic so driver and framework messages never reach the client
+    fn detail(&self) -> String {
+        match self {
+            AppErrorType::DbError(_) => "An unexpected database error occurred".to_string(),
+            AppErrorType::ActixError(_) => "An unexpected internal error occurred".to_string(),
+            AppErrorType::NotFoundError(error_message)
+            | AppErrorType::InvalidInput(error_message)
+            | AppErrorType::ConflictError(error_message)
+            | AppErrorType::UnprocessableError(error_message)
+            | AppErrorType::UnauthorizedError(error_message)
+            | AppErrorType::ForbiddenError(error_message)
+            | AppErrorType::PayloadTooLargeError(error_message)
+            | AppErrorType::PreconditionFailedError(error_message) => error_message.to_string(),
+            AppErrorType::ValidationError(_) => "The request body failed validation".to_string(),
         }
     }
+
+    pub fn problem(&self) -> AppErrorResponse {
+        let status = error::ResponseError::status_code(self);
+        AppErrorResponse {
+            problem_type: format!("/problems/{}", self.code().replace('_', "-")),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            instance: None,
+            code: self.code().to_string(),
+            errors: match self {
+                AppErrorType::ValidationError(errors) => errors.clone(),
+                _ => Vec::new(),
+            },
+        }
+    }
+}
+
+impl AppErrorResponse {
+    fn into_response(self) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = HttpResponse::build(status);
+        if status == StatusCode::UNAUTHORIZED {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        response.content_type(PROBLEM_JSON).json(self)
+    }
 }
 
 impl error::ResponseError for AppErrorType {
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).json(AppErrorResponse {
-            error_message: self.error_response(),
-        })
+        METRICS.count_error(self);
+        // The full message is logged here, including the driver detail the client never sees
+        if self.status_code().is_server_error() {
+            tracing::error!(error.variant = self.variant(), error.code = self.code(), error = %self, "request error");
+        } else {
+            tracing::info!(error.variant = self.variant(), error.code = self.code(), error = %self, "request error");
+        }
+        self.problem().into_response()
     }
 
     fn status_code(&self) -> StatusCode {
@@ -52,24 +177,120 @@ impl error::ResponseError for AppErrorType {
             AppErrorType::NotFoundError(_) => StatusCode::NOT_FOUND,
             AppErrorType::ActixError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppErrorType::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            AppErrorType::ConflictError(_) => StatusCode::CONFLICT,
+            AppErrorType::UnprocessableError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppErrorType::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppErrorType::UnauthorizedError(_) => StatusCode::UNAUTHORIZED,
+            AppErrorType::ForbiddenError(_) => StatusCode::FORBIDDEN,
+            AppErrorType::PayloadTooLargeError(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppErrorType::PreconditionFailedError(_) => StatusCode::PRECONDITION_FAILED,
         }
     }
 }
 
 impl fmt::Display for AppErrorType {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{}", self)
+        match self {
+            AppErrorType::DbError(error_message)
+            | AppErrorType::NotFoundError(error_message)
+            | AppErrorType::ActixError(error_message)
+            | AppErrorType::InvalidInput(error_message)
+            | AppErrorType::ConflictError(error_message)
+            | AppErrorType::UnprocessableError(error_message)
+            | AppErrorType::UnauthorizedError(error_message)
+            | AppErrorType::ForbiddenError(error_message)
+            | AppErrorType::PayloadTooLargeError(error_message)
+            | AppErrorType::PreconditionFailedError(error_message) => {
+                write!(f, "{}: {}", self.code(), error_message)
+            }
+            AppErrorType::ValidationError(errors) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                write!(f, "{}: {}", self.code(), fields.join(", "))
+            }
+        }
     }
 }
 
 impl From<SqlxError> for AppErrorType {
     fn from(error: SqlxError) -> Self {
-        AppErrorType::DbError(error.to_string())
+        // Map constraint violations by SQLSTATE; the driver message stays in the server log
+        let code = match &error {
+            SqlxError::Database(db_error) => db_error.code().map(|code| code.into_owned()),
+            _ => None,
+        };
+        let mapped = match code.as_deref() {
+            Some("23505") => AppErrorType::unique_violation(),
+            Some("23503") => AppErrorType::foreign_key_violation(),
+            Some("23P01") => AppErrorType::exclusion_violation(),
+            Some("23514") => {
+                AppErrorType::InvalidInput("A value is outside the allowed range".into())
+            }
+            _ => return AppErrorType::DbError(error.to_string()),
+        };
+        tracing::info!(error = %error, "constraint violation");
+        mapped
+    }
+}
+
+impl From<ValidationErrors> for AppErrorType {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut field_errors: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| FieldError {
+                    field: field.to_string(),
+                    code: error.code.to_string(),
+                    message: error
+                        .message
+                        .as_ref()
+                        .map(|message| message.to_string())
+                        .unwrap_or_else(|| error.code.to_string()),
+                })
+            })
+            .collect();
+        // field_errors() is a HashMap, so sort for a stable response
+        field_errors.sort_by(|a, b| (&a.field, &a.code).cmp(&(&b.field, &b.code)));
+        AppErrorType::ValidationError(field_errors)
     }
 }
 
 impl From<actix_web::Error> for AppErrorType {
     fn from(error: actix_web::Error) -> Self {
+        // Bodies over `server.json_limit_bytes` are the client's problem, not ours
+        if error.as_response_error().status_code() == StatusCode::PAYLOAD_TOO_LARGE {
+            return AppErrorType::PayloadTooLargeError(
+                "The request body is larger than the server accepts".into(),
+            );
+        }
         AppErrorType::ActixError(error.to_string())
     }
 }
+
+impl From<error::BlockingError> for AppErrorType {
+    fn from(error: error::BlockingError) -> Self {
+        AppErrorType::ActixError(error.to_string())
+    }
+}
+
+// Fills in the problem `instance` with the request path, which `ResponseError` cannot see
+pub async fn problem_instance(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let res = next.call(req).await?;
+    let problem = res
+        .response()
+        .error()
+        .and_then(|err| err.as_error::<AppErrorType>())
+        .map(AppErrorType::problem);
+
+    match problem {
+        Some(mut problem) => {
+            problem.instance = Some(res.request().path().to_string());
+            let (req, _) = res.into_parts();
+            Ok(ServiceResponse::new(req, problem.into_response()))
+        }
+        None => Ok(res.map_into_boxed_body()),
+    }
+}
@@ -0,0 +1,162 @@
+use crate::errors::AppErrorType;
+use crate::models::topic::Topic;
+use crate::models::tutor::Tutor;
+use actix_web::{
+    dev::Payload,
+    http::header::{self, EntityTag, Header, IfMatch, IfNoneMatch},
+    FromRequest, HttpRequest, HttpResponse,
+};
+use serde::Serialize;
+use std::future::{ready, Future, Ready};
+
+// Resources whose `version` column is served as a strong ETag
+pub trait Versioned {
+    fn version(&self) -> i32;
+
+    fn entity_tag(&self) -> EntityTag {
+        EntityTag::new_strong(self.version().to_string())
+    }
+}
+
+impl Versioned for Tutor {
+    fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+impl Versioned for Topic {
+    fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+// The versions named by `If-Match`, for the repository's conditional updates. `None` when
+// the header is absent or `*`, which makes the update unconditional. Weak or non-numeric
+// tags can never match, so a header made only of those fails with 412.
+#[derive(Debug, Clone, Default)]
+pub struct IfMatchVersions(pub Option<Vec<i32>>);
+
+impl IfMatchVersions {
+    pub fn as_deref(&self) -> Option<&[i32]> {
+        self.0.as_deref()
+    }
+}
+
+impl FromRequest for IfMatchVersions {
+    type Error = AppErrorType;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        if !req.headers().contains_key(header::IF_MATCH) {
+            return ready(Ok(IfMatchVersions(None)));
+        }
+        ready(match IfMatch::parse(req) {
+            Ok(IfMatch::Any) => Ok(IfMatchVersions(None)),
+            Ok(IfMatch::Items(tags)) => Ok(IfMatchVersions(Some(
+                tags.iter()
+                    .filter(|tag| !tag.weak)
+                    .filter_map(|tag| tag.tag().parse().ok())
+                    .collect(),
+            ))),
+            Err(_err) => Err(AppErrorType::InvalidInput(
+                "If-Match must be * or a list of entity tags".into(),
+            )),
+        })
+    }
+}
+
+// 200 with the resource and its ETag, or 304 when `If-None-Match` already names that version
+pub fn conditional_get<T: Serialize + Versioned>(req: &HttpRequest, resource: &T) -> HttpResponse {
+    let etag = resource.entity_tag();
+    if not_modified(req, &etag) {
+        HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .finish()
+    } else {
+        tagged(resource)
+    }
+}
+
+// Whether `If-None-Match` is `*` or names `etag`, compared weakly as RFC 9110 asks for GET
+pub fn not_modified(req: &HttpRequest, etag: &EntityTag) -> bool {
+    match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        Err(_err) => false,
+    }
+}
+
+// 200 with the resource and its ETag
+pub fn tagged<T: Serialize + Versioned>(resource: &T) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(header::ETag(resource.entity_tag()))
+        .json(resource)
+}
+
+// The response to a conditional update: the written resource with its new ETag, or when
+// `If-Match` was stale a 412 with the current representation, so the client can redo its
+// change on top of it. `current` is only awaited in the second case.
+pub async fn updated<T: Serialize + Versioned>(
+    result: Result<T, AppErrorType>,
+    current: impl Future<Output = Result<T, AppErrorType>>,
+) -> Result<HttpResponse, AppErrorType> {
+    match result {
+        Ok(resource) => Ok(tagged(&resource)),
+        Err(AppErrorType::PreconditionFailedError(_)) => {
+            let current = current.await?;
+            Ok(HttpResponse::PreconditionFailed()
+                .insert_header(header::ETag(current.entity_tag()))
+                .json(current))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    fn if_match(value: &str) -> Option<Vec<i32>> {
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, value))
+            .to_http_request();
+        IfMatchVersions::extract(&req).into_inner().unwrap().0
+    }
+
+    #[test]
+    fn if_match_lists_strong_numeric_versions() {
+        assert_eq!(if_match("*"), None);
+        assert_eq!(if_match(r#""3", "4""#), Some(vec![3, 4]));
+        assert_eq!(if_match(r#"W/"3", "abc""#), Some(vec![]));
+
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(IfMatchVersions::extract(&req).into_inner().unwrap().0, None);
+    }
+
+    #[test]
+    fn if_none_match_on_the_current_version_is_not_modified() {
+        let tutor = Tutor {
+            id: 1,
+            first_name: "Mark".into(),
+            last_name: "Smith".into(),
+            email: "mark@fakemail.com".into(),
+            average_rating: None,
+            review_count: 0,
+            version: 2,
+        };
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, r#"W/"2""#))
+            .to_http_request();
+        let resp = conditional_get(&req, &tutor);
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), r#""2""#);
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, r#""1""#))
+            .to_http_request();
+        assert_eq!(conditional_get(&req, &tutor).status(), StatusCode::OK);
+    }
+}
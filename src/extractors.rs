@@ -0,0 +1,70 @@
+use crate::errors::AppErrorType;
+use actix_web::{dev::Payload, web, FromRequest, HttpMessage, HttpRequest};
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use validator::Validate;
+
+// JSON body that has been deserialized and then checked against its `Validate` rules.
+// Malformed JSON is a 400 naming the offending path, line and column; rule failures are a 422.
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = AppErrorType;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let is_json = matches!(
+            req.mime_type(),
+            Ok(Some(mime)) if mime.subtype() == "json" || mime.suffix().is_some_and(|s| s == "json")
+        );
+        let body = web::Bytes::from_request(req, payload);
+
+        Box::pin(async move {
+            if !is_json {
+                return Err(AppErrorType::InvalidInput(
+                    "Content type must be application/json".into(),
+                ));
+            }
+            let body = body.await?;
+            let value = parse_json::<T>(&body)?;
+            value.validate()?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, AppErrorType> {
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|err| {
+        match err.path().to_string().as_str() {
+            "." => AppErrorType::InvalidInput(format!("Invalid JSON input: {}", err.inner())),
+            path => AppErrorType::InvalidInput(format!(
+                "Invalid JSON input at {}: {}",
+                path,
+                err.inner()
+            )),
+        }
+    })?;
+    deserializer
+        .end()
+        .map_err(|err| AppErrorType::InvalidInput(format!("Invalid JSON input: {}", err)))?;
+
+    Ok(value)
+}
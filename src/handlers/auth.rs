@@ -0,0 +1,294 @@
+use crate::auth::{verify_password, Role};
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::extractors::ValidatedJson;
+use crate::models::auth::{LoginRequest, RefreshRequest, TokenResponse};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Access and refresh tokens", body = TokenResponse),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Invalid email or password", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn login(
+    app_state: web::Data<AppState>,
+    credentials: ValidatedJson<LoginRequest>,
+) -> Result<HttpResponse, AppErrorType> {
+    let LoginRequest { email, password } = credentials.into_inner();
+
+    let tutor = app_state.tutors.get_tutor_credentials(&email).await?;
+    let password_hash = tutor.as_ref().and_then(|tutor| tutor.password_hash.clone());
+    check_password(password, password_hash).await?;
+    let tutor = tutor.ok_or_else(invalid_login)?;
+
+    let role: Role = tutor.role.parse()?;
+    app_state
+        .jwt
+        .issue_tokens(tutor.tutor_id, role)
+        .map(|tokens| HttpResponse::Ok().json(tokens))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/student-login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Access and refresh tokens for the student", body = TokenResponse),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Invalid email or password", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn student_login(
+    app_state: web::Data<AppState>,
+    credentials: ValidatedJson<LoginRequest>,
+) -> Result<HttpResponse, AppErrorType> {
+    let LoginRequest { email, password } = credentials.into_inner();
+
+    let student = app_state.students.get_student_credentials(&email).await?;
+    let password_hash = student
+        .as_ref()
+        .and_then(|student| student.password_hash.clone());
+    check_password(password, password_hash).await?;
+    let student = student.ok_or_else(invalid_login)?;
+
+    app_state
+        .jwt
+        .issue_tokens(student.student_id, Role::Student)
+        .map(|tokens| HttpResponse::Ok().json(tokens))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "A new pair of tokens", body = TokenResponse),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Invalid or expired refresh token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn refresh(
+    app_state: web::Data<AppState>,
+    request: ValidatedJson<RefreshRequest>,
+) -> Result<HttpResponse, AppErrorType> {
+    let identity = app_state.jwt.verify_refresh_token(&request.refresh_token)?;
+    // Look the subject up again: a tutor may have been deleted or had their role changed,
+    // and a student may have been deleted, since the refresh token was issued
+    let role: Role = match identity.role {
+        Role::Student => app_state
+            .students
+            .get_student_details(identity.subject_id)
+            .await
+            .map(|_student| Role::Student),
+        _ => match app_state.tutors.get_tutor_role(identity.subject_id).await {
+            Ok(role) => role.parse(),
+            Err(err) => Err(err),
+        },
+    }
+    .map_err(|err| match err {
+        AppErrorType::NotFoundError(_) => {
+            AppErrorType::UnauthorizedError("Invalid or expired token".into())
+        }
+        err => err,
+    })?;
+
+    app_state
+        .jwt
+        .issue_tokens(identity.subject_id, role)
+        .map(|tokens| HttpResponse::Ok().json(tokens))
+}
+
+fn invalid_login() -> AppErrorType {
+    AppErrorType::UnauthorizedError("Invalid email or password".into())
+}
+
+// Argon2 hash of a password no account has, with the same parameters as real hashes
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$uRBgqi1mrfG7WqGY+ONu/Q$Y0MuQFw0jtHb6GgitTEy3clqyqSS5HwWq95X1gPS8fQ";
+
+// Accounts without a password hash cannot log in. Unknown emails and such accounts are still
+// checked against a dummy hash, so every attempt costs one Argon2 verification and the
+// response time does not tell which emails have accounts.
+async fn check_password(
+    password: String,
+    password_hash: Option<String>,
+) -> Result<(), AppErrorType> {
+    let has_password = password_hash.is_some();
+    let password_hash = password_hash.unwrap_or_else(|| DUMMY_PASSWORD_HASH.to_string());
+    // Argon2 is deliberately slow, so keep it off the async workers
+    let verified = web::block(move || verify_password(&password, &password_hash)).await?;
+    if !(has_password && verified) {
+        return Err(invalid_login());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Identity;
+    use crate::handlers::testing::test_app_state;
+    use actix_web::body::to_bytes;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, FromRequest, ResponseError};
+
+    fn login_request(email: &str, password: &str) -> ValidatedJson<LoginRequest> {
+        ValidatedJson(LoginRequest {
+            email: email.into(),
+            password: password.into(),
+        })
+    }
+
+    #[actix_rt::test]
+    async fn login_issues_tokens_that_identify_the_tutor() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+
+        let resp = login(
+            app_state.clone(),
+            login_request("Bob.Lopez@fakemail.com", "tutor-password"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let tokens: TokenResponse =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(tokens.token_type, "Bearer");
+        let req = test::TestRequest::default()
+            .app_data(app_state)
+            .insert_header((
+                header::AUTHORIZATION,
+                format!("Bearer {}", tokens.access_token),
+            ))
+            .to_http_request();
+        let identity = Identity::extract(&req).await.unwrap();
+        assert_eq!(identity.subject_id, 3);
+        assert_eq!(identity.role, Role::Tutor);
+    }
+
+    #[actix_rt::test]
+    async fn student_login_issues_student_tokens_that_refresh() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+
+        let err = login(
+            app_state.clone(),
+            login_request("alice.nguyen@fakemail.com", "student-password"),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+
+        let resp = student_login(
+            app_state.clone(),
+            login_request("alice.nguyen@fakemail.com", "student-password"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let tokens: TokenResponse =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        let identity = app_state
+            .jwt
+            .verify_refresh_token(&tokens.refresh_token)
+            .unwrap();
+        assert_eq!(identity.role, Role::Student);
+        assert_eq!(
+            identity
+                .require_owner(identity.subject_id)
+                .unwrap_err()
+                .status_code(),
+            StatusCode::FORBIDDEN
+        );
+
+        let resp = refresh(
+            app_state,
+            ValidatedJson(RefreshRequest {
+                refresh_token: tokens.refresh_token,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn login_rejects_wrong_password() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+
+        let err = login(app_state, login_request("mark@fakemail.com", "guess"))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn unknown_emails_are_checked_against_the_dummy_hash() {
+        assert!(!verify_password("guess", DUMMY_PASSWORD_HASH));
+        // A well-formed hash, so the verification does the full Argon2 work
+        assert!(argon2::PasswordHash::new(DUMMY_PASSWORD_HASH).is_ok());
+
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let err = login(app_state, login_request("nobody@fakemail.com", "guess"))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn refresh_rejects_access_token() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let tokens = app_state.jwt.issue_tokens(1, Role::Tutor).unwrap();
+
+        let err = refresh(
+            app_state.clone(),
+            ValidatedJson(RefreshRequest {
+                refresh_token: tokens.access_token,
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+
+        let resp = refresh(
+            app_state,
+            ValidatedJson(RefreshRequest {
+                refresh_token: tokens.refresh_token,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn identity_requires_bearer_token() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let req = test::TestRequest::default()
+            .app_data(app_state)
+            .to_http_request();
+
+        let err = Identity::extract(&req).await.unwrap_err();
+
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            err.error_response()
+                .headers()
+                .get(header::WWW_AUTHENTICATE)
+                .unwrap(),
+            "Bearer"
+        );
+    }
+}
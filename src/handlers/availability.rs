@@ -0,0 +1,498 @@
+use crate::auth::{Identity, Role};
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::extractors::ValidatedJson;
+use crate::models::availability::{
+    Availability, AvailabilityException, Booking, ExceptionWindows, NewBooking, Slot, SlotParams,
+    WeeklyAvailability,
+};
+use crate::models::session::DateRange;
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+use chrono::NaiveDate;
+
+#[utoipa::path(
+    get,
+    path = "/tutors/{tutor_id}/availability",
+    tag = "availability",
+    params(("tutor_id" = i32, Path, description = "Tutor id")),
+    responses(
+        (status = 200, description = "The tutor's weekly windows and date exceptions", body = Availability),
+        (status = 404, description = "Tutor not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn get_availability(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let tutor_id = params.0;
+
+    app_state
+        .availability
+        .get_availability(tutor_id)
+        .await
+        .map(|availability| HttpResponse::Ok().json(availability))
+}
+
+#[utoipa::path(
+    put,
+    path = "/tutors/{tutor_id}/availability",
+    tag = "availability",
+    params(("tutor_id" = i32, Path, description = "Tutor id")),
+    request_body = WeeklyAvailability,
+    responses(
+        (status = 200, description = "The tutor's availability with the new weekly windows", body = Availability),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Tutor not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn put_weekly_availability(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    weekly: ValidatedJson<WeeklyAvailability>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let tutor_id = params.0;
+    identity.require_owner(tutor_id)?;
+
+    app_state
+        .availability
+        .put_weekly_availability(tutor_id, weekly.into_inner())
+        .await
+        .map(|availability| HttpResponse::Ok().json(availability))
+}
+
+#[utoipa::path(
+    put,
+    path = "/tutors/{tutor_id}/availability/exceptions/{date}",
+    tag = "availability",
+    params(
+        ("tutor_id" = i32, Path, description = "Tutor id"),
+        ("date" = NaiveDate, Path, description = "Date in the tutor's time zone, such as 2024-06-10"),
+    ),
+    request_body = ExceptionWindows,
+    responses(
+        (status = 200, description = "The windows that now apply on the date", body = AvailabilityException),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Tutor not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn put_availability_exception(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, NaiveDate)>,
+    exception: ValidatedJson<ExceptionWindows>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let (tutor_id, date) = (params.0, params.1);
+    identity.require_owner(tutor_id)?;
+
+    app_state
+        .availability
+        .put_availability_exception(tutor_id, date, exception.into_inner().windows)
+        .await
+        .map(|exception| HttpResponse::Ok().json(exception))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tutors/{tutor_id}/availability/exceptions/{date}",
+    tag = "availability",
+    params(
+        ("tutor_id" = i32, Path, description = "Tutor id"),
+        ("date" = NaiveDate, Path, description = "Date in the tutor's time zone, such as 2024-06-10"),
+    ),
+    responses(
+        (status = 200, description = "Confirmation message", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "No exception on that date", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn delete_availability_exception(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, NaiveDate)>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let (tutor_id, date) = (params.0, params.1);
+    identity.require_owner(tutor_id)?;
+
+    app_state
+        .availability
+        .delete_availability_exception(tutor_id, date)
+        .await
+        .map(|message| HttpResponse::Ok().json(message))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tutors/{tutor_id}/slots",
+    tag = "availability",
+    params(("tutor_id" = i32, Path, description = "Tutor id"), SlotParams),
+    responses(
+        (status = 200, description = "Free slots in start order, with times in the tutor's time zone", body = Vec<Slot>),
+        (status = 400, description = "Malformed query parameters, a range that is reversed or too long, or an unsupported duration", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Tutor not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn get_free_slots(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    slot_params: web::Query<SlotParams>,
+) -> Result<HttpResponse, AppErrorType> {
+    let tutor_id = params.0;
+    let range = slot_params.interval()?;
+    let length = slot_params.length()?;
+
+    app_state
+        .availability
+        .get_free_slots(tutor_id, range, length)
+        .await
+        .map(|slots| HttpResponse::Ok().json(slots))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tutors/{tutor_id}/bookings",
+    tag = "availability",
+    params(("tutor_id" = i32, Path, description = "Tutor id"), DateRange),
+    responses(
+        (status = 200, description = "Bookings overlapping the range, in start order", body = Vec<Booking>),
+        (status = 400, description = "Malformed query parameters, or a range that is reversed or too long", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not see this tutor's bookings", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Tutor not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_bookings(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    range: web::Query<DateRange>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let tutor_id = params.0;
+    identity.require_owner(tutor_id)?;
+    let range = range.interval()?;
+
+    app_state
+        .availability
+        .get_bookings(tutor_id, range)
+        .await
+        .map(|bookings| HttpResponse::Ok().json(bookings))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tutors/{tutor_id}/bookings",
+    tag = "availability",
+    params(("tutor_id" = i32, Path, description = "Tutor id")),
+    request_body = NewBooking,
+    responses(
+        (status = 200, description = "The calling student's booking", body = Booking),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not a student", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Tutor not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The time is outside the tutor's availability, or already taken by a booking or session", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation, or the student no longer exists", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn book_slot(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    booking: ValidatedJson<NewBooking>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let tutor_id = params.0;
+    let student_id = identity.require_student()?;
+
+    app_state
+        .availability
+        .book_slot(tutor_id, student_id, booking.into_inner())
+        .await
+        .map(|booking| HttpResponse::Ok().json(booking))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tutors/{tutor_id}/bookings/{booking_id}",
+    tag = "availability",
+    params(("tutor_id" = i32, Path, description = "Tutor id"), ("booking_id" = i32, Path, description = "Booking id")),
+    responses(
+        (status = 200, description = "Confirmation message", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Booking not found, or not the calling student's", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn cancel_booking(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let (tutor_id, booking_id) = (params.0, params.1);
+    // A student cancels only their own bookings; the tutor and admins any of the tutor's
+    let student_id = match identity.role {
+        Role::Student => Some(identity.subject_id),
+        _ => {
+            identity.require_owner(tutor_id)?;
+            None
+        }
+    };
+
+    app_state
+        .availability
+        .cancel_booking(tutor_id, booking_id, student_id)
+        .await
+        .map(|message| HttpResponse::Ok().json(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::testing::test_app_state;
+    use crate::models::availability::{TimeWindow, WeeklyWindow};
+    use crate::models::session::SessionSchedule;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+    use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc};
+
+    // Tutor 3 teaches topics 3 and 4
+    const TEACHER: Identity = Identity {
+        subject_id: 3,
+        role: Role::Tutor,
+    };
+
+    fn student(subject_id: i32) -> Identity {
+        Identity {
+            subject_id,
+            role: Role::Student,
+        }
+    }
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+    }
+
+    // Next Monday, at least a day away so nothing falls before now
+    fn next_monday() -> NaiveDate {
+        let tomorrow = Utc::now().date_naive() + Duration::days(1);
+        tomorrow + Duration::days((7 - tomorrow.weekday().num_days_from_monday() as i64) % 7)
+    }
+
+    async fn mondays_nine_to_eleven(app_state: &web::Data<AppState>) {
+        put_weekly_availability(
+            app_state.clone(),
+            web::Path::from((3,)),
+            ValidatedJson(WeeklyAvailability {
+                time_zone: "UTC".into(),
+                windows: vec![WeeklyWindow {
+                    weekday: 1,
+                    starts_at: time("09:00"),
+                    ends_at: time("11:00"),
+                }],
+            }),
+            TEACHER,
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn free_slots(app_state: &web::Data<AppState>, date: NaiveDate) -> Vec<Slot> {
+        let query = format!("from={}&to={}&duration=60", date, date);
+        let resp = get_free_slots(
+            app_state.clone(),
+            web::Path::from((3,)),
+            web::Query::<SlotParams>::from_query(&query).unwrap(),
+        )
+        .await
+        .unwrap();
+        serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap()
+    }
+
+    fn booking(slot: &Slot) -> ValidatedJson<NewBooking> {
+        ValidatedJson(NewBooking {
+            starts_at: slot.starts_at,
+            ends_at: slot.ends_at,
+        })
+    }
+
+    #[actix_rt::test]
+    async fn a_booked_slot_is_no_longer_free() {
+        let app_state = test_app_state().await;
+        mondays_nine_to_eleven(&app_state).await;
+        let monday = next_monday();
+
+        let slots = free_slots(&app_state, monday).await;
+        assert_eq!(slots.len(), 2);
+
+        let resp = book_slot(
+            app_state.clone(),
+            web::Path::from((3,)),
+            booking(&slots[0]),
+            student(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let booked: Booking =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(booked.student_id, 1);
+        assert_eq!(free_slots(&app_state, monday).await, slots[1..]);
+
+        // A second student on the same slot, and a time outside the windows
+        let err = book_slot(
+            app_state.clone(),
+            web::Path::from((3,)),
+            booking(&slots[0]),
+            student(2),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        let late = Slot {
+            starts_at: slots[1].starts_at + Duration::minutes(30),
+            ends_at: slots[1].ends_at + Duration::minutes(30),
+        };
+        let err = book_slot(
+            app_state.clone(),
+            web::Path::from((3,)),
+            booking(&late),
+            student(2),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        // Students book for themselves; not even the tutor books for them
+        let err = book_slot(
+            app_state.clone(),
+            web::Path::from((3,)),
+            booking(&slots[1]),
+            TEACHER,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn students_cancel_only_their_own_bookings() {
+        let app_state = test_app_state().await;
+        mondays_nine_to_eleven(&app_state).await;
+        let slots = free_slots(&app_state, next_monday()).await;
+
+        let mut booking_ids = vec![];
+        for (slot, student_id) in slots.iter().zip([1, 2]) {
+            let resp = book_slot(
+                app_state.clone(),
+                web::Path::from((3,)),
+                booking(slot),
+                student(student_id),
+            )
+            .await
+            .unwrap();
+            let booked: Booking =
+                serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+            booking_ids.push(booked.id);
+        }
+
+        let err = cancel_booking(
+            app_state.clone(),
+            web::Path::from((3, booking_ids[0])),
+            student(2),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        cancel_booking(
+            app_state.clone(),
+            web::Path::from((3, booking_ids[0])),
+            student(1),
+        )
+        .await
+        .unwrap();
+
+        // The tutor cancels any booking of theirs
+        cancel_booking(
+            app_state.clone(),
+            web::Path::from((3, booking_ids[1])),
+            TEACHER,
+        )
+        .await
+        .unwrap();
+        assert_eq!(free_slots(&app_state, next_monday()).await, slots);
+    }
+
+    #[actix_rt::test]
+    async fn exceptions_and_sessions_take_time_out_of_the_slots() {
+        let app_state = test_app_state().await;
+        mondays_nine_to_eleven(&app_state).await;
+        let monday = next_monday();
+
+        app_state
+            .sessions
+            .post_new_session(
+                3,
+                SessionSchedule {
+                    starts_at: monday.and_time(time("10:00")),
+                    ends_at: monday.and_time(time("10:30")),
+                    time_zone: "UTC".into(),
+                    recurrence: None,
+                },
+            )
+            .await
+            .unwrap();
+        let slots = free_slots(&app_state, monday).await;
+        let starts: Vec<DateTime<_>> = slots.iter().map(|slot| slot.starts_at).collect();
+        assert_eq!(
+            starts,
+            [monday.and_time(time("09:00")).and_utc().fixed_offset()]
+        );
+
+        // A day off, then a day with its own window
+        put_availability_exception(
+            app_state.clone(),
+            web::Path::from((3, monday)),
+            ValidatedJson(ExceptionWindows { windows: vec![] }),
+            TEACHER,
+        )
+        .await
+        .unwrap();
+        assert!(free_slots(&app_state, monday).await.is_empty());
+        put_availability_exception(
+            app_state.clone(),
+            web::Path::from((3, monday)),
+            ValidatedJson(ExceptionWindows {
+                windows: vec![TimeWindow {
+                    starts_at: time("14:00"),
+                    ends_at: time("15:00"),
+                }],
+            }),
+            TEACHER,
+        )
+        .await
+        .unwrap();
+        let slots = free_slots(&app_state, monday).await;
+        assert_eq!(
+            slots[0].starts_at,
+            monday.and_time(time("14:00")).and_utc().fixed_offset()
+        );
+
+        delete_availability_exception(app_state.clone(), web::Path::from((3, monday)), TEACHER)
+            .await
+            .unwrap();
+        assert_eq!(free_slots(&app_state, monday).await.len(), 1);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::etag::not_modified;
+use crate::models::calendar::{Calendar, CALENDAR_CONTENT_TYPE};
+use crate::state::AppState;
+use actix_web::http::header::{self, EntityTag};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+
+#[utoipa::path(
+    get,
+    path = "/tutors/{tutor_id}/calendar.ics",
+    tag = "calendar",
+    params(("tutor_id" = i32, Path, description = "Tutor id")),
+    responses(
+        (status = 200, description = "An iCalendar feed of the sessions of the tutor's topics", body = String, content_type = "text/calendar"),
+        (status = 304, description = "The feed has not changed since the ETag in If-None-Match"),
+        (status = 404, description = "Tutor not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn get_tutor_calendar(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let tutor_id = params.0;
+
+    app_state
+        .sessions
+        .get_calendar_for_tutor(tutor_id)
+        .await
+        .map(|calendar| calendar_response(&req, &calendar))
+}
+
+#[utoipa::path(
+    get,
+    path = "/topics/{topic_id}/calendar.ics",
+    tag = "calendar",
+    params(("topic_id" = i32, Path, description = "Topic id")),
+    responses(
+        (status = 200, description = "An iCalendar feed of the topic's sessions", body = String, content_type = "text/calendar"),
+        (status = 304, description = "The feed has not changed since the ETag in If-None-Match"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn get_topic_calendar(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+
+    app_state
+        .sessions
+        .get_calendar_for_topic(topic_id)
+        .await
+        .map(|calendar| calendar_response(&req, &calendar))
+}
+
+// Feeds have no version column, so their ETag is a digest of the feed's content. It is weak
+// because every response has a new DTSTAMP.
+fn calendar_response(req: &HttpRequest, calendar: &Calendar) -> HttpResponse {
+    let etag = EntityTag::new_weak(calendar.feed_tag());
+    if not_modified(req, &etag) {
+        return HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type(CALENDAR_CONTENT_TYPE)
+        .insert_header(header::ETag(etag))
+        .body(calendar.to_ics(Utc::now().naive_utc()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Identity, Role};
+    use crate::extractors::ValidatedJson;
+    use crate::handlers::session::post_new_session;
+    use crate::handlers::testing::test_app_state;
+    use crate::models::session::SessionSchedule;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::ResponseError;
+    use chrono::NaiveDateTime;
+
+    // Tutor 3 teaches topics 3 and 4
+    async fn schedule_session(app_state: &web::Data<AppState>, topic_id: i32, starts_at: &str) {
+        let starts_at = NaiveDateTime::parse_from_str(starts_at, "%Y-%m-%d %H:%M").unwrap();
+        let schedule = SessionSchedule {
+            starts_at,
+            ends_at: starts_at + chrono::Duration::hours(1),
+            time_zone: "Europe/London".into(),
+            recurrence: Some("FREQ=WEEKLY;COUNT=3".into()),
+        };
+        post_new_session(
+            app_state.clone(),
+            web::Path::from((topic_id,)),
+            ValidatedJson(schedule),
+            Identity {
+                subject_id: 3,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn tutor_feed_has_an_event_per_session() {
+        let app_state = test_app_state().await;
+        schedule_session(&app_state, 3, "2024-06-03 18:00").await;
+        schedule_session(&app_state, 4, "2024-06-04 18:00").await;
+
+        let req = TestRequest::default().to_http_request();
+        let resp = get_tutor_calendar(req, app_state.clone(), web::Path::from((3,)))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            CALENDAR_CONTENT_TYPE
+        );
+        let ics = String::from_utf8(to_bytes(resp.into_body()).await.unwrap().to_vec()).unwrap();
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        // 18:00 in London is 17:00 UTC during BST
+        assert!(ics.contains("\r\nDTSTART:20240603T170000Z\r\n"));
+        assert!(ics.contains("\r\nRDATE:20240611T170000Z,20240618T170000Z\r\n"));
+
+        let req = TestRequest::default().to_http_request();
+        let resp = get_topic_calendar(req, app_state.clone(), web::Path::from((4,)))
+            .await
+            .unwrap();
+        let ics = String::from_utf8(to_bytes(resp.into_body()).await.unwrap().to_vec()).unwrap();
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+
+        let req = TestRequest::default().to_http_request();
+        let err = get_tutor_calendar(req, app_state, web::Path::from((99,)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn unchanged_feed_is_not_modified() {
+        let app_state = test_app_state().await;
+        schedule_session(&app_state, 3, "2024-06-03 18:00").await;
+
+        let req = TestRequest::default().to_http_request();
+        let resp = get_topic_calendar(req, app_state.clone(), web::Path::from((3,)))
+            .await
+            .unwrap();
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_http_request();
+        let resp = get_topic_calendar(req, app_state.clone(), web::Path::from((3,)))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), etag);
+
+        // A new session changes the feed
+        schedule_session(&app_state, 3, "2024-07-01 18:00").await;
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .to_http_request();
+        let resp = get_topic_calendar(req, app_state, web::Path::from((3,)))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::models::catalogue::{
+    parse_import, render_export, ExportParams, ImportParams, ImportReport,
+};
+use crate::state::AppState;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "catalogue",
+    params(ExportParams),
+    responses(
+        (status = 200, description = "Every tutor, then every topic, one CatalogueRecord per row or line", content(
+            (String = "text/csv"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not an admin", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn export_catalogue(
+    app_state: web::Data<AppState>,
+    params: web::Query<ExportParams>,
+) -> Result<HttpResponse, AppErrorType> {
+    let format = params.format;
+    let records = app_state.catalogue.export_catalogue().await?;
+    let body = render_export(format, &records)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "catalogue.{}",
+                format.extension()
+            ))],
+        })
+        .body(body))
+}
+
+#[utoipa::path(
+    post,
+    path = "/import",
+    tag = "catalogue",
+    params(ImportParams),
+    request_body(description = "CatalogueRecord rows in the format named by `format`", content(
+        (String = "text/csv"),
+        (String = "application/x-ndjson"),
+    )),
+    responses(
+        (status = 200, description = "The import was committed; rejected rows were skipped in best_effort mode", body = ImportReport),
+        (status = 400, description = "Malformed query parameters, or an upload with no readable rows", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not an admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 413, description = "The upload is larger than the server accepts", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "An atomic import had rejected rows, so nothing was written", body = ImportReport),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn import_catalogue(
+    app_state: web::Data<AppState>,
+    params: web::Query<ImportParams>,
+    body: Result<web::Bytes, actix_web::Error>,
+) -> Result<HttpResponse, AppErrorType> {
+    // Taken as a Result so an oversized upload is a problem response like the JSON routes
+    let rows = parse_import(params.format, &body?)?;
+    let report = app_state
+        .catalogue
+        .import_catalogue(rows, params.mode)
+        .await?;
+
+    Ok(if report.committed {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::UnprocessableEntity().json(report)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::testing::test_app_state;
+    use crate::models::catalogue::{CatalogueFormat, ImportMode, ImportOutcome};
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+
+    async fn import(
+        app_state: &web::Data<AppState>,
+        mode: ImportMode,
+        body: &str,
+    ) -> (StatusCode, ImportReport) {
+        let params = ImportParams {
+            format: CatalogueFormat::Ndjson,
+            mode,
+        };
+        let resp = import_catalogue(
+            app_state.clone(),
+            web::Query(params),
+            Ok(web::Bytes::from(body.to_string())),
+        )
+        .await
+        .unwrap();
+        let status = resp.status();
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    const UPLOAD: &str = r#"{"kind":"tutor","email":"ann@fakemail.com","first_name":"Ann","last_name":"Lee"}
+{"kind":"topic","email":"ann@fakemail.com","title":"Macros","format":"video"}
+{"kind":"tutor","email":"mark@fakemail.com","first_name":"Mark","last_name":"Smith"}
+{"kind":"topic","email":"MARK@fakemail.com","title":"traits","topic_level":"Advanced"}
+{"kind":"topic","email":"nobody@fakemail.com","title":"Closures"}
+"#;
+
+    #[actix_rt::test]
+    async fn atomic_import_with_a_rejected_row_writes_nothing() {
+        let app_state = test_app_state().await;
+
+        let (status, report) = import(&app_state, ImportMode::Atomic, UPLOAD).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!report.committed);
+        assert_eq!(
+            (
+                report.created,
+                report.updated,
+                report.skipped,
+                report.rejected
+            ),
+            (2, 1, 1, 1)
+        );
+        assert_eq!(report.rows[4].errors[0].code, "unprocessable_entity");
+
+        let records = app_state.catalogue.export_catalogue().await.unwrap();
+        assert!(records
+            .iter()
+            .all(|record| record.email != "ann@fakemail.com"));
+    }
+
+    #[actix_rt::test]
+    async fn best_effort_import_resolves_tutors_by_email() {
+        let app_state = test_app_state().await;
+
+        let (status, report) = import(&app_state, ImportMode::BestEffort, UPLOAD).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(report.committed);
+        assert_eq!(report.rows[0].outcome, ImportOutcome::Created);
+        assert_eq!(report.rows[2].outcome, ImportOutcome::Skipped);
+        // "traits" matched the seed topic "Traits" of mark@fakemail.com
+        assert_eq!(report.rows[3].outcome, ImportOutcome::Updated);
+        assert_eq!(report.rows[3].id, Some(1));
+        assert_eq!(report.rows[4].outcome, ImportOutcome::Rejected);
+
+        let topic = app_state.topics.get_topic_details(1).await.unwrap();
+        assert_eq!(topic.title, "traits");
+        assert_eq!(topic.topic_level.as_deref(), Some("Advanced"));
+        let macros = report.rows[1].id.unwrap();
+        let topic = app_state.topics.get_topic_details(macros).await.unwrap();
+        assert_eq!(topic.tutor_id, report.rows[0].id.unwrap());
+
+        // Importing an export changes nothing
+        let resp = export_catalogue(
+            app_state.clone(),
+            web::Query(ExportParams {
+                format: CatalogueFormat::Csv,
+            }),
+        )
+        .await
+        .unwrap();
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let rows = parse_import(CatalogueFormat::Csv, &body).unwrap();
+        let report = app_state
+            .catalogue
+            .import_catalogue(rows, ImportMode::Atomic)
+            .await
+            .unwrap();
+        assert_eq!(report.skipped, report.rows.len());
+    }
+}
@@ -1,7 +1,159 @@
+use crate::metrics::METRICS;
+use crate::models::health::{HealthStatus, LivenessReport, ReadinessChecks, ReadinessReport};
 use crate::state::AppState;
 use actix_web::{web, HttpResponse};
 
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "general",
+    responses(
+        (status = 200, description = "The service is up", body = String),
+    ),
+)]
 pub async fn health_check_handler(app_state: web::Data<AppState>) -> HttpResponse {
     let response_body = &app_state.health_check_response;
     HttpResponse::Ok().json(response_body)
 }
+
+// Liveness only says the process can still serve requests; it never touches dependencies,
+// so a database outage does not get every instance restarted
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "general",
+    responses(
+        (status = 200, description = "The process is able to serve requests", body = LivenessReport),
+    ),
+)]
+pub async fn liveness_handler() -> HttpResponse {
+    HttpResponse::Ok().json(LivenessReport {
+        status: HealthStatus::Ok,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "general",
+    responses(
+        (status = 200, description = "Every dependency is healthy", body = ReadinessReport),
+        (status = 503, description = "At least one dependency is degraded", body = ReadinessReport),
+    ),
+)]
+pub async fn readiness_handler(app_state: web::Data<AppState>) -> HttpResponse {
+    let database = app_state
+        .health
+        .database_health(app_state.settings.database.ping_timeout())
+        .await;
+    let status = database.status;
+    let report = ReadinessReport {
+        status,
+        checks: ReadinessChecks { database },
+    };
+
+    match status {
+        HealthStatus::Ok => HttpResponse::Ok().json(report),
+        HealthStatus::Degraded => HttpResponse::ServiceUnavailable().json(report),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "general",
+    responses(
+        (status = 200, description = "Request, database and error metrics in the Prometheus text format", body = String, content_type = "text/plain; version=0.0.4"),
+    ),
+)]
+pub async fn metrics_handler(app_state: web::Data<AppState>) -> HttpResponse {
+    // Pool gauges are read at scrape time rather than kept up to date on every checkout
+    if let Some(stats) = app_state.health.pool_stats() {
+        METRICS.set_pool_stats(&stats);
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(METRICS.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbaccess::memory::InMemoryRepository;
+    use crate::dbaccess::HealthRepository;
+    use crate::handlers::testing::test_state;
+    use crate::models::health::{DatabaseHealth, PoolStats};
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct UnreachableDatabase;
+
+    #[async_trait]
+    impl HealthRepository for UnreachableDatabase {
+        async fn database_health(&self, timeout: Duration) -> DatabaseHealth {
+            DatabaseHealth {
+                status: HealthStatus::Degraded,
+                backend: "postgres".into(),
+                latency_ms: Some(timeout.as_millis() as u64),
+                error: Some("Database did not answer".into()),
+                pool: None,
+                migration_version: None,
+            }
+        }
+
+        fn pool_stats(&self) -> Option<PoolStats> {
+            Some(PoolStats {
+                size: 4,
+                idle: 1,
+                in_use: 3,
+            })
+        }
+    }
+
+    fn test_app_state(health: Arc<dyn HealthRepository>) -> web::Data<AppState> {
+        let mut app_state = test_state(InMemoryRepository::new());
+        app_state.health = health;
+        web::Data::new(app_state)
+    }
+
+    #[actix_rt::test]
+    async fn readiness_is_ok_when_the_store_is_healthy() {
+        let app_state = test_app_state(Arc::new(InMemoryRepository::new()));
+
+        let resp = readiness_handler(app_state).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let report: ReadinessReport =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(report.status, HealthStatus::Ok);
+        assert_eq!(report.checks.database.backend, "memory");
+    }
+
+    #[actix_rt::test]
+    async fn readiness_is_unavailable_when_the_database_is_degraded() {
+        let app_state = test_app_state(Arc::new(UnreachableDatabase));
+
+        let resp = readiness_handler(app_state).await;
+
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report: ReadinessReport =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert_eq!(report.checks.database.latency_ms, Some(500));
+    }
+
+    #[actix_rt::test]
+    async fn metrics_include_pool_gauges() {
+        let app_state = test_app_state(Arc::new(UnreachableDatabase));
+
+        let resp = metrics_handler(app_state).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains(r#"db_pool_connections{state="in_use"} 3"#));
+    }
+}
@@ -1,3 +1,36 @@
+pub mod auth;
+pub mod availability;
+pub mod calendar;
+pub mod catalogue;
 pub mod general;
+pub mod review;
+pub mod session;
+pub mod student;
 pub mod topic;
+pub mod trash;
 pub mod tutor;
+
+// Fixtures shared by the handler tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::auth::JwtKeys;
+    use crate::dbaccess::memory::InMemoryRepository;
+    use crate::settings::Settings;
+    use crate::state::AppState;
+    use actix_web::web;
+    use std::sync::Arc;
+
+    pub(crate) fn test_state(repo: InMemoryRepository) -> AppState {
+        AppState::with_repository(
+            Arc::new(repo),
+            "".to_string(),
+            JwtKeys::new(b"test-secret"),
+            Settings::default(),
+        )
+    }
+
+    // The in-memory store with the seed data
+    pub(crate) async fn test_app_state() -> web::Data<AppState> {
+        web::Data::new(test_state(InMemoryRepository::with_seed_data().await))
+    }
+}
@@ -0,0 +1,303 @@
+use crate::auth::Identity;
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::extractors::ValidatedJson;
+use crate::models::page::{Page, PageParams};
+use crate::models::review::{Review, WriteReview};
+use crate::state::AppState;
+use actix_web::{web, HttpRequest, HttpResponse};
+
+#[utoipa::path(
+    get,
+    path = "/topics/{topic_id}/reviews",
+    tag = "reviews",
+    params(("topic_id" = i32, Path, description = "Topic id"), PageParams),
+    responses(
+        (status = 200, description = "A page of the topic's reviews, ordered by id", body = Page<Review>),
+        (status = 400, description = "Malformed query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn get_reviews_for_topic(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    params: web::Path<(i32,)>,
+    page_params: web::Query<PageParams>,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+    let page = page_params.into_inner().into_page_request()?;
+
+    app_state
+        .reviews
+        .get_reviews_for_topic(topic_id, &page)
+        .await
+        .map(|reviews| reviews.into_response(&req))
+}
+
+#[utoipa::path(
+    put,
+    path = "/topics/{topic_id}/review",
+    tag = "reviews",
+    params(("topic_id" = i32, Path, description = "Topic id")),
+    request_body = WriteReview,
+    responses(
+        (status = 200, description = "The calling student's new or replaced review", body = Review),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not a student enrolled in the topic", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn put_review(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    review: ValidatedJson<WriteReview>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+    let student_id = identity.require_student()?;
+
+    app_state
+        .reviews
+        .put_review(topic_id, student_id, review.into_inner())
+        .await
+        .map(|review| HttpResponse::Ok().json(review))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/topics/{topic_id}/review",
+    tag = "reviews",
+    params(("topic_id" = i32, Path, description = "Topic id")),
+    responses(
+        (status = 200, description = "Confirmation message", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not a student", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic or review not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn delete_review(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+    let student_id = identity.require_student()?;
+
+    app_state
+        .reviews
+        .delete_review(topic_id, student_id)
+        .await
+        .map(|message| HttpResponse::Ok().json(message))
+}
+
+// Admins can take down any student's review
+#[utoipa::path(
+    delete,
+    path = "/topics/{topic_id}/reviews/{student_id}",
+    tag = "reviews",
+    params(
+        ("topic_id" = i32, Path, description = "Topic id"),
+        ("student_id" = i32, Path, description = "The reviewing student's id"),
+    ),
+    responses(
+        (status = 200, description = "Confirmation message", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not an admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic or review not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn moderate_review(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let (topic_id, student_id) = params.into_inner();
+
+    app_state
+        .reviews
+        .delete_review(topic_id, student_id)
+        .await
+        .map(|message| HttpResponse::Ok().json(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use crate::handlers::testing::test_app_state;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    fn student(student_id: i32) -> Identity {
+        Identity {
+            subject_id: student_id,
+            role: Role::Student,
+        }
+    }
+
+    async fn review(
+        app_state: &web::Data<AppState>,
+        topic_id: i32,
+        reviewer: Identity,
+        rating: i16,
+    ) -> Result<HttpResponse, AppErrorType> {
+        put_review(
+            app_state.clone(),
+            web::Path::from((topic_id,)),
+            ValidatedJson(WriteReview {
+                rating,
+                body: Some("Clear and well paced".into()),
+            }),
+            reviewer,
+        )
+        .await
+    }
+
+    // Tutor 3 teaches topics 3 and 4, and students 1 and 2 take both
+    async fn enrolled_app_state() -> web::Data<AppState> {
+        let app_state = test_app_state().await;
+        for topic_id in [3, 4] {
+            for student_id in [1, 2] {
+                app_state
+                    .students
+                    .enroll_student(topic_id, student_id)
+                    .await
+                    .unwrap();
+            }
+        }
+        app_state
+    }
+
+    async fn ratings(app_state: &web::Data<AppState>, topic_id: i32) -> [(Option<f64>, i32); 2] {
+        let topic = app_state.topics.get_topic_details(topic_id).await.unwrap();
+        let tutor = app_state.tutors.get_tutor_details(3).await.unwrap();
+        [
+            (topic.average_rating, topic.review_count),
+            (tutor.average_rating, tutor.review_count),
+        ]
+    }
+
+    #[actix_rt::test]
+    async fn reviews_move_the_topic_and_tutor_ratings() {
+        let app_state = enrolled_app_state().await;
+        let version = app_state.topics.get_topic_details(3).await.unwrap().version;
+
+        review(&app_state, 3, student(1), 5).await.unwrap();
+        review(&app_state, 3, student(2), 2).await.unwrap();
+        review(&app_state, 4, student(1), 4).await.unwrap();
+        assert_eq!(
+            ratings(&app_state, 3).await,
+            [(Some(3.5), 2), (Some(11.0 / 3.0), 3)]
+        );
+        let topic = app_state.topics.get_topic_details(3).await.unwrap();
+        assert_ne!(topic.version, version);
+
+        // A second review by the same student replaces the first
+        review(&app_state, 3, student(2), 4).await.unwrap();
+        assert_eq!(
+            ratings(&app_state, 3).await,
+            [(Some(4.5), 2), (Some(13.0 / 3.0), 3)]
+        );
+
+        delete_review(app_state.clone(), web::Path::from((3,)), student(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            ratings(&app_state, 3).await,
+            [(Some(4.0), 1), (Some(4.0), 2)]
+        );
+
+        // Moderation removes another student's review
+        moderate_review(app_state.clone(), web::Path::from((4, 1)))
+            .await
+            .unwrap();
+        assert_eq!(
+            ratings(&app_state, 3).await,
+            [(Some(4.0), 1), (Some(4.0), 1)]
+        );
+
+        // Deleting a student takes their remaining reviews with them
+        app_state.students.delete_student(2).await.unwrap();
+        assert_eq!(ratings(&app_state, 3).await, [(None, 0), (None, 0)]);
+    }
+
+    #[actix_rt::test]
+    async fn reviews_are_written_by_the_reviewing_student() {
+        let app_state = enrolled_app_state().await;
+
+        // Tutors and admins cannot write or delete a review for a student
+        for identity in [
+            Identity {
+                subject_id: 3,
+                role: Role::Tutor,
+            },
+            Identity {
+                subject_id: 1,
+                role: Role::Admin,
+            },
+        ] {
+            let err = review(&app_state, 3, identity, 5).await.unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+            let err = delete_review(app_state.clone(), web::Path::from((3,)), identity)
+                .await
+                .unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        }
+
+        // A token for a student deleted since it was issued
+        let err = review(&app_state, 3, student(99), 5).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+
+        let err = delete_review(app_state.clone(), web::Path::from((3,)), student(1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn only_enrolled_students_review_a_topic() {
+        let app_state = test_app_state().await;
+
+        // Student 1 takes topic 1 only
+        let err = review(&app_state, 2, student(1), 5).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let topic = app_state.topics.get_topic_details(2).await.unwrap();
+        assert_eq!((topic.average_rating, topic.review_count), (None, 0));
+
+        app_state.students.enroll_student(2, 1).await.unwrap();
+        review(&app_state, 2, student(1), 5).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn trashed_topics_leave_the_tutor_rating() {
+        let app_state = enrolled_app_state().await;
+        review(&app_state, 3, student(1), 5).await.unwrap();
+        review(&app_state, 4, student(1), 2).await.unwrap();
+        let version = app_state.tutors.get_tutor_details(3).await.unwrap().version;
+
+        app_state.topics.delete_topic(3, 4).await.unwrap();
+        assert_eq!(
+            ratings(&app_state, 3).await,
+            [(Some(5.0), 1), (Some(5.0), 1)]
+        );
+        let tutor = app_state.tutors.get_tutor_details(3).await.unwrap();
+        assert_ne!(tutor.version, version);
+
+        app_state.trash.restore_topic(4).await.unwrap();
+        assert_eq!(
+            ratings(&app_state, 3).await,
+            [(Some(5.0), 1), (Some(3.5), 2)]
+        );
+
+        // Trashing the tutor trashes the rest; the restore brings their ratings back
+        app_state.tutors.delete_tutor(3, false).await.unwrap();
+        let restored = app_state.trash.restore_tutor(3).await.unwrap();
+        assert_eq!(
+            (restored.tutor.average_rating, restored.tutor.review_count),
+            (Some(3.5), 2)
+        );
+    }
+}
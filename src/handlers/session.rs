@@ -0,0 +1,293 @@
+use crate::auth::Identity;
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::extractors::ValidatedJson;
+use crate::models::session::{DateRange, Occurrence, Session, SessionSchedule};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+
+#[utoipa::path(
+    get,
+    path = "/topics/{topic_id}/sessions",
+    tag = "sessions",
+    params(("topic_id" = i32, Path, description = "Topic id")),
+    responses(
+        (status = 200, description = "The topic's sessions, in the order they were created", body = Vec<Session>),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn get_sessions_for_topic(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+
+    app_state
+        .sessions
+        .get_sessions_for_topic(topic_id)
+        .await
+        .map(|sessions| HttpResponse::Ok().json(sessions))
+}
+
+#[utoipa::path(
+    post,
+    path = "/topics/{topic_id}/sessions",
+    tag = "sessions",
+    params(("topic_id" = i32, Path, description = "Topic id")),
+    request_body = SessionSchedule,
+    responses(
+        (status = 200, description = "The new session", body = Session),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "An occurrence overlaps another session of the same tutor", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn post_new_session(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    schedule: ValidatedJson<SessionSchedule>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+    let topic = app_state.topics.get_topic_details(topic_id).await?;
+    identity.require_owner(topic.tutor_id)?;
+
+    app_state
+        .sessions
+        .post_new_session(topic_id, schedule.into_inner())
+        .await
+        .map(|session| HttpResponse::Ok().json(session))
+}
+
+#[utoipa::path(
+    put,
+    path = "/topics/{topic_id}/sessions/{session_id}",
+    tag = "sessions",
+    params(("topic_id" = i32, Path, description = "Topic id"), ("session_id" = i32, Path, description = "Session id")),
+    request_body = SessionSchedule,
+    responses(
+        (status = 200, description = "The moved session", body = Session),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic or session not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "An occurrence overlaps another session of the same tutor", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn update_session(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    schedule: ValidatedJson<SessionSchedule>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let (topic_id, session_id) = (params.0, params.1);
+    let topic = app_state.topics.get_topic_details(topic_id).await?;
+    identity.require_owner(topic.tutor_id)?;
+
+    app_state
+        .sessions
+        .update_session(topic_id, session_id, schedule.into_inner())
+        .await
+        .map(|session| HttpResponse::Ok().json(session))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/topics/{topic_id}/sessions/{session_id}",
+    tag = "sessions",
+    params(("topic_id" = i32, Path, description = "Topic id"), ("session_id" = i32, Path, description = "Session id")),
+    responses(
+        (status = 200, description = "Confirmation message", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic or session not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn delete_session(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let (topic_id, session_id) = (params.0, params.1);
+    let topic = app_state.topics.get_topic_details(topic_id).await?;
+    identity.require_owner(topic.tutor_id)?;
+
+    app_state
+        .sessions
+        .delete_session(topic_id, session_id)
+        .await
+        .map(|message| HttpResponse::Ok().json(message))
+}
+
+#[utoipa::path(
+    get,
+    path = "/topics/{topic_id}/occurrences",
+    tag = "sessions",
+    params(("topic_id" = i32, Path, description = "Topic id"), DateRange),
+    responses(
+        (status = 200, description = "Every occurrence of the topic's sessions that overlaps the range, in start order", body = Vec<Occurrence>),
+        (status = 400, description = "Malformed query parameters, or a range that is reversed or too long", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn get_occurrences_for_topic(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    range: web::Query<DateRange>,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+    let interval = range.interval()?;
+
+    app_state
+        .sessions
+        .get_occurrences_for_topic(topic_id, interval)
+        .await
+        .map(|occurrences| HttpResponse::Ok().json(occurrences))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use crate::handlers::testing::test_app_state;
+    use crate::models::topic::CreateTopic;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+    use chrono::NaiveDateTime;
+
+    // Tutor 3 teaches topics 3 and 4
+    const TEACHER: Identity = Identity {
+        subject_id: 3,
+        role: Role::Tutor,
+    };
+
+    fn weekly(starts_at: &str, hours: i64, count: u32) -> ValidatedJson<SessionSchedule> {
+        let starts_at = NaiveDateTime::parse_from_str(starts_at, "%Y-%m-%d %H:%M").unwrap();
+        ValidatedJson(SessionSchedule {
+            starts_at,
+            ends_at: starts_at + chrono::Duration::hours(hours),
+            time_zone: "America/New_York".into(),
+            recurrence: Some(format!("FREQ=WEEKLY;COUNT={}", count)),
+        })
+    }
+
+    #[actix_rt::test]
+    async fn overlapping_sessions_of_one_tutor_are_a_conflict() {
+        let app_state = test_app_state().await;
+        let create = |topic_id: i32, schedule| {
+            post_new_session(
+                app_state.clone(),
+                web::Path::from((topic_id,)),
+                schedule,
+                TEACHER,
+            )
+        };
+
+        let resp = create(3, weekly("2024-06-03 18:00", 2, 4)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let session: Session =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+
+        // The third Monday of the first session, on another of the tutor's topics
+        let err = create(4, weekly("2024-06-17 19:00", 1, 1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        // Back to back is fine
+        let resp = create(4, weekly("2024-06-17 20:00", 1, 1)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Another tutor's topic at the same time is fine too
+        app_state
+            .topics
+            .post_new_topic(CreateTopic {
+                tutor_id: 1,
+                title: "Macros".into(),
+                topic_description: None,
+                format: None,
+                duration: None,
+                topic_level: None,
+                capacity: None,
+            })
+            .await
+            .unwrap();
+        let resp = post_new_session(
+            app_state.clone(),
+            web::Path::from((5,)),
+            weekly("2024-06-17 19:00", 1, 1),
+            Identity {
+                subject_id: 1,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Moving the first session onto the second conflicts; moving it clear does not
+        let err = update_session(
+            app_state.clone(),
+            web::Path::from((3, session.id)),
+            weekly("2024-06-10 19:30", 1, 2),
+            TEACHER,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        let resp = update_session(
+            app_state.clone(),
+            web::Path::from((3, session.id)),
+            weekly("2024-06-04 18:00", 2, 4),
+            TEACHER,
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn occurrences_are_expanded_within_the_range() {
+        let app_state = test_app_state().await;
+        post_new_session(
+            app_state.clone(),
+            web::Path::from((3,)),
+            weekly("2024-06-03 18:00", 2, 10),
+            TEACHER,
+        )
+        .await
+        .unwrap();
+
+        let range = web::Query::<DateRange>::from_query("from=2024-06-10&to=2024-06-24").unwrap();
+        let resp = get_occurrences_for_topic(app_state.clone(), web::Path::from((3,)), range)
+            .await
+            .unwrap();
+        let occurrences: Vec<Occurrence> =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        let starts: Vec<String> = occurrences
+            .iter()
+            .map(|occurrence| occurrence.starts_at.to_rfc3339())
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                "2024-06-10T18:00:00-04:00",
+                "2024-06-17T18:00:00-04:00",
+                "2024-06-24T18:00:00-04:00"
+            ]
+        );
+
+        let range = web::Query::<DateRange>::from_query("from=2024-06-10&to=2025-06-24").unwrap();
+        let err = get_occurrences_for_topic(app_state, web::Path::from((3,)), range)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,513 @@
+use crate::auth::{hash_password, Identity};
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::extractors::ValidatedJson;
+use crate::models::page::{Page, PageParams};
+use crate::models::student::{
+    CreateStudent, EnrolledTopic, Enrollment, NewEnrollment, RegisterStudent, Roster, Student,
+};
+use crate::state::AppState;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+
+#[utoipa::path(
+    get,
+    path = "/students/",
+    tag = "students",
+    params(PageParams),
+    responses(
+        (status = 200, description = "A page of students, ordered by id", body = Page<Student>),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not a tutor or admin", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_all_students(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    page_params: web::Query<PageParams>,
+) -> Result<HttpResponse, AppErrorType> {
+    let page = page_params.into_inner().into_page_request()?;
+
+    app_state
+        .students
+        .get_all_students(&page)
+        .await
+        .map(|students| students.into_response(&req))
+}
+
+#[utoipa::path(
+    post,
+    path = "/students/",
+    tag = "students",
+    request_body = CreateStudent,
+    responses(
+        (status = 200, description = "The new student", body = Student),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not a tutor or admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The email address is already registered", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn post_new_student(
+    new_student: ValidatedJson<CreateStudent>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppErrorType> {
+    app_state
+        .students
+        .post_new_student(new_student.into_inner(), None)
+        .await
+        .map(|student| HttpResponse::Ok().json(student))
+}
+
+#[utoipa::path(
+    post,
+    path = "/students/register",
+    tag = "students",
+    request_body = RegisterStudent,
+    responses(
+        (status = 200, description = "The new student, who can now log in", body = Student),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The email address is already registered", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn register_student(
+    registration: ValidatedJson<RegisterStudent>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppErrorType> {
+    let registration = registration.into_inner();
+    let password = registration.password.clone();
+    let password_hash = web::block(move || hash_password(&password)).await??;
+
+    app_state
+        .students
+        .post_new_student(registration.into(), Some(password_hash))
+        .await
+        .map(|student| HttpResponse::Ok().json(student))
+}
+
+#[utoipa::path(
+    get,
+    path = "/students/{student_id}",
+    tag = "students",
+    params(("student_id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "The student", body = Student),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not a tutor or admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Student not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_student_details(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let student_id = params.0;
+
+    app_state
+        .students
+        .get_student_details(student_id)
+        .await
+        .map(|student| HttpResponse::Ok().json(student))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/students/{student_id}",
+    tag = "students",
+    params(("student_id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Confirmation message; the student's enrollments are deleted with them", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not an admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Student not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn delete_student(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let student_id = params.0;
+
+    app_state
+        .students
+        .delete_student(student_id)
+        .await
+        .map(|message| HttpResponse::Ok().json(message))
+}
+
+#[utoipa::path(
+    get,
+    path = "/students/{student_id}/topics",
+    tag = "students",
+    params(("student_id" = i32, Path, description = "Student id"), PageParams),
+    responses(
+        (status = 200, description = "A page of the topics the student is enrolled in, in enrollment order", body = Page<EnrolledTopic>),
+        (status = 400, description = "Malformed query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not a tutor or admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Student not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_topics_for_student(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    params: web::Path<(i32,)>,
+    page_params: web::Query<PageParams>,
+) -> Result<HttpResponse, AppErrorType> {
+    let student_id = params.0;
+    let page = page_params.into_inner().into_page_request()?;
+
+    app_state
+        .students
+        .get_topics_for_student(student_id, &page)
+        .await
+        .map(|topics| topics.into_response(&req))
+}
+
+#[utoipa::path(
+    post,
+    path = "/topics/{topic_id}/enrollments",
+    tag = "students",
+    params(("topic_id" = i32, Path, description = "Topic id")),
+    request_body = NewEnrollment,
+    responses(
+        (status = 200, description = "The new enrollment", body = Enrollment),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller does not teach this topic", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The topic is full or the student is already enrolled", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation or names an unknown student", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn enroll_student(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    enrollment: ValidatedJson<NewEnrollment>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+    let topic = app_state.topics.get_topic_details(topic_id).await?;
+    identity.require_owner(topic.tutor_id)?;
+
+    app_state
+        .students
+        .enroll_student(topic_id, enrollment.student_id)
+        .await
+        .map(|enrollment| HttpResponse::Ok().json(enrollment))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/topics/{topic_id}/enrollments/{student_id}",
+    tag = "students",
+    params(("topic_id" = i32, Path, description = "Topic id"), ("student_id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Confirmation message", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller does not teach this topic", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic or enrollment not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn unenroll_student(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let (topic_id, student_id) = (params.0, params.1);
+    let topic = app_state.topics.get_topic_details(topic_id).await?;
+    identity.require_owner(topic.tutor_id)?;
+
+    app_state
+        .students
+        .unenroll_student(topic_id, student_id)
+        .await
+        .map(|message| HttpResponse::Ok().json(message))
+}
+
+#[utoipa::path(
+    get,
+    path = "/topics/{topic_id}/students",
+    tag = "students",
+    params(("topic_id" = i32, Path, description = "Topic id"), PageParams),
+    responses(
+        (status = 200, description = "The topic's capacity and enrolled count, with a page of its students", body = Roster),
+        (status = 400, description = "Malformed query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not a tutor or admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn get_topic_roster(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    params: web::Path<(i32,)>,
+    page_params: web::Query<PageParams>,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+    let page = page_params.into_inner().into_page_request()?;
+    let roster = app_state.students.get_topic_roster(topic_id, &page).await?;
+
+    let mut response = HttpResponse::Ok();
+    if let Some(links) = roster.students.link_header(&req) {
+        response.insert_header((header::LINK, links));
+    }
+    Ok(response.json(roster))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{verify_password, Role};
+    use crate::handlers::testing::test_app_state;
+    use crate::models::topic::TopicPatch;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, ResponseError};
+
+    // Tutor 1 teaches topic 1, which seed student 1 is enrolled in
+    const TEACHER: Identity = Identity {
+        subject_id: 1,
+        role: Role::Tutor,
+    };
+
+    async fn enroll(
+        app_state: &web::Data<AppState>,
+        topic_id: i32,
+        student_id: i32,
+        identity: Identity,
+    ) -> Result<HttpResponse, AppErrorType> {
+        enroll_student(
+            app_state.clone(),
+            web::Path::from((topic_id,)),
+            ValidatedJson(NewEnrollment { student_id }),
+            identity,
+        )
+        .await
+    }
+
+    #[actix_rt::test]
+    async fn enrolling_in_a_full_topic_is_a_conflict() {
+        let app_state = test_app_state().await;
+        app_state
+            .topics
+            .patch_topic(
+                1,
+                1,
+                TopicPatch {
+                    capacity: Some(Some(2)),
+                    ..TopicPatch::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        let carol = app_state
+            .students
+            .post_new_student(
+                CreateStudent {
+                    first_name: "Carol".into(),
+                    last_name: "Diaz".into(),
+                    email: "carol.diaz@fakemail.com".into(),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        let resp = enroll(&app_state, 1, 2, TEACHER).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let err = enroll(&app_state, 1, carol.id, TEACHER).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        assert_eq!(err.problem().code, "conflict");
+
+        let err = enroll(&app_state, 1, 1, TEACHER).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        // Leaving frees the place
+        unenroll_student(app_state.clone(), web::Path::from((1, 2)), TEACHER)
+            .await
+            .unwrap();
+        let resp = enroll(&app_state, 1, carol.id, TEACHER).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn only_the_topic_tutor_enrolls_known_students() {
+        let app_state = test_app_state().await;
+
+        let err = enroll(
+            &app_state,
+            1,
+            2,
+            Identity {
+                subject_id: 2,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+
+        let err = enroll(&app_state, 1, 1000, TEACHER).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let err = enroll(&app_state, 1000, 2, TEACHER).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn roster_and_student_topics_list_enrollments() {
+        let app_state = test_app_state().await;
+        enroll(&app_state, 1, 2, TEACHER).await.unwrap();
+        enroll(
+            &app_state,
+            3,
+            2,
+            Identity {
+                subject_id: 3,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap();
+
+        let roster = app_state
+            .students
+            .get_topic_roster(1, &PageParams::default().into_page_request().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(roster.capacity, None);
+        assert_eq!(roster.enrolled, 2);
+        let names: Vec<&str> = roster
+            .students
+            .items
+            .iter()
+            .map(|enrolled| enrolled.student.first_name.as_str())
+            .collect();
+        assert_eq!(names, ["Alice", "Raj"]);
+
+        let topics = app_state
+            .students
+            .get_topics_for_student(2, &PageParams::default().into_page_request().unwrap())
+            .await
+            .unwrap();
+        let titles: Vec<&str> = topics
+            .items
+            .iter()
+            .map(|enrolled| enrolled.topic.title.as_str())
+            .collect();
+        assert_eq!(titles, ["Traits", "Concurrency"]);
+
+        // A trashed topic drops out of the student's topics
+        app_state.topics.delete_topic(3, 3).await.unwrap();
+        let topics = app_state
+            .students
+            .get_topics_for_student(2, &PageParams::default().into_page_request().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(topics.items.len(), 1);
+
+        delete_student(app_state.clone(), web::Path::from((2,)))
+            .await
+            .unwrap();
+        let roster = app_state
+            .students
+            .get_topic_roster(1, &PageParams::default().into_page_request().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(roster.enrolled, 1);
+    }
+
+    #[actix_rt::test]
+    async fn roster_pages_its_students_and_keeps_the_count() {
+        let app_state = test_app_state().await;
+        enroll(&app_state, 1, 2, TEACHER).await.unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/topics/1/students?limit=1")
+            .to_http_request();
+        let page_params = PageParams {
+            limit: Some(1),
+            ..PageParams::default()
+        };
+        let resp = get_topic_roster(
+            app_state,
+            req,
+            web::Path::from((1,)),
+            web::Query(page_params),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            resp.headers().get(header::LINK).unwrap(),
+            "</topics/1/students?limit=1&offset=1>; rel=\"next\""
+        );
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["enrolled"], 2);
+        assert_eq!(body["students"]["items"].as_array().unwrap().len(), 1);
+        assert_eq!(body["students"]["items"][0]["first_name"], "Alice");
+    }
+
+    #[actix_rt::test]
+    async fn only_a_registering_student_sets_a_password() {
+        let app_state = test_app_state().await;
+        let credentials = |email: &'static str| {
+            let app_state = app_state.clone();
+            async move {
+                app_state
+                    .students
+                    .get_student_credentials(email)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            }
+        };
+
+        // A student created by a tutor cannot log in
+        post_new_student(
+            ValidatedJson(CreateStudent {
+                first_name: "Carol".into(),
+                last_name: "Diaz".into(),
+                email: "carol.diaz@fakemail.com".into(),
+            }),
+            app_state.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(credentials("carol.diaz@fakemail.com")
+            .await
+            .password_hash
+            .is_none());
+
+        let resp = register_student(
+            ValidatedJson(RegisterStudent {
+                first_name: "Dan".into(),
+                last_name: "Okafor".into(),
+                email: "dan.okafor@fakemail.com".into(),
+                password: "dan-password".into(),
+            }),
+            app_state.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let hash = credentials("dan.okafor@fakemail.com")
+            .await
+            .password_hash
+            .unwrap();
+        assert!(verify_password("dan-password", &hash));
+    }
+}
@@ -1,63 +1,284 @@
-use crate::dbaccess::topic::*;
-use crate::errors::AppErrorType;
-use crate::models::topic::{CreateTopic, UpdateTopic};
+use crate::auth::Identity;
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::etag::{self, IfMatchVersions};
+use crate::extractors::ValidatedJson;
+use crate::models::page::{Page, PageParams, StreamParams};
+use crate::models::topic::{
+    CreateTopic, Topic, TopicFilter, TopicPatch, TopicSearchHit, TopicSearchParams, UpdateTopic,
+};
 use crate::state::AppState;
-use actix_web::{web, HttpResponse};
+use crate::streaming;
+use actix_web::{web, HttpRequest, HttpResponse};
 
-pub async fn get_all_topics(app_state: web::Data<AppState>) -> Result<HttpResponse, AppErrorType> {
-    get_all_topics_db(&app_state.db)
+#[utoipa::path(
+    get,
+    path = "/topics/",
+    tag = "topics",
+    params(TopicFilter, PageParams, StreamParams),
+    responses(
+        (status = 200, description = "A page of topics, or with `stream` every matching topic as a chunked JSON array or NDJSON", content(
+            (Page<Topic> = "application/json"),
+            (Topic = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn get_all_topics(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    filter: web::Query<TopicFilter>,
+    page_params: web::Query<PageParams>,
+    stream: web::Query<StreamParams>,
+) -> Result<HttpResponse, AppErrorType> {
+    if let Some(format) = stream.format(&page_params)? {
+        return app_state
+            .topics
+            .stream_topics(&filter)
+            .map(|topics| streaming::streamed(format, topics));
+    }
+    let page = page_params.into_inner().into_page_request()?;
+
+    app_state
+        .topics
+        .get_all_topics(&filter, &page)
         .await
-        .map(|topics| HttpResponse::Ok().json(topics))
+        .map(|topics| topics.into_response(&req))
 }
 
+#[utoipa::path(
+    get,
+    path = "/tutors/{tutor_id}/topics",
+    tag = "topics",
+    params(("tutor_id" = i32, Path, description = "Tutor id"), TopicFilter, PageParams, StreamParams),
+    responses(
+        (status = 200, description = "A page of the tutor's topics, or with `stream` every matching topic as a chunked JSON array or NDJSON", content(
+            (Page<Topic> = "application/json"),
+            (Topic = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
 pub async fn get_topics_for_tutor(
     app_state: web::Data<AppState>,
+    req: HttpRequest,
     params: web::Path<(i32,)>,
+    filter: web::Query<TopicFilter>,
+    page_params: web::Query<PageParams>,
+    stream: web::Query<StreamParams>,
 ) -> Result<HttpResponse, AppErrorType> {
     let tuple = params.0;
     let tutor_id: i32 = tuple;
+    if let Some(format) = stream.format(&page_params)? {
+        return app_state
+            .topics
+            .stream_topics_for_tutor(tutor_id, &filter)
+            .map(|topics| streaming::streamed(format, topics));
+    }
+    let page = page_params.into_inner().into_page_request()?;
 
-    get_topics_for_tutor_db(&app_state.db, tutor_id)
+    app_state
+        .topics
+        .get_topics_for_tutor(tutor_id, &filter, &page)
         .await
-        .map(|courses| HttpResponse::Ok().json(courses))
+        .map(|courses| courses.into_response(&req))
 }
 
+#[utoipa::path(
+    get,
+    path = "/topics/search",
+    tag = "topics",
+    params(TopicSearchParams, PageParams),
+    responses(
+        (status = 200, description = "Matching topics, most relevant first", body = Page<TopicSearchHit>),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn search_topics(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    search: web::Query<TopicSearchParams>,
+    page_params: web::Query<PageParams>,
+) -> Result<HttpResponse, AppErrorType> {
+    let page = page_params.into_inner().into_page_request()?;
+    let terms = search.q.trim();
+    if terms.is_empty() {
+        return Err(AppErrorType::InvalidInput(
+            "Search query q must not be empty".into(),
+        ));
+    }
+
+    app_state
+        .topics
+        .search_topics(terms, &page)
+        .await
+        .map(|hits| hits.into_response(&req))
+}
+
+#[utoipa::path(
+    get,
+    path = "/topics/{topic_id}",
+    tag = "topics",
+    params(
+        ("topic_id" = i32, Path, description = "Topic id"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a copy the client already has"),
+    ),
+    responses(
+        (status = 200, description = "The topic", body = Topic, headers(("ETag" = String, description = "The topic's version"))),
+        (status = 304, description = "The topic still has the version named in If-None-Match"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
 pub async fn get_topic_details(
     app_state: web::Data<AppState>,
+    req: HttpRequest,
     params: web::Path<(i32,)>,
 ) -> Result<HttpResponse, AppErrorType> {
-    let (topic_id) = params.0;
-    get_topic_details_db(&app_state.db, topic_id)
+    let topic_id = params.0;
+    app_state
+        .topics
+        .get_topic_details(topic_id)
         .await
-        .map(|topic| HttpResponse::Ok().json(topic))
+        .map(|topic| etag::conditional_get(&req, &topic))
 }
 
+#[utoipa::path(
+    post,
+    path = "/topics/",
+    tag = "topics",
+    request_body = CreateTopic,
+    responses(
+        (status = 200, description = "The new topic", body = Topic),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "The request body failed validation or the tutor does not exist", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn post_new_topic(
-    new_topic: web::Json<CreateTopic>,
+    new_topic: ValidatedJson<CreateTopic>,
     app_state: web::Data<AppState>,
+    identity: Identity,
 ) -> Result<HttpResponse, AppErrorType> {
-    post_new_topic_db(&app_state.db, new_topic.into_inner())
+    identity.require_owner(new_topic.tutor_id)?;
+    app_state
+        .topics
+        .post_new_topic(new_topic.into_inner())
         .await
         .map(|topic| HttpResponse::Ok().json(topic))
 }
 
+#[utoipa::path(
+    put,
+    path = "/tutors/{tutor_id}/{topic_id}",
+    tag = "topics",
+    params(
+        ("tutor_id" = i32, Path, description = "Tutor id"),
+        ("topic_id" = i32, Path, description = "Topic id"),
+        ("If-Match" = Option<String>, Header, description = "Only update if the topic still has this ETag"),
+    ),
+    request_body = UpdateTopic,
+    responses(
+        (status = 200, description = "The updated topic", body = Topic, headers(("ETag" = String, description = "The topic's new version"))),
+        (status = 400, description = "Malformed JSON or query parameters", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 412, description = "The topic has changed since the If-Match version; the body is the current topic", body = Topic, headers(("ETag" = String, description = "The topic's current version"))),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn update_topic_details(
     app_state: web::Data<AppState>,
     params: web::Path<(i32, i32)>,
-    topic: web::Json<UpdateTopic>,
+    topic: ValidatedJson<UpdateTopic>,
+    if_match: IfMatchVersions,
+    identity: Identity,
 ) -> Result<HttpResponse, AppErrorType> {
     let (tutor_id, topic_id) = (params.0, params.1);
-    update_topic_details_db(&app_state.db, tutor_id, topic_id, topic.into_inner())
-        .await
-        .map(|topic| HttpResponse::Ok().json(topic))
+    identity.require_owner(tutor_id)?;
+    let result = app_state
+        .topics
+        .update_topic_details(tutor_id, topic_id, topic.into_inner(), if_match.as_deref())
+        .await;
+    etag::updated(result, current_topic(&app_state, tutor_id, topic_id)).await
 }
 
+#[utoipa::path(
+    patch,
+    path = "/tutors/{tutor_id}/{topic_id}",
+    tag = "topics",
+    params(
+        ("tutor_id" = i32, Path, description = "Tutor id"),
+        ("topic_id" = i32, Path, description = "Topic id"),
+        ("If-Match" = Option<String>, Header, description = "Only update if the topic still has this ETag"),
+    ),
+    request_body(content = TopicPatch, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "The updated topic", body = Topic, headers(("ETag" = String, description = "The topic's new version"))),
+        (status = 400, description = "Malformed JSON, or null for title", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 412, description = "The topic has changed since the If-Match version; the body is the current topic", body = Topic, headers(("ETag" = String, description = "The topic's current version"))),
+        (status = 422, description = "The request body failed validation", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn patch_topic(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    patch: ValidatedJson<TopicPatch>,
+    if_match: IfMatchVersions,
+    identity: Identity,
+) -> Result<HttpResponse, AppErrorType> {
+    let (tutor_id, topic_id) = (params.0, params.1);
+    identity.require_owner(tutor_id)?;
+    let result = app_state
+        .topics
+        .patch_topic(tutor_id, topic_id, patch.into_inner(), if_match.as_deref())
+        .await;
+    etag::updated(result, current_topic(&app_state, tutor_id, topic_id)).await
+}
+
+// The body of a 412, looked up under the tutor in the path as the update was, so a stale
+// write through another tutor's path gets a 404 rather than that tutor's topic
+async fn current_topic(
+    app_state: &AppState,
+    tutor_id: i32,
+    topic_id: i32,
+) -> Result<Topic, AppErrorType> {
+    let topic = app_state.topics.get_topic_details(topic_id).await?;
+    if topic.tutor_id != tutor_id {
+        return Err(AppErrorType::NotFoundError("Topic id not found".into()));
+    }
+    Ok(topic)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tutors/{tutor_id}/{topic_id}",
+    tag = "topics",
+    params(("tutor_id" = i32, Path, description = "Tutor id"), ("topic_id" = i32, Path, description = "Topic id")),
+    responses(
+        (status = 200, description = "Confirmation message", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller may not change this resource", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn delete_topic(
     app_state: web::Data<AppState>,
     params: web::Path<(i32, i32)>,
+    identity: Identity,
 ) -> Result<HttpResponse, AppErrorType> {
     let (tutor_id, topic_id) = (params.0, params.1);
-    delete_topic_db(&app_state.db, tutor_id, topic_id)
+    identity.require_owner(tutor_id)?;
+    app_state
+        .topics
+        .delete_topic(tutor_id, topic_id)
         .await
         .map(|res| HttpResponse::Ok().json(res))
 }
@@ -65,55 +286,217 @@ pub async fn delete_topic(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use actix_web::http::StatusCode;
-    use actix_web::ResponseError;
-    use chrono::NaiveDate;
-    use dotenv::dotenv;
-    use sqlx::postgres::PgPool;
-    use std::env;
-    use std::sync::Mutex;
+    use crate::auth::Role;
+    use crate::handlers::testing::test_app_state;
+    use actix_web::body::to_bytes;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, FromRequest, ResponseError};
 
     #[actix_rt::test]
     async fn get_all_topics_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> = test_app_state().await;
+
+        let req = test::TestRequest::default().to_http_request();
+        let filter = web::Query::<TopicFilter>::from_query("").unwrap();
+        let page_params = web::Query::<PageParams>::from_query("").unwrap();
 
-        let resp = get_all_topics(app_state).await.unwrap();
+        let resp = get_all_topics(
+            app_state,
+            req,
+            filter,
+            page_params,
+            web::Query(StreamParams::default()),
+        )
+        .await
+        .unwrap();
 
         assert_eq!(resp.status(), StatusCode::OK);
     }
 
+    #[actix_rt::test]
+    async fn get_all_topics_follows_cursor() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let req = test::TestRequest::default()
+            .uri("/topics/?limit=3")
+            .to_http_request();
+        let filter = web::Query::<TopicFilter>::from_query("limit=3").unwrap();
+        let page_params = web::Query::<PageParams>::from_query("limit=3").unwrap();
+
+        let resp = get_all_topics(
+            app_state.clone(),
+            req,
+            filter,
+            page_params,
+            web::Query(StreamParams::default()),
+        )
+        .await
+        .unwrap();
+
+        let link = resp.headers().get(header::LINK).unwrap().to_str().unwrap();
+        assert_eq!(link, "</topics/?limit=3&offset=3>; rel=\"next\"");
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["items"].as_array().unwrap().len(), 3);
+        let cursor = body["next_cursor"].as_str().unwrap().to_string();
+
+        let query = format!("limit=3&cursor={}", cursor);
+        let req = test::TestRequest::default()
+            .uri(&format!("/topics/?{}", query))
+            .to_http_request();
+        let filter = web::Query::<TopicFilter>::from_query(&query).unwrap();
+        let page_params = web::Query::<PageParams>::from_query(&query).unwrap();
+
+        let resp = get_all_topics(
+            app_state,
+            req,
+            filter,
+            page_params,
+            web::Query(StreamParams::default()),
+        )
+        .await
+        .unwrap();
+
+        assert!(resp.headers().get(header::LINK).is_none());
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["items"][0]["title"], "Strings");
+        assert!(body["next_cursor"].is_null());
+    }
+
+    #[actix_rt::test]
+    async fn get_topics_for_tutor_with_total() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let req = test::TestRequest::default().to_http_request();
+        let params: web::Path<(i32,)> = web::Path::from((3,));
+        let filter = web::Query::<TopicFilter>::from_query("").unwrap();
+        let page_params =
+            web::Query::<PageParams>::from_query("limit=1&include_total=true").unwrap();
+
+        let resp = get_topics_for_tutor(
+            app_state,
+            req,
+            params,
+            filter,
+            page_params,
+            web::Query(StreamParams::default()),
+        )
+        .await
+        .unwrap();
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["total"], 2);
+        assert_eq!(body["items"][0]["title"], "Strings");
+    }
+
+    #[actix_rt::test]
+    async fn get_all_topics_rejects_offset_with_cursor() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let req = test::TestRequest::default().to_http_request();
+        let filter = web::Query::<TopicFilter>::from_query("").unwrap();
+        let page_params = web::Query::<PageParams>::from_query("offset=2&cursor=aWQ6Mg").unwrap();
+
+        let resp = get_all_topics(
+            app_state,
+            req,
+            filter,
+            page_params,
+            web::Query(StreamParams::default()),
+        )
+        .await;
+
+        assert!(resp.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn get_all_topics_filtered_and_sorted() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let req = test::TestRequest::default().to_http_request();
+        let filter = web::Query::<TopicFilter>::from_query("format=video&sort=-title").unwrap();
+        let page_params = web::Query::<PageParams>::from_query("limit=1").unwrap();
+
+        let resp = get_all_topics(
+            app_state,
+            req,
+            filter,
+            page_params,
+            web::Query(StreamParams::default()),
+        )
+        .await
+        .unwrap();
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["items"][0]["title"], "Strings");
+        assert_eq!(body["offset"], 0);
+        assert!(body["next_cursor"].is_string());
+    }
+
+    #[actix_rt::test]
+    async fn get_all_topics_rejects_unknown_sort_column() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let req = test::TestRequest::default().to_http_request();
+        let filter = web::Query::<TopicFilter>::from_query("sort=topic_description").unwrap();
+        let page_params = web::Query::<PageParams>::from_query("").unwrap();
+
+        let resp = get_all_topics(
+            app_state,
+            req,
+            filter,
+            page_params,
+            web::Query(StreamParams::default()),
+        )
+        .await;
+
+        assert!(resp.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn search_topics_ranks_and_highlights() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let req = test::TestRequest::default().to_http_request();
+        let search = web::Query::<TopicSearchParams>::from_query("q=lifetime").unwrap();
+        let page_params = web::Query::<PageParams>::from_query("").unwrap();
+
+        let resp = search_topics(app_state, req, search, page_params)
+            .await
+            .unwrap();
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        let items = body["items"].as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["id"], 2);
+        assert_eq!(items[0]["title_highlight"], "<b>Lifetimes</b>");
+        assert!(items[0]["rank"].as_f64().unwrap() > 0.0);
+    }
+
+    #[actix_rt::test]
+    async fn search_topics_requires_query() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let req = test::TestRequest::default().to_http_request();
+        let search = web::Query::<TopicSearchParams>::from_query("q=%20").unwrap();
+        let page_params = web::Query::<PageParams>::from_query("").unwrap();
+
+        let resp = search_topics(app_state, req, search, page_params).await;
+
+        assert!(resp.is_err());
+    }
+
     #[actix_rt::test]
     async fn get_topic_details_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> = test_app_state().await;
         let params: web::Path<(i32,)> = web::Path::from((1,));
 
-        let resp = get_topic_details(app_state, params).await.unwrap();
+        let req = test::TestRequest::default().to_http_request();
+        let resp = get_topic_details(app_state, req, params).await.unwrap();
 
         assert_eq!(resp.status(), StatusCode::OK);
     }
 
-    #[ignore]
     #[actix_rt::test]
     async fn post_topic_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> = test_app_state().await;
         let new_topic_payload = CreateTopic {
             tutor_id: 1,
             title: "Test topic".into(),
@@ -121,88 +504,282 @@ mod tests {
             format: None,
             topic_level: Some("Beginner".into()),
             duration: None,
+            capacity: None,
         };
-        let topic_param = web::Json(new_topic_payload);
+        let topic_param = ValidatedJson(new_topic_payload);
 
-        let resp = post_new_topic(topic_param, app_state).await.unwrap();
+        let resp = post_new_topic(
+            topic_param,
+            app_state,
+            Identity {
+                subject_id: 1,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap();
 
         assert_eq!(resp.status(), StatusCode::OK);
     }
 
+    #[actix_rt::test]
+    async fn post_topic_for_unknown_tutor_is_problem() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let new_topic_payload = CreateTopic {
+            tutor_id: 1000,
+            title: "Test topic".into(),
+            topic_description: None,
+            format: None,
+            topic_level: None,
+            duration: None,
+            capacity: None,
+        };
+
+        let err = post_new_topic(
+            ValidatedJson(new_topic_payload),
+            app_state,
+            Identity {
+                subject_id: 1000,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap_err();
+        let resp = err.error_response();
+
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["code"], "unprocessable_entity");
+        assert_eq!(body["status"], 422);
+        assert_eq!(body["title"], "Unprocessable Entity");
+    }
+
+    #[actix_rt::test]
+    async fn post_topic_malformed_json_names_the_field() {
+        let (req, mut payload) = test::TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload(r#"{"tutor_id": "one", "title": "Traits"}"#)
+            .to_http_parts();
+
+        let err = ValidatedJson::<CreateTopic>::from_request(&req, &mut payload)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        let detail = err.problem().detail;
+        assert!(detail.contains("at tutor_id"), "{}", detail);
+        assert!(detail.contains("line 1 column"), "{}", detail);
+    }
+
+    #[actix_rt::test]
+    async fn update_topic_rejects_unknown_level() {
+        let (req, mut payload) = test::TestRequest::put()
+            .set_json(serde_json::json!({ "title": "Traits", "topic_level": "Expert" }))
+            .to_http_parts();
+
+        let err = ValidatedJson::<UpdateTopic>::from_request(&req, &mut payload)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.problem().errors[0].field, "topic_level");
+        assert_eq!(err.problem().errors[0].code, "one_of");
+    }
+
     #[actix_rt::test]
     async fn update_topic_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> = test_app_state().await;
         let update_topic_payload = UpdateTopic {
-            title: Some("Updated title".into()),
+            title: "Updated title".into(),
             topic_description: Some("Updated topic description".into()),
             format: Some("Updated topic format".into()),
             duration: None,
-            topic_level: Some("Updated topic level".into()),
+            topic_level: Some("Intermediate".into()),
+            capacity: Some(20),
         };
-        let topic_param = web::Json(update_topic_payload);
+        let topic_param = ValidatedJson(update_topic_payload);
         let params: web::Path<(i32, i32)> = web::Path::from((1, 1));
 
-        let resp = update_topic_details(app_state, params, topic_param)
+        let resp = update_topic_details(
+            app_state,
+            params,
+            topic_param,
+            IfMatchVersions::default(),
+            Identity {
+                subject_id: 1,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn update_topic_of_another_tutor_is_forbidden() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let update_topic_payload = UpdateTopic {
+            title: "Updated title".into(),
+            topic_description: None,
+            format: None,
+            duration: None,
+            topic_level: None,
+            capacity: None,
+        };
+        let params: web::Path<(i32, i32)> = web::Path::from((1, 1));
+
+        let err = update_topic_details(
+            app_state,
+            params,
+            ValidatedJson(update_topic_payload),
+            IfMatchVersions::default(),
+            Identity {
+                subject_id: 2,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn stale_update_fallback_is_scoped_to_the_tutor() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+
+        // Topic 3 is Bob Lopez's, so it is not found under Mark Smith
+        let err = current_topic(&app_state, 1, 3).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        let topic = current_topic(&app_state, 3, 3).await.unwrap();
+        assert_eq!(topic.id, 3);
+    }
+
+    #[actix_rt::test]
+    async fn merge_patch_keeps_absent_fields_and_clears_nulls() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let owner = Identity {
+            subject_id: 3,
+            role: Role::Tutor,
+        };
+        let patch_body = |body: serde_json::Value| async move {
+            let (req, mut payload) = test::TestRequest::patch()
+                .insert_header((header::CONTENT_TYPE, "application/merge-patch+json"))
+                .set_payload(body.to_string())
+                .to_http_parts();
+            ValidatedJson::<TopicPatch>::from_request(&req, &mut payload).await
+        };
+
+        let patch = patch_body(serde_json::json!({ "format": null, "duration": "2h" }))
             .await
             .unwrap();
+        let resp = patch_topic(
+            app_state.clone(),
+            web::Path::from((3, 3)),
+            patch,
+            IfMatchVersions::default(),
+            owner,
+        )
+        .await
+        .unwrap();
+        let topic: Topic =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(topic.title, "Concurrency");
+        assert_eq!(topic.format, None);
+        assert_eq!(topic.duration.as_deref(), Some("2h"));
+        // Untouched nullable fields stay null rather than becoming empty strings
+        assert_eq!(topic.topic_level, None);
 
-        assert_eq!(resp.status(), StatusCode::OK);
+        let err = patch_body(serde_json::json!({ "title": null }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn put_replaces_the_whole_topic() {
+        let app_state: web::Data<AppState> = test_app_state().await;
+        let replacement = UpdateTopic {
+            title: "Traits in depth".into(),
+            topic_description: None,
+            format: Some("video".into()),
+            duration: None,
+            topic_level: None,
+            capacity: None,
+        };
+
+        update_topic_details(
+            app_state.clone(),
+            web::Path::from((1, 1)),
+            ValidatedJson(replacement),
+            IfMatchVersions::default(),
+            Identity {
+                subject_id: 1,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap();
+
+        let topic = app_state.topics.get_topic_details(1).await.unwrap();
+        assert_eq!(topic.format.as_deref(), Some("video"));
+        assert_eq!(topic.topic_level, None);
+        assert_eq!(topic.topic_description, None);
     }
 
-    #[ignore]
     #[actix_rt::test]
     async fn delete_test_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> = test_app_state().await;
 
         let params: web::Path<(i32, i32)> = web::Path::from((1, 1));
 
-        let resp = delete_topic(app_state, params).await.unwrap();
+        let resp = delete_topic(
+            app_state,
+            params,
+            Identity {
+                subject_id: 1,
+                role: Role::Tutor,
+            },
+        )
+        .await
+        .unwrap();
 
         assert_eq!(resp.status(), StatusCode::OK);
     }
 
     #[actix_rt::test]
     async fn get_topic_details_failure_test() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> = test_app_state().await;
         let params: web::Path<(i32,)> = web::Path::from((1000,));
 
-        let resp = get_topic_details(app_state, params).await;
+        let req = test::TestRequest::default().to_http_request();
+        let resp = get_topic_details(app_state, req, params).await;
 
-        assert_eq!(resp.is_err(), true);
+        assert!(resp.is_err());
     }
 
     #[actix_rt::test]
     async fn delete_test_failure() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> = test_app_state().await;
 
         let params: web::Path<(i32, i32)> = web::Path::from((1, 100));
 
-        let resp = delete_topic(app_state, params).await;
+        let resp = delete_topic(
+            app_state,
+            params,
+            Identity {
+                subject_id: 1,
+                role: Role::Tutor,
+            },
+        )
+        .await;
 
-        assert_eq!(resp.is_err(), true);
+        assert!(resp.is_err());
     }
 }
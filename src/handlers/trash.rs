@@ -0,0 +1,134 @@
+use crate::errors::{AppErrorResponse, AppErrorType};
+use crate::models::topic::Topic;
+use crate::models::trash::{Trash, TutorRestoration};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+
+#[utoipa::path(
+    get,
+    path = "/trash",
+    tag = "trash",
+    responses(
+        (status = 200, description = "Soft-deleted tutors and topics, with when each will be purged", body = Trash),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not an admin", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn list_trash(app_state: web::Data<AppState>) -> Result<HttpResponse, AppErrorType> {
+    app_state
+        .trash
+        .list_trash(app_state.settings.trash.retention())
+        .await
+        .map(|trash| HttpResponse::Ok().json(trash))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tutors/{tutor_id}/restore",
+    tag = "trash",
+    params(("tutor_id" = i32, Path, description = "Tutor id")),
+    responses(
+        (status = 200, description = "The restored tutor and the topics restored with them", body = TutorRestoration),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not an admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Tutor not found in the trash", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The tutor's email has been registered again", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn restore_tutor(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let tutor_id = params.0;
+    app_state
+        .trash
+        .restore_tutor(tutor_id)
+        .await
+        .map(|restoration| HttpResponse::Ok().json(restoration))
+}
+
+#[utoipa::path(
+    post,
+    path = "/topics/{topic_id}/restore",
+    tag = "trash",
+    params(("topic_id" = i32, Path, description = "Topic id")),
+    responses(
+        (status = 200, description = "The restored topic", body = Topic),
+        (status = 401, description = "Missing or invalid bearer token", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The caller is not an admin", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Topic not found in the trash", body = AppErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The topic's tutor is still in the trash", body = AppErrorResponse, content_type = "application/problem+json"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn restore_topic(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+) -> Result<HttpResponse, AppErrorType> {
+    let topic_id = params.0;
+    app_state
+        .trash
+        .restore_topic(topic_id)
+        .await
+        .map(|topic| HttpResponse::Ok().json(topic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::testing::test_app_state;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    #[actix_rt::test]
+    async fn restoring_a_tutor_restores_their_topics() {
+        let app_state = test_app_state().await;
+        app_state.topics.delete_topic(3, 4).await.unwrap();
+        app_state.tutors.delete_tutor(3, false).await.unwrap();
+
+        let resp = list_trash(app_state.clone()).await.unwrap();
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let trash: Trash = serde_json::from_slice(&body).unwrap();
+        assert_eq!(trash.tutors.len(), 1);
+        assert_eq!(trash.topics.len(), 2);
+        assert_eq!(
+            trash.tutors[0].purge_at - trash.tutors[0].deleted_at,
+            chrono::Duration::days(30)
+        );
+
+        let resp = restore_tutor(app_state.clone(), web::Path::from((3,)))
+            .await
+            .unwrap();
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let restoration: TutorRestoration = serde_json::from_slice(&body).unwrap();
+        // Topic 4 was deleted on its own earlier, so it stays in the trash
+        assert_eq!(restoration.topic_count, 1);
+        assert_eq!(restoration.topics[0].id, 3);
+        assert!(app_state.topics.get_topic_details(3).await.is_ok());
+        assert!(app_state.topics.get_topic_details(4).await.is_err());
+
+        let resp = restore_topic(app_state.clone(), web::Path::from((4,)))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn topic_of_a_trashed_tutor_cannot_be_restored_alone() {
+        let app_state = test_app_state().await;
+        app_state.tutors.delete_tutor(1, false).await.unwrap();
+
+        let err = restore_topic(app_state.clone(), web::Path::from((1,)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        let err = restore_tutor(app_state, web::Path::from((2,)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,51 +1,156 @@
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware, web, App, HttpServer};
 use dotenv::dotenv;
-use sqlx::postgres::PgPool;
+use sqlx::postgres::{PgPool, PgPoolOptions};
 use std::env;
 use std::io;
+use std::process;
+use std::sync::Arc;
 
+#[path = "./auth.rs"]
+mod auth;
 #[path = "./dbaccess/mod.rs"]
 mod dbaccess;
 #[path = "./errors.rs"]
 mod errors;
+#[path = "./etag.rs"]
+mod etag;
+#[path = "./extractors.rs"]
+mod extractors;
 #[path = "./handlers/mod.rs"]
 mod handlers;
+#[path = "./metrics.rs"]
+mod metrics;
+#[path = "./migrate.rs"]
+mod migrate;
 #[path = "./models/mod.rs"]
 mod models;
+#[path = "./openapi.rs"]
+mod openapi;
+#[path = "./purge.rs"]
+mod purge;
 #[path = "./routes.rs"]
 mod routes;
+#[path = "./settings.rs"]
+mod settings;
 #[path = "./state.rs"]
 mod state;
+#[path = "./streaming.rs"]
+mod streaming;
+#[path = "./telemetry.rs"]
+mod telemetry;
 
+use auth::JwtKeys;
+use dbaccess::memory::InMemoryRepository;
+use dbaccess::postgres::PgRepository;
 use routes::*;
+use settings::{DatabaseSettings, Settings, StorageBackend};
 use state::AppState;
 
 #[actix_rt::main]
 async fn main() -> io::Result<()> {
     dotenv().ok();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-    let db_pool = PgPool::connect(&database_url).await.unwrap();
-    // Construct App State
-    let shared_data = web::Data::new(AppState {
-        health_check_response: "I'm good.".to_string(),
-        db: db_pool,
+    // `rust_tutors migrate <up|down|status>` manages the schema and exits. It only needs the
+    // database settings, so it is dispatched before the server's are loaded.
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("migrate") => return migrate_command(args.get(1).map(String::as_str)).await,
+        Some(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, migrate::USAGE)),
+        None => {}
+    }
+
+    let settings = Settings::load().unwrap_or_else(|err| {
+        eprint!("{}", err);
+        process::exit(1);
     });
+    telemetry::init(&settings.log);
+
+    // Construct App State
+    let health_check_response = "I'm good.".to_string();
+    let jwt = JwtKeys::new(settings.auth.jwt_secret.as_bytes());
+
+    // database.backend = "memory" runs the API against a throwaway in-memory store
+    let app_state = match settings.database.backend {
+        StorageBackend::Memory => AppState::with_repository(
+            Arc::new(InMemoryRepository::new()),
+            health_check_response,
+            jwt,
+            settings.clone(),
+        ),
+        StorageBackend::Postgres => {
+            let db_pool = connect(&settings.database).await?;
+            migrate::run_pending_migrations(&db_pool).await?;
+
+            AppState::with_repository(
+                Arc::new(PgRepository::new(db_pool)),
+                health_check_response,
+                jwt,
+                settings.clone(),
+            )
+        }
+    };
+
+    let shared_data = web::Data::new(app_state);
+    purge::spawn(shared_data.trash.clone(), settings.trash.clone());
+
     //Construct app and configure routes
     let app = move || {
+        let import_limit_bytes = shared_data.settings.server.import_limit_bytes;
         App::new()
+            .wrap(middleware::from_fn(errors::problem_instance))
+            .wrap(middleware::from_fn(metrics::track_requests))
+            .wrap(middleware::from_fn(telemetry::request_span))
             .app_data(shared_data.clone())
-            .app_data(web::JsonConfig::default().error_handler(|_err, _req| {
-                errors::AppErrorType::InvalidInput("Invalid JSON input".to_string()).into()
+            .app_data(web::PayloadConfig::new(
+                shared_data.settings.server.json_limit_bytes,
+            ))
+            .app_data(web::QueryConfig::default().error_handler(|err, _req| {
+                errors::AppErrorType::InvalidInput(err.to_string()).into()
             }))
             .configure(general_routes)
+            .configure(auth_routes)
             .configure(tutor_routes)
             .configure(topic_routes)
+            .configure(trash_routes)
+            .configure(|cfg| catalogue_routes(cfg, import_limit_bytes))
+            .configure(student_routes)
+            .configure(docs_routes)
     };
 
-    let host_port =
-        env::var("SERVER_HOST_PORT").expect("SERVER_HOSTNAME_PORT is not set in .env file");
-
     //Start HTTP server
-    HttpServer::new(app).bind(host_port).unwrap().run().await
+    HttpServer::new(app)
+        .workers(settings.server.workers)
+        .client_request_timeout(settings.server.request_timeout())
+        .keep_alive(settings.server.keep_alive())
+        .shutdown_timeout(settings.server.shutdown_timeout_secs)
+        .bind(&settings.server.bind)?
+        .run()
+        .await
+}
+
+async fn migrate_command(action: Option<&str>) -> io::Result<()> {
+    let database = Settings::load_database().unwrap_or_else(|err| {
+        eprint!("{}", err);
+        process::exit(1);
+    });
+    // The in-memory store has no schema to migrate
+    if database.backend == StorageBackend::Memory {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            migrate::NEEDS_POSTGRES,
+        ));
+    }
+
+    let db_pool = connect(&database).await?;
+    migrate::run_migrate_command(&db_pool, action).await
+}
+
+async fn connect(database: &DatabaseSettings) -> io::Result<PgPool> {
+    PgPoolOptions::new()
+        .max_connections(database.max_connections)
+        .min_connections(database.min_connections)
+        .acquire_timeout(database.acquire_timeout())
+        .connect(&database.url)
+        .await
+        .map_err(io::Error::other)
 }
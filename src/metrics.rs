@@ -0,0 +1,236 @@
+use crate::dbaccess::RowStream;
+use crate::errors::AppErrorType;
+use crate::models::health::PoolStats;
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::future::Future;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+// Errors are counted from `ResponseError::error_response`, which has no access to app state,
+// so the collectors live in one process-wide registry
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+// Requests that matched no route share one label instead of one per probed URL
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    db_query_duration: HistogramVec,
+    db_pool_connections: IntGaugeVec,
+    app_errors: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time to produce an HTTP response",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let db_query_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "db_query_duration_seconds",
+                "Time spent in each dbaccess function",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+            ]),
+            &["query", "outcome"],
+        )
+        .unwrap();
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .unwrap();
+        let app_errors = IntCounterVec::new(
+            Opts::new(
+                "app_errors_total",
+                "Error responses by AppErrorType variant",
+            ),
+            &["variant", "code"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_query_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_pool_connections.clone()))
+            .unwrap();
+        registry.register(Box::new(app_errors.clone())).unwrap();
+
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            db_query_duration,
+            db_pool_connections,
+            app_errors,
+        }
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_request_duration
+            .with_label_values(&labels)
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_query(&self, query: &str, succeeded: bool, elapsed: Duration) {
+        let outcome = if succeeded { "ok" } else { "error" };
+        self.db_query_duration
+            .with_label_values(&[query, outcome])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn count_error(&self, error: &AppErrorType) {
+        self.app_errors
+            .with_label_values(&[error.variant(), error.code()])
+            .inc();
+    }
+
+    pub fn set_pool_stats(&self, stats: &PoolStats) {
+        for (state, connections) in [
+            ("size", stats.size),
+            ("idle", stats.idle),
+            ("in_use", stats.in_use),
+        ] {
+            self.db_pool_connections
+                .with_label_values(&[state])
+                .set(connections.into());
+        }
+    }
+
+    // Everything collected so far, in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+// Times one dbaccess function; `query` is its name, e.g. "get_all_topics_db"
+pub async fn timed_query<T>(
+    query: &'static str,
+    call: impl Future<Output = Result<T, AppErrorType>>,
+) -> Result<T, AppErrorType> {
+    let started = Instant::now();
+    let result = call.await;
+    METRICS.observe_query(query, result.is_ok(), started.elapsed());
+    result
+}
+
+// `timed_query` for a streamed listing: observed once the last row has been read, or at
+// the first error. A stream dropped early, because the client went away, is not counted.
+pub fn timed_stream<T: Send + 'static>(query: &'static str, rows: RowStream<T>) -> RowStream<T> {
+    Box::pin(async_stream::stream! {
+        let started = Instant::now();
+        for await row in rows {
+            let failed = row.is_err();
+            yield row;
+            if failed {
+                METRICS.observe_query(query, false, started.elapsed());
+                return;
+            }
+        }
+        METRICS.observe_query(query, true, started.elapsed());
+    })
+}
+
+// Counts and times every request, labelled with the route pattern it matched
+// (e.g. `/tutors/{tutor_id}`) rather than the concrete path
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let method = req.method().to_string();
+    let started = Instant::now();
+    let result = next.call(req).await;
+
+    let (route, status) = match &result {
+        Ok(res) => (res.request().match_pattern(), res.status()),
+        Err(err) => (None, err.as_response_error().status_code()),
+    };
+    METRICS.observe_request(
+        &method,
+        route.as_deref().unwrap_or(UNMATCHED_ROUTE),
+        status.as_u16(),
+        started.elapsed(),
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{middleware, test, web, App, HttpResponse};
+
+    #[actix_rt::test]
+    async fn requests_are_labelled_by_route_pattern() {
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(track_requests))
+                .service(
+                    web::scope("/metrics-test")
+                        .route("/{item_id}", web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        for uri in [
+            "/metrics-test/1",
+            "/metrics-test/2",
+            "/metrics-test/1/extra",
+        ] {
+            test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        }
+
+        let rendered = METRICS.render();
+        assert!(rendered.contains(
+            r#"http_requests_total{method="GET",route="/metrics-test/{item_id}",status="200"} 2"#
+        ));
+        assert!(rendered.contains(r#"route="unmatched",status="404"}"#));
+    }
+
+    #[actix_rt::test]
+    async fn queries_and_errors_are_recorded() {
+        let _ = timed_query("metrics_test_db", async {
+            Err::<(), _>(AppErrorType::NotFoundError("missing".into()))
+        })
+        .await;
+        METRICS.count_error(&AppErrorType::ConflictError("taken".into()));
+
+        let rendered = METRICS.render();
+        assert!(rendered.contains(
+            r#"db_query_duration_seconds_count{outcome="error",query="metrics_test_db"} 1"#
+        ));
+        assert!(rendered.contains(r#"app_errors_total{code="conflict",variant="ConflictError"}"#));
+    }
+}
@@ -0,0 +1,95 @@
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::io;
+
+// Migrations under ./migrations are embedded into the binary at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+pub const USAGE: &str = "usage: rust_tutors [migrate <up|down|status>]";
+pub const NEEDS_POSTGRES: &str =
+    "migrations need the postgres backend; set database.backend = \"postgres\"";
+
+pub async fn run_pending_migrations(pool: &PgPool) -> io::Result<()> {
+    MIGRATOR.run(pool).await.map_err(io::Error::other)
+}
+
+pub async fn run_migrate_command(pool: &PgPool, action: Option<&str>) -> io::Result<()> {
+    let result = match action {
+        Some("up") => MIGRATOR
+            .run(pool)
+            .await
+            .map(|_| println!("Database is up to date")),
+        Some("down") => revert_latest_migration(pool).await,
+        Some("status") => print_migration_status(pool).await,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, USAGE)),
+    };
+    result.map_err(io::Error::other)
+}
+
+async fn revert_latest_migration(pool: &PgPool) -> Result<(), MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut applied: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+    drop(conn);
+    applied.sort_unstable();
+
+    let latest = match applied.pop() {
+        Some(version) => version,
+        None => {
+            println!("No applied migrations to revert");
+            return Ok(());
+        }
+    };
+    // Undo everything newer than the previously applied version, i.e. just the latest one
+    let target = applied.last().copied().unwrap_or(0);
+    MIGRATOR.undo(pool, target).await?;
+    println!("Reverted migration {}", latest);
+    Ok(())
+}
+
+async fn print_migration_status(pool: &PgPool) -> Result<(), MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied: HashMap<i64, Vec<u8>> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| (migration.version, migration.checksum.into_owned()))
+        .collect();
+
+    for migration in MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        let status = match applied.get(&migration.version) {
+            Some(checksum) if checksum[..] == migration.checksum[..] => "applied",
+            Some(_) => "applied (checksum mismatch)",
+            None => "pending",
+        };
+        println!(
+            "{:>14}  {:<28}  {}",
+            migration.version, status, migration.description
+        );
+    }
+
+    for version in applied.keys() {
+        if !MIGRATOR
+            .iter()
+            .any(|migration| migration.version == *version)
+        {
+            println!("{:>14}  {:<28}", version, "applied (missing locally)");
+        }
+    }
+
+    if let Some(version) = conn.dirty_version().await? {
+        println!("Migration {} is dirty and needs manual repair", version);
+    }
+
+    Ok(())
+}
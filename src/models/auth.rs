@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct LoginRequest {
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub password: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct RefreshRequest {
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
+
+// Stored login details for a tutor; tutors created before accounts existed have no password
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TutorCredentials {
+    pub tutor_id: i32,
+    pub password_hash: Option<String>,
+    pub role: String,
+}
+
+// Stored login details for a student; students created without a password have none
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct StudentCredentials {
+    pub student_id: i32,
+    pub password_hash: Option<String>,
+}
@@ -0,0 +1,416 @@
+use super::session::{in_zone, invalid, known_time_zone, to_utc, Interval};
+use crate::errors::AppErrorType;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+pub const MAX_SLOT_RANGE_DAYS: i64 = 31;
+pub const MAX_WINDOWS: usize = 50;
+const DEFAULT_SLOT_MINUTES: i64 = 60;
+// Both slots and bookings
+const SLOT_MINUTES: RangeInclusive<i64> = 15..=480;
+
+// A weekly window, as wall-clock times in the tutor's time zone
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct WeeklyWindow {
+    /// ISO day number, 1 = Monday to 7 = Sunday
+    pub weekday: i16,
+    pub starts_at: NaiveTime,
+    pub ends_at: NaiveTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct TimeWindow {
+    pub starts_at: NaiveTime,
+    pub ends_at: NaiveTime,
+}
+
+// The body of `PUT /tutors/{tutor_id}/availability`, which replaces every weekly window
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct WeeklyAvailability {
+    /// IANA time zone name that the windows and exceptions are in
+    #[validate(custom(function = "known_time_zone"))]
+    pub time_zone: String,
+    #[validate(custom(function = "valid_weekly_windows"))]
+    pub windows: Vec<WeeklyWindow>,
+}
+
+// The body of `PUT /tutors/{tutor_id}/availability/exceptions/{date}`; no windows takes the
+// whole day off
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct ExceptionWindows {
+    #[validate(custom(function = "valid_windows"))]
+    pub windows: Vec<TimeWindow>,
+}
+
+// Windows that replace the weekly ones on `date`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AvailabilityException {
+    pub date: NaiveDate,
+    pub windows: Vec<TimeWindow>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Availability {
+    pub tutor_id: i32,
+    pub time_zone: String,
+    // By weekday, then start
+    pub windows: Vec<WeeklyWindow>,
+    // By date
+    pub exceptions: Vec<AvailabilityException>,
+}
+
+#[derive(Deserialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SlotParams {
+    /// First day to include, in UTC
+    pub from: NaiveDate,
+    /// Last day to include, in UTC; the range can cover at most 31 days
+    pub to: NaiveDate,
+    /// Slot length in minutes, from 15 to 480; 60 by default
+    pub duration: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Slot {
+    pub starts_at: DateTime<FixedOffset>,
+    pub ends_at: DateTime<FixedOffset>,
+}
+
+// The booking student is the caller, never a field of the body
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct NewBooking {
+    /// Usually the times of a free slot; any UTC offset is accepted
+    pub starts_at: DateTime<FixedOffset>,
+    pub ends_at: DateTime<FixedOffset>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct Booking {
+    pub id: i32,
+    pub tutor_id: i32,
+    pub student_id: i32,
+    // UTC
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+fn valid_weekly_windows(windows: &[WeeklyWindow]) -> Result<(), ValidationError> {
+    if windows
+        .iter()
+        .any(|window| !(1..=7).contains(&window.weekday))
+    {
+        return Err(windows_error(
+            "weekday must be from 1 (Monday) to 7 (Sunday)",
+        ));
+    }
+    disjoint(
+        windows
+            .iter()
+            .map(|window| (window.weekday, window.starts_at, window.ends_at))
+            .collect(),
+    )
+}
+
+fn valid_windows(windows: &[TimeWindow]) -> Result<(), ValidationError> {
+    disjoint(
+        windows
+            .iter()
+            .map(|window| (0, window.starts_at, window.ends_at))
+            .collect(),
+    )
+}
+
+// Windows are (day, start, end); windows on the same day must not overlap
+fn disjoint(mut windows: Vec<(i16, NaiveTime, NaiveTime)>) -> Result<(), ValidationError> {
+    if windows.len() > MAX_WINDOWS {
+        return Err(windows_error(&format!(
+            "must have at most {} windows",
+            MAX_WINDOWS
+        )));
+    }
+    if windows
+        .iter()
+        .any(|(_, starts_at, ends_at)| ends_at <= starts_at)
+    {
+        return Err(windows_error("each window must end after it starts"));
+    }
+    windows.sort();
+    if windows
+        .windows(2)
+        .any(|pair| pair[0].0 == pair[1].0 && pair[1].1 < pair[0].2)
+    {
+        return Err(windows_error("windows on the same day must not overlap"));
+    }
+    Ok(())
+}
+
+fn windows_error(message: &str) -> ValidationError {
+    ValidationError::new("windows").with_message(message.to_string().into())
+}
+
+impl SlotParams {
+    pub fn interval(&self) -> Result<Interval, AppErrorType> {
+        Interval::days(self.from, self.to, MAX_SLOT_RANGE_DAYS)
+    }
+
+    pub fn length(&self) -> Result<Duration, AppErrorType> {
+        let minutes = self.duration.unwrap_or(DEFAULT_SLOT_MINUTES);
+        if !SLOT_MINUTES.contains(&minutes) {
+            return Err(AppErrorType::InvalidInput(format!(
+                "duration must be from {} to {} minutes",
+                SLOT_MINUTES.start(),
+                SLOT_MINUTES.end()
+            )));
+        }
+        Ok(Duration::minutes(minutes))
+    }
+}
+
+impl NewBooking {
+    // The booked time in UTC. Errors are reported like failed validation.
+    pub fn interval(&self, now: NaiveDateTime) -> Result<Interval, AppErrorType> {
+        let interval = Interval {
+            starts_at: self.starts_at.naive_utc(),
+            ends_at: self.ends_at.naive_utc(),
+        };
+        let length = interval.ends_at - interval.starts_at;
+        if length < Duration::minutes(*SLOT_MINUTES.start())
+            || length > Duration::minutes(*SLOT_MINUTES.end())
+        {
+            return Err(invalid(
+                "ends_at",
+                "range",
+                &format!(
+                    "must be {} to {} minutes after starts_at",
+                    SLOT_MINUTES.start(),
+                    SLOT_MINUTES.end()
+                ),
+            ));
+        }
+        if interval.starts_at < now {
+            return Err(invalid("starts_at", "range", "must be in the future"));
+        }
+        Ok(interval)
+    }
+}
+
+impl Booking {
+    pub fn interval(&self) -> Interval {
+        Interval {
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+        }
+    }
+}
+
+impl Availability {
+    // What a tutor who never set any windows has
+    pub fn none(tutor_id: i32) -> Self {
+        Availability {
+            tutor_id,
+            time_zone: "UTC".into(),
+            windows: Vec::new(),
+            exceptions: Vec::new(),
+        }
+    }
+
+    fn tz(&self) -> Tz {
+        Tz::from_str(&self.time_zone).unwrap_or(Tz::UTC)
+    }
+
+    // The windows on a local date as UTC intervals, in order
+    fn windows_on(&self, tz: Tz, date: NaiveDate) -> Vec<Interval> {
+        let local = |starts_at: NaiveTime, ends_at: NaiveTime| Interval {
+            starts_at: to_utc(tz, date.and_time(starts_at)),
+            ends_at: to_utc(tz, date.and_time(ends_at)),
+        };
+        match self
+            .exceptions
+            .iter()
+            .find(|exception| exception.date == date)
+        {
+            Some(exception) => exception
+                .windows
+                .iter()
+                .map(|window| local(window.starts_at, window.ends_at))
+                .collect(),
+            None => self
+                .windows
+                .iter()
+                .filter(|window| window.weekday as u32 == date.weekday().number_from_monday())
+                .map(|window| local(window.starts_at, window.ends_at))
+                .collect(),
+        }
+    }
+
+    // Back-to-back slots of `length` from the start of each window, keeping those that start
+    // within `range`, no earlier than `now`, and clear of every `busy` interval
+    pub fn free_slots(
+        &self,
+        range: Interval,
+        length: Duration,
+        busy: &[Interval],
+        now: NaiveDateTime,
+    ) -> Vec<Slot> {
+        let tz = self.tz();
+        let first = tz.from_utc_datetime(&range.starts_at).date_naive();
+        let last = tz.from_utc_datetime(&range.ends_at).date_naive();
+        let mut slots = Vec::new();
+
+        for date in first.iter_days().take_while(|date| *date <= last) {
+            for window in self.windows_on(tz, date) {
+                let mut starts_at = window.starts_at;
+                while starts_at + length <= window.ends_at {
+                    let slot = Interval {
+                        starts_at,
+                        ends_at: starts_at + length,
+                    };
+                    if starts_at >= range.starts_at.max(now)
+                        && starts_at < range.ends_at
+                        && !busy.iter().any(|busy| busy.overlaps(&slot))
+                    {
+                        slots.push(Slot {
+                            starts_at: in_zone(tz, slot.starts_at),
+                            ends_at: in_zone(tz, slot.ends_at),
+                        });
+                    }
+                    starts_at += length;
+                }
+            }
+        }
+        slots
+    }
+
+    // Whether a booking lies within one window; windows never span midnight, so only the
+    // local date the booking starts on needs checking
+    pub fn covers(&self, booking: Interval) -> bool {
+        let tz = self.tz();
+        let date = tz.from_utc_datetime(&booking.starts_at).date_naive();
+        self.windows_on(tz, date).iter().any(|window| {
+            window.starts_at <= booking.starts_at && booking.ends_at <= window.ends_at
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+    }
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    // Mondays 09:00 to 12:00 in Berlin, except 10 June when it is 14:00 to 15:00
+    fn availability() -> Availability {
+        Availability {
+            tutor_id: 1,
+            time_zone: "Europe/Berlin".into(),
+            windows: vec![WeeklyWindow {
+                weekday: 1,
+                starts_at: time("09:00"),
+                ends_at: time("12:00"),
+            }],
+            exceptions: vec![AvailabilityException {
+                date: NaiveDate::from_ymd_opt(2024, 6, 10).unwrap(),
+                windows: vec![TimeWindow {
+                    starts_at: time("14:00"),
+                    ends_at: time("15:00"),
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn free_slots_skip_busy_times_and_follow_exceptions() {
+        let range = Interval::days(
+            NaiveDate::from_ymd_opt(2024, 6, 3).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 6, 10).unwrap(),
+            MAX_SLOT_RANGE_DAYS,
+        )
+        .unwrap();
+        // 10:30 to 11:00 Berlin time on 3 June
+        let busy = [Interval {
+            starts_at: at("2024-06-03 08:30"),
+            ends_at: at("2024-06-03 09:00"),
+        }];
+        let slots =
+            availability().free_slots(range, Duration::minutes(60), &busy, at("2024-01-01 00:00"));
+        let starts: Vec<String> = slots
+            .iter()
+            .map(|slot| slot.starts_at.to_rfc3339())
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                "2024-06-03T09:00:00+02:00",
+                "2024-06-03T11:00:00+02:00",
+                "2024-06-10T14:00:00+02:00"
+            ]
+        );
+
+        // Nothing before `now`
+        let slots =
+            availability().free_slots(range, Duration::minutes(30), &[], at("2024-06-03 09:00"));
+        assert_eq!(slots[0].starts_at.to_rfc3339(), "2024-06-03T11:00:00+02:00");
+    }
+
+    #[test]
+    fn bookings_must_lie_within_a_window() {
+        let availability = availability();
+        let covers = |starts_at: &str, ends_at: &str| {
+            availability.covers(Interval {
+                starts_at: at(starts_at),
+                ends_at: at(ends_at),
+            })
+        };
+        assert!(covers("2024-06-03 07:15", "2024-06-03 08:00"));
+        assert!(!covers("2024-06-03 09:30", "2024-06-03 10:30"));
+        // The exception replaces the Monday window
+        assert!(!covers("2024-06-10 07:00", "2024-06-10 08:00"));
+        assert!(covers("2024-06-10 12:00", "2024-06-10 13:00"));
+
+        let booking = NewBooking {
+            starts_at: DateTime::parse_from_rfc3339("2024-06-03T09:00:00+02:00").unwrap(),
+            ends_at: DateTime::parse_from_rfc3339("2024-06-03T09:10:00+02:00").unwrap(),
+        };
+        assert!(booking.interval(at("2024-01-01 00:00")).is_err());
+    }
+
+    #[test]
+    fn windows_on_the_same_day_must_not_overlap() {
+        let weekly = |windows: &[(i16, &str, &str)]| WeeklyAvailability {
+            time_zone: "UTC".into(),
+            windows: windows
+                .iter()
+                .map(|(weekday, starts_at, ends_at)| WeeklyWindow {
+                    weekday: *weekday,
+                    starts_at: time(starts_at),
+                    ends_at: time(ends_at),
+                })
+                .collect(),
+        };
+        assert!(weekly(&[(1, "09:00", "12:00"), (1, "12:00", "13:00")])
+            .validate()
+            .is_ok());
+        assert!(weekly(&[(1, "09:00", "12:00"), (2, "10:00", "11:00")])
+            .validate()
+            .is_ok());
+        assert!(weekly(&[(1, "09:00", "12:00"), (1, "11:00", "13:00")])
+            .validate()
+            .is_err());
+        assert!(weekly(&[(8, "09:00", "12:00")]).validate().is_err());
+        assert!(weekly(&[(1, "12:00", "09:00")]).validate().is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use super::session::{Interval, Session};
+use super::topic::Topic;
+use chrono::NaiveDateTime;
+use sha2::{Digest, Sha256};
+
+pub const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+const PRODID: &str = "-//rust_tutors//Tutor calendar//EN";
+// The right-hand side of every UID
+const UID_DOMAIN: &str = "rust-tutors";
+// RFC 5545 lines are at most 75 octets, not counting the CRLF
+const MAX_LINE_OCTETS: usize = 75;
+
+// The sessions behind a tutor's or a topic's feed
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    pub name: String,
+    pub events: Vec<CalendarEvent>,
+}
+
+// A session with its topic and every occurrence, in UTC and in order
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+    pub session: Session,
+    pub topic: Topic,
+    pub occurrences: Vec<Interval>,
+}
+
+impl Calendar {
+    // One VEVENT per session, so its UID stays the same when the session is moved. Times are
+    // in UTC, which needs no VTIMEZONE; the occurrences after the first are listed in RDATE
+    // rather than as an RRULE, so DST changes come out as the session defined them.
+    // `generated_at` is the DTSTAMP of every event.
+    pub fn to_ics(&self, generated_at: NaiveDateTime) -> String {
+        self.lines(Some(generated_at))
+            .iter()
+            .map(|line| fold(line) + "\r\n")
+            .collect()
+    }
+
+    // Changes whenever the feed's content does, and only then: DTSTAMP, which changes with
+    // every response, is left out
+    pub fn feed_tag(&self) -> String {
+        let mut digest = Sha256::new();
+        for line in self.lines(None) {
+            digest.update(line);
+            digest.update("\r\n");
+        }
+        format!("{:x}", digest.finalize())
+    }
+
+    fn lines(&self, dtstamp: Option<NaiveDateTime>) -> Vec<String> {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            format!("PRODID:{}", PRODID),
+            "CALSCALE:GREGORIAN".to_string(),
+            format!("X-WR-CALNAME:{}", escape_text(&self.name)),
+        ];
+        for event in &self.events {
+            let Some((first, rest)) = event.occurrences.split_first() else {
+                continue;
+            };
+            let modified = event
+                .topic
+                .updated_at
+                .map_or(event.session.updated_at, |topic_updated_at| {
+                    topic_updated_at.max(event.session.updated_at)
+                });
+            lines.extend([
+                "BEGIN:VEVENT".to_string(),
+                format!("UID:session-{}@{}", event.session.id, UID_DOMAIN),
+            ]);
+            lines.extend(dtstamp.map(|dtstamp| format!("DTSTAMP:{}", utc(dtstamp))));
+            lines.extend([
+                format!("LAST-MODIFIED:{}", utc(modified)),
+                format!("DTSTART:{}", utc(first.starts_at)),
+                format!("DTEND:{}", utc(first.ends_at)),
+            ]);
+            if !rest.is_empty() {
+                let dates: Vec<String> = rest
+                    .iter()
+                    .map(|occurrence| utc(occurrence.starts_at))
+                    .collect();
+                lines.push(format!("RDATE:{}", dates.join(",")));
+            }
+            lines.extend([
+                format!("SUMMARY:{}", escape_text(&event.topic.title)),
+                format!("DESCRIPTION:{}", escape_text(&description(&event.topic))),
+                "END:VEVENT".to_string(),
+            ]);
+        }
+        lines.push("END:VCALENDAR".to_string());
+        lines
+    }
+}
+
+fn description(topic: &Topic) -> String {
+    let mut parts = vec![topic.title.clone()];
+    parts.extend(topic.topic_description.clone());
+    parts.extend(
+        topic
+            .format
+            .as_ref()
+            .map(|format| format!("Format: {}", format)),
+    );
+    parts.extend(
+        topic
+            .topic_level
+            .as_ref()
+            .map(|level| format!("Level: {}", level)),
+    );
+    parts.join("\n")
+}
+
+fn utc(time: NaiveDateTime) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// TEXT values escape backslashes, semicolons, commas and newlines
+fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Continuation lines start with a space, which counts towards their 75 octets. Lines are only
+// broken between characters, never inside a UTF-8 sequence.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn calendar() -> Calendar {
+        let topic = Topic {
+            id: 4,
+            tutor_id: 3,
+            title: "Rust; the basics".into(),
+            topic_description: Some("Ownership, borrowing\nand lifetimes".into()),
+            format: Some("Online".into()),
+            duration: None,
+            topic_level: Some("Beginner".into()),
+            capacity: None,
+            average_rating: None,
+            review_count: 0,
+            created_at: Some(at("2024-05-01 10:00")),
+            updated_at: Some(at("2024-05-20 10:00")),
+            version: 2,
+        };
+        let session = Session {
+            id: 7,
+            topic_id: 4,
+            tutor_id: 3,
+            starts_at: at("2024-06-03 18:00"),
+            ends_at: at("2024-06-03 19:30"),
+            time_zone: "Europe/London".into(),
+            recurrence: Some("FREQ=WEEKLY;COUNT=2".into()),
+            created_at: at("2024-05-02 10:00"),
+            updated_at: at("2024-05-02 10:00"),
+        };
+        Calendar {
+            name: "Bob Lopez".into(),
+            events: vec![CalendarEvent {
+                session,
+                topic,
+                occurrences: vec![
+                    Interval {
+                        starts_at: at("2024-06-03 17:00"),
+                        ends_at: at("2024-06-03 18:30"),
+                    },
+                    Interval {
+                        starts_at: at("2024-06-10 17:00"),
+                        ends_at: at("2024-06-10 18:30"),
+                    },
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn sessions_become_events_with_stable_uids() {
+        let ics = calendar().to_ics(at("2024-06-01 09:30"));
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        for line in [
+            "UID:session-7@rust-tutors",
+            "DTSTAMP:20240601T093000Z",
+            "LAST-MODIFIED:20240520T100000Z",
+            "DTSTART:20240603T170000Z",
+            "DTEND:20240603T183000Z",
+            "RDATE:20240610T170000Z",
+            "SUMMARY:Rust\\; the basics",
+        ] {
+            assert!(ics.contains(&format!("\r\n{}\r\n", line)), "{}", line);
+        }
+        let unfolded = ics.replace("\r\n ", "");
+        assert!(unfolded.contains(
+            "DESCRIPTION:Rust\\; the basics\\nOwnership\\, borrowing\\nand lifetimes\\nFormat: Online\\nLevel: Beginner\r\n"
+        ));
+    }
+
+    #[test]
+    fn feed_tag_follows_the_content_but_not_the_dtstamp() {
+        let tag = calendar().feed_tag();
+        // A fixed digest, so tags survive rebuilds and restarts of the server
+        assert_eq!(
+            tag,
+            "836efeba97f169dc444ffa243c90310e7ab957710525b127142a700d6f80f624"
+        );
+
+        let mut moved = calendar();
+        moved.events[0].occurrences[1].starts_at = at("2024-06-11 17:00");
+        assert_ne!(moved.feed_tag(), tag);
+    }
+
+    #[test]
+    fn long_lines_are_folded_between_characters() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold(&line);
+        assert!(folded
+            .split("\r\n")
+            .all(|part| part.len() <= MAX_LINE_OCTETS));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,432 @@
+use crate::errors::{AppErrorType, FieldError};
+use crate::models::topic::UpdateTopic;
+use crate::models::tutor::UpdateTutor;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogueFormat {
+    Csv,
+    Ndjson,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    // Nothing is written unless every row succeeds
+    #[default]
+    Atomic,
+    // Rejected rows are skipped and the rest are written
+    BestEffort,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordKind {
+    Tutor,
+    Topic,
+}
+
+// One CSV row or NDJSON line. Tutors are identified by `email`; a topic's `email` is its
+// tutor's, and within that tutor it is identified by `title`. Every format has the same
+// columns so an export can be edited and imported again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct CatalogueRecord {
+    pub kind: RecordKind,
+    pub email: String,
+    #[serde(default)]
+    pub first_name: Option<String>,
+    #[serde(default)]
+    pub last_name: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub topic_description: Option<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub duration: Option<String>,
+    #[serde(default)]
+    pub topic_level: Option<String>,
+    #[serde(default)]
+    pub capacity: Option<i32>,
+}
+
+#[derive(Deserialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    pub format: CatalogueFormat,
+}
+
+#[derive(Deserialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportParams {
+    pub format: CatalogueFormat,
+    #[serde(default)]
+    pub mode: ImportMode,
+}
+
+// A record that passed validation, ready for the repository
+#[derive(Debug, Clone)]
+pub enum ImportItem {
+    Tutor(UpdateTutor),
+    Topic {
+        tutor_email: String,
+        topic: UpdateTopic,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportRow {
+    // Line in the uploaded file, counting the CSV header
+    pub line: usize,
+    pub kind: Option<RecordKind>,
+    pub key: String,
+    pub item: Result<ImportItem, Vec<FieldError>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportOutcome {
+    Created,
+    Updated,
+    // The row matched an existing record exactly
+    Skipped,
+    Rejected,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ImportRowReport {
+    pub line: usize,
+    // Absent when the row could not be parsed
+    pub kind: Option<RecordKind>,
+    // The tutor's email, or `email/title` for a topic
+    pub key: String,
+    pub outcome: ImportOutcome,
+    pub id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+// With `atomic` and a rejected row nothing is committed, and the other rows report what
+// would have happened
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub committed: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub rejected: usize,
+    pub rows: Vec<ImportRowReport>,
+}
+
+impl ImportMode {
+    pub fn commits(self, rows: &[ImportRowReport]) -> bool {
+        self == ImportMode::BestEffort
+            || rows
+                .iter()
+                .all(|row| row.outcome != ImportOutcome::Rejected)
+    }
+}
+
+impl ImportRow {
+    pub fn report(&self, result: Result<(ImportOutcome, i32), Vec<FieldError>>) -> ImportRowReport {
+        let (outcome, id, errors) = match result {
+            Ok((outcome, id)) => (outcome, Some(id), Vec::new()),
+            Err(errors) => (ImportOutcome::Rejected, None, errors),
+        };
+        ImportRowReport {
+            line: self.line,
+            kind: self.kind,
+            key: self.key.clone(),
+            outcome,
+            id,
+            errors,
+        }
+    }
+}
+
+impl ImportReport {
+    pub fn new(mode: ImportMode, committed: bool, rows: Vec<ImportRowReport>) -> Self {
+        let count = |outcome| rows.iter().filter(|row| row.outcome == outcome).count();
+        ImportReport {
+            mode,
+            committed,
+            created: count(ImportOutcome::Created),
+            updated: count(ImportOutcome::Updated),
+            skipped: count(ImportOutcome::Skipped),
+            rejected: count(ImportOutcome::Rejected),
+            rows,
+        }
+    }
+}
+
+// A row-level failure in the report, in the same shape as the validation errors
+pub fn row_errors(error: AppErrorType) -> Vec<FieldError> {
+    match error {
+        AppErrorType::ValidationError(errors) => errors,
+        error => {
+            let problem = error.problem();
+            vec![FieldError {
+                field: "row".into(),
+                code: problem.code,
+                message: problem.detail,
+            }]
+        }
+    }
+}
+
+fn required(field: &str, kind: &str) -> FieldError {
+    FieldError {
+        field: field.into(),
+        code: "required".into(),
+        message: format!("is required for {} rows", kind),
+    }
+}
+
+impl CatalogueRecord {
+    fn key(&self) -> String {
+        match (self.kind, &self.title) {
+            (RecordKind::Topic, Some(title)) => format!("{}/{}", self.email, title),
+            _ => self.email.clone(),
+        }
+    }
+
+    // The same rules as `PUT`: a tutor row needs every tutor column, and a topic row
+    // replaces every optional topic field, so blank cells clear them
+    fn into_item(self) -> Result<ImportItem, Vec<FieldError>> {
+        match self.kind {
+            RecordKind::Tutor => {
+                let (Some(first_name), Some(last_name)) =
+                    (self.first_name.clone(), self.last_name.clone())
+                else {
+                    let mut errors = Vec::new();
+                    if self.first_name.is_none() {
+                        errors.push(required("first_name", "tutor"));
+                    }
+                    if self.last_name.is_none() {
+                        errors.push(required("last_name", "tutor"));
+                    }
+                    return Err(errors);
+                };
+                let tutor = UpdateTutor {
+                    first_name,
+                    last_name,
+                    email: self.email,
+                };
+                tutor.validate().map_err(|err| row_errors(err.into()))?;
+                Ok(ImportItem::Tutor(tutor))
+            }
+            RecordKind::Topic => {
+                let Some(title) = self.title else {
+                    return Err(vec![required("title", "topic")]);
+                };
+                let topic = UpdateTopic {
+                    title,
+                    topic_description: self.topic_description,
+                    format: self.format,
+                    duration: self.duration,
+                    topic_level: self.topic_level,
+                    capacity: self.capacity,
+                };
+                topic.validate().map_err(|err| row_errors(err.into()))?;
+                Ok(ImportItem::Topic {
+                    tutor_email: self.email,
+                    topic,
+                })
+            }
+        }
+    }
+}
+
+fn parsed_row(line: usize, record: Result<CatalogueRecord, String>) -> ImportRow {
+    match record {
+        Ok(record) => ImportRow {
+            line,
+            kind: Some(record.kind),
+            key: record.key(),
+            item: record.into_item(),
+        },
+        Err(message) => ImportRow {
+            line,
+            kind: None,
+            key: String::new(),
+            item: Err(vec![FieldError {
+                field: "row".into(),
+                code: "malformed".into(),
+                message,
+            }]),
+        },
+    }
+}
+
+// Every row of an upload, each either validated or with the reasons it was rejected. Only
+// a file that cannot be read at all is an error.
+pub fn parse_import(format: CatalogueFormat, body: &[u8]) -> Result<Vec<ImportRow>, AppErrorType> {
+    let body = std::str::from_utf8(body)
+        .map_err(|_err| AppErrorType::InvalidInput("The upload must be UTF-8 text".into()))?;
+
+    let rows: Vec<ImportRow> = match format {
+        CatalogueFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(body.as_bytes());
+            let headers = reader
+                .headers()
+                .map_err(|err| {
+                    AppErrorType::InvalidInput(format!("Unreadable CSV header: {}", err))
+                })?
+                .clone();
+            reader
+                .records()
+                .enumerate()
+                .map(|(index, record)| {
+                    let line = record
+                        .as_ref()
+                        .ok()
+                        .and_then(|record| record.position())
+                        .map_or(index + 2, |position| position.line() as usize);
+                    let record = record.and_then(|record| record.deserialize(Some(&headers)));
+                    parsed_row(line, record.map_err(|err| err.to_string()))
+                })
+                .collect()
+        }
+        CatalogueFormat::Ndjson => body
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                let record = serde_json::from_str(line).map_err(|err| err.to_string());
+                parsed_row(index + 1, record)
+            })
+            .collect(),
+    };
+
+    if rows.is_empty() {
+        return Err(AppErrorType::InvalidInput(
+            "The upload contains no rows".into(),
+        ));
+    }
+    Ok(rows)
+}
+
+pub fn render_export(
+    format: CatalogueFormat,
+    records: &[CatalogueRecord],
+) -> Result<Vec<u8>, AppErrorType> {
+    match format {
+        CatalogueFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for record in records {
+                writer
+                    .serialize(record)
+                    .map_err(|err| AppErrorType::ActixError(err.to_string()))?;
+            }
+            writer
+                .into_inner()
+                .map_err(|err| AppErrorType::ActixError(err.to_string()))
+        }
+        CatalogueFormat::Ndjson => {
+            let mut body = Vec::new();
+            for record in records {
+                serde_json::to_writer(&mut body, record)
+                    .map_err(|err| AppErrorType::ActixError(err.to_string()))?;
+                body.push(b'\n');
+            }
+            Ok(body)
+        }
+    }
+}
+
+impl CatalogueFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            CatalogueFormat::Csv => "text/csv; charset=utf-8",
+            CatalogueFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            CatalogueFormat::Csv => "csv",
+            CatalogueFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_rows_are_validated_one_by_one() {
+        let body = "kind,email,first_name,last_name,title,topic_level\n\
+            tutor,ann@fakemail.com,Ann,Lee,,\n\
+            tutor,not-an-email,Ann,Lee,,\n\
+            topic,ann@fakemail.com,,,Macros,Expert\n\
+            topic,ann@fakemail.com,,,,\n\
+            lesson,ann@fakemail.com,,,,\n";
+        let rows = parse_import(CatalogueFormat::Csv, body.as_bytes()).unwrap();
+
+        assert_eq!(rows.len(), 5);
+        assert!(matches!(rows[0].item, Ok(ImportItem::Tutor(_))));
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[1].item.as_ref().unwrap_err()[0].field, "email");
+        assert_eq!(rows[2].key, "ann@fakemail.com/Macros");
+        assert_eq!(rows[2].item.as_ref().unwrap_err()[0].field, "topic_level");
+        assert_eq!(rows[3].item.as_ref().unwrap_err()[0].code, "required");
+        assert_eq!(rows[4].kind, None);
+        assert_eq!(rows[4].item.as_ref().unwrap_err()[0].code, "malformed");
+    }
+
+    #[test]
+    fn export_round_trips_through_both_formats() {
+        let records = vec![
+            CatalogueRecord {
+                kind: RecordKind::Tutor,
+                email: "ann@fakemail.com".into(),
+                first_name: Some("Ann".into()),
+                last_name: Some("Lee, Jr.".into()),
+                title: None,
+                topic_description: None,
+                format: None,
+                duration: None,
+                topic_level: None,
+                capacity: None,
+            },
+            CatalogueRecord {
+                kind: RecordKind::Topic,
+                email: "ann@fakemail.com".into(),
+                first_name: None,
+                last_name: None,
+                title: Some("Macros".into()),
+                topic_description: Some("Declarative\nand procedural".into()),
+                format: Some("video".into()),
+                duration: None,
+                topic_level: Some("Advanced".into()),
+                capacity: Some(12),
+            },
+        ];
+
+        for format in [CatalogueFormat::Csv, CatalogueFormat::Ndjson] {
+            let body = render_export(format, &records).unwrap();
+            let rows = parse_import(format, &body).unwrap();
+            assert_eq!(rows.len(), 2);
+            match &rows[1].item {
+                Ok(ImportItem::Topic { tutor_email, topic }) => {
+                    assert_eq!(tutor_email, "ann@fakemail.com");
+                    assert_eq!(
+                        topic.topic_description.as_deref(),
+                        Some("Declarative\nand procedural")
+                    );
+                    assert_eq!(topic.duration, None);
+                    assert_eq!(topic.capacity, Some(12));
+                }
+                other => panic!("unexpected row {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct LivenessReport {
+    pub status: HealthStatus,
+}
+
+// Returned by /health/ready; `status` is degraded as soon as any check is
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ReadinessReport {
+    pub status: HealthStatus,
+    pub checks: ReadinessChecks,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ReadinessChecks {
+    pub database: DatabaseHealth,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct DatabaseHealth {
+    pub status: HealthStatus,
+    pub backend: String,
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub pool: Option<PoolStats>,
+    pub migration_version: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+}
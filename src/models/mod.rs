@@ -1,2 +1,14 @@
+pub mod auth;
+pub mod availability;
+pub mod calendar;
+pub mod catalogue;
+pub mod health;
+pub mod page;
+pub mod patch;
+pub mod review;
+pub mod session;
+pub mod student;
 pub mod topic;
+pub mod trash;
 pub mod tutor;
+pub mod validation;
@@ -0,0 +1,331 @@
+use crate::errors::AppErrorType;
+use actix_web::{http::header, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 20;
+pub const MAX_PAGE_LIMIT: i64 = 100;
+
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    /// Page size, 1 to 100 (default 20)
+    pub limit: Option<i64>,
+    /// Rows to skip; cannot be combined with `cursor`
+    pub offset: Option<i64>,
+    /// Opaque `next_cursor` from a previous page
+    pub cursor: Option<String>,
+    /// Also count every matching row
+    pub include_total: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFormat {
+    // One JSON array
+    Json,
+    // One JSON object per line
+    Ndjson,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StreamParams {
+    /// Stream every matching row instead of returning a page
+    pub stream: Option<StreamFormat>,
+}
+
+// Either offset paging (`after_id` is None) or keyset paging continuing after `after_id`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRequest {
+    pub limit: i64,
+    pub offset: i64,
+    pub after_id: Option<i32>,
+    pub include_total: bool,
+}
+
+// A column from a listing's allow-list, so it is safe to splice into ORDER BY
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub column: &'static str,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cursor {
+    AfterId(i32),
+    Offset(i64),
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub limit: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+impl PageParams {
+    pub fn into_page_request(self) -> Result<PageRequest, AppErrorType> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+            return Err(AppErrorType::InvalidInput(format!(
+                "limit must be between 1 and {}",
+                MAX_PAGE_LIMIT
+            )));
+        }
+
+        let cursor = match (&self.cursor, self.offset) {
+            (Some(_), Some(_)) => {
+                return Err(AppErrorType::InvalidInput(
+                    "offset and cursor cannot be combined".into(),
+                ))
+            }
+            (Some(cursor), None) => Some(decode_cursor(cursor)?),
+            (None, Some(offset)) if offset < 0 => {
+                return Err(AppErrorType::InvalidInput(
+                    "offset must not be negative".into(),
+                ))
+            }
+            (None, _) => None,
+        };
+        let (offset, after_id) = match cursor {
+            Some(Cursor::AfterId(id)) => (0, Some(id)),
+            Some(Cursor::Offset(offset)) => (offset, None),
+            None => (self.offset.unwrap_or(0), None),
+        };
+
+        Ok(PageRequest {
+            limit,
+            offset,
+            after_id,
+            include_total: self.include_total.unwrap_or(false),
+        })
+    }
+}
+
+impl StreamParams {
+    // A stream is the whole listing, so paging parameters would be silently ignored
+    pub fn format(&self, page: &PageParams) -> Result<Option<StreamFormat>, AppErrorType> {
+        let paged = page.limit.is_some()
+            || page.offset.is_some()
+            || page.cursor.is_some()
+            || page.include_total.is_some();
+        if self.stream.is_some() && paged {
+            return Err(AppErrorType::InvalidInput(
+                "stream cannot be combined with limit, offset, cursor or include_total".into(),
+            ));
+        }
+        Ok(self.stream)
+    }
+}
+
+impl PageRequest {
+    // Rows to fetch so that one extra row tells us whether another page exists
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    // Keyset paging needs rows ordered by id alone; any other sort pages by offset.
+    // Returns the id ordering (true = descending) when keyset paging applies.
+    pub fn keyset_order(&self, sort: &[SortKey]) -> Result<Option<bool>, AppErrorType> {
+        match sort {
+            [key] if key.column == "id" => Ok(Some(key.descending)),
+            _ if self.after_id.is_some() => Err(AppErrorType::InvalidInput(
+                "cursor does not match the requested sort".into(),
+            )),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl SortKey {
+    pub fn asc(column: &'static str) -> Self {
+        SortKey {
+            column,
+            descending: false,
+        }
+    }
+
+    pub fn desc(column: &'static str) -> Self {
+        SortKey {
+            column,
+            descending: true,
+        }
+    }
+}
+
+// Parses `sort=created_at,-title` against `allowed`; id is appended as a tiebreaker
+pub fn parse_sort(
+    spec: Option<&str>,
+    allowed: &[&'static str],
+    default: SortKey,
+) -> Result<Vec<SortKey>, AppErrorType> {
+    let mut keys = Vec::new();
+    for field in spec.unwrap_or_default().split(',').map(str::trim) {
+        if field.is_empty() {
+            continue;
+        }
+        let (name, descending) = match field.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (field.strip_prefix('+').unwrap_or(field), false),
+        };
+        let column = allowed
+            .iter()
+            .find(|column| **column == name)
+            .ok_or_else(|| AppErrorType::InvalidInput(format!("Cannot sort by '{}'", name)))?;
+        if keys.iter().any(|key: &SortKey| key.column == *column) {
+            continue;
+        }
+        keys.push(SortKey { column, descending });
+    }
+
+    if keys.is_empty() {
+        keys.push(default);
+    }
+    if !keys.iter().any(|key| key.column == "id") {
+        keys.push(SortKey::asc("id"));
+    }
+    Ok(keys)
+}
+
+pub fn order_by_sql(sort: &[SortKey]) -> String {
+    sort.iter()
+        .map(|key| {
+            format!(
+                "{} {}",
+                key.column,
+                if key.descending { "DESC" } else { "ASC" }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl<T> Page<T> {
+    // `rows` must be fetched with `PageRequest::fetch_limit` and in cursor order
+    pub fn from_rows(
+        mut rows: Vec<T>,
+        page: &PageRequest,
+        total: Option<i64>,
+        id_of: impl Fn(&T) -> i32,
+    ) -> Self {
+        let has_more = rows.len() as i64 > page.limit;
+        rows.truncate(page.limit as usize);
+        let next_cursor = match rows.last() {
+            Some(last) if has_more => Some(encode_cursor(Cursor::AfterId(id_of(last)))),
+            _ => None,
+        };
+
+        Page {
+            items: rows,
+            limit: page.limit,
+            offset: page.after_id.is_none().then_some(page.offset),
+            next_cursor,
+            total,
+        }
+    }
+
+    // Like `from_rows`, for listings sorted on something other than id
+    pub fn from_offset_rows(mut rows: Vec<T>, page: &PageRequest, total: Option<i64>) -> Self {
+        let has_more = rows.len() as i64 > page.limit;
+        rows.truncate(page.limit as usize);
+
+        Page {
+            items: rows,
+            limit: page.limit,
+            offset: Some(page.offset),
+            next_cursor: has_more.then(|| encode_cursor(Cursor::Offset(page.offset + page.limit))),
+            total,
+        }
+    }
+
+    // JSON envelope plus RFC 8288 `Link` header pointing at the neighbouring pages
+    pub fn into_response(self, req: &HttpRequest) -> HttpResponse
+    where
+        T: Serialize,
+    {
+        let mut response = HttpResponse::Ok();
+        if let Some(links) = self.link_header(req) {
+            response.insert_header((header::LINK, links));
+        }
+        response.json(self)
+    }
+
+    // For responses that carry a page inside a larger body
+    pub fn link_header(&self, req: &HttpRequest) -> Option<String> {
+        let mut links = Vec::new();
+        match self.offset {
+            Some(offset) => {
+                if self.next_cursor.is_some() {
+                    links.push(page_link(
+                        req,
+                        "offset",
+                        &(offset + self.limit).to_string(),
+                        "next",
+                    ));
+                }
+                if offset > 0 {
+                    let prev = (offset - self.limit).max(0).to_string();
+                    links.push(page_link(req, "offset", &prev, "prev"));
+                }
+            }
+            None => {
+                if let Some(cursor) = &self.next_cursor {
+                    links.push(page_link(req, "cursor", cursor, "next"));
+                }
+            }
+        }
+
+        (!links.is_empty()).then(|| links.join(", "))
+    }
+}
+
+fn page_link(req: &HttpRequest, key: &str, value: &str, rel: &str) -> String {
+    // Keep every other query parameter (limit, filters...) as the client sent it
+    let mut query: Vec<&str> = req
+        .query_string()
+        .split('&')
+        .filter(|pair| {
+            let name = pair.split('=').next().unwrap_or_default();
+            !pair.is_empty() && name != "offset" && name != "cursor"
+        })
+        .collect();
+    let position = format!("{}={}", key, value);
+    query.push(&position);
+
+    format!("<{}?{}>; rel=\"{}\"", req.path(), query.join("&"), rel)
+}
+
+fn encode_cursor(cursor: Cursor) -> String {
+    let raw = match cursor {
+        Cursor::AfterId(id) => format!("id:{}", id),
+        Cursor::Offset(offset) => format!("offset:{}", offset),
+    };
+    URL_SAFE_NO_PAD.encode(raw)
+}
+
+fn decode_cursor(cursor: &str) -> Result<Cursor, AppErrorType> {
+    let decoded = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or_default();
+
+    match decoded.split_once(':') {
+        Some(("id", id)) => id.parse().ok().map(Cursor::AfterId),
+        Some(("offset", offset)) => offset
+            .parse()
+            .ok()
+            .filter(|offset| *offset >= 0)
+            .map(Cursor::Offset),
+        _ => None,
+    }
+    .ok_or_else(|| AppErrorType::InvalidInput("Invalid cursor".into()))
+}
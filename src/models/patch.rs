@@ -0,0 +1,13 @@
+use serde::{Deserialize, Deserializer};
+
+// For `#[serde(default, deserialize_with = "present")]` on fields of an RFC 7396 merge patch:
+// a field left out of the document stays `None`, while one that was sent becomes `Some`.
+// On an `Option<Option<T>>` field that keeps an explicit `null` apart from an absent field;
+// on an `Option<T>` field it rejects `null`, for columns that cannot be cleared.
+pub fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
@@ -0,0 +1,85 @@
+use crate::errors::AppErrorType;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+// A student's review of a topic. Each student has at most one per topic.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Review {
+    pub id: i32,
+    pub topic_id: i32,
+    pub student_id: i32,
+    // 1 to 5 stars
+    pub rating: i16,
+    pub body: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+// The body of `PUT /topics/{topic_id}/review`, which creates or replaces the calling
+// student's review
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct WriteReview {
+    #[validate(range(min = 1, max = 5, message = "must be between 1 and 5"))]
+    pub rating: i16,
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
+    pub body: Option<String>,
+}
+
+// How the running totals on a topic and its tutor move when one review is written or removed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RatingChange {
+    pub rating_sum: i32,
+    pub review_count: i32,
+}
+
+impl RatingChange {
+    // From the review's previous rating, if it had one, to its new one
+    pub fn between(old: Option<i16>, new: Option<i16>) -> Self {
+        let value = |rating: Option<i16>| rating.map_or(0, i32::from);
+        let count = |rating: Option<i16>| rating.is_some() as i32;
+        RatingChange {
+            rating_sum: value(new) - value(old),
+            review_count: count(new) - count(old),
+        }
+    }
+}
+
+// Only a student enrolled in a topic may review it
+pub fn check_enrolled(enrolled: bool) -> Result<(), AppErrorType> {
+    if enrolled {
+        Ok(())
+    } else {
+        Err(AppErrorType::ForbiddenError(
+            "Only a student enrolled in the topic may review it".into(),
+        ))
+    }
+}
+
+// None until there is a review to average
+pub fn average_rating(rating_sum: i64, review_count: i64) -> Option<f64> {
+    (review_count > 0).then(|| rating_sum as f64 / review_count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rating_changes_track_the_running_totals() {
+        let change = |old, new| {
+            let RatingChange {
+                rating_sum,
+                review_count,
+            } = RatingChange::between(old, new);
+            (rating_sum, review_count)
+        };
+        assert_eq!(change(None, Some(4)), (4, 1));
+        assert_eq!(change(Some(4), Some(2)), (-2, 0));
+        assert_eq!(change(Some(2), None), (-2, -1));
+
+        assert_eq!(average_rating(0, 0), None);
+        assert_eq!(average_rating(9, 2), Some(4.5));
+    }
+}
@@ -0,0 +1,537 @@
+use crate::errors::{AppErrorType, FieldError};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime,
+    Offset, TimeZone, Weekday,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+// Every occurrence is stored so the database can check it for overlaps, so a recurrence
+// must end, and end within this many occurrences
+pub const MAX_OCCURRENCES: usize = 500;
+pub const MAX_OCCURRENCE_RANGE_DAYS: i64 = 366;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct Session {
+    pub id: i32,
+    pub topic_id: i32,
+    pub tutor_id: i32,
+    // Wall-clock times in `time_zone`
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub time_zone: String,
+    pub recurrence: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+// The body of both `POST` and `PUT`; a `PUT` moves every occurrence of the session
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct SessionSchedule {
+    /// Start of the first occurrence, as a wall-clock time in `time_zone`
+    pub starts_at: NaiveDateTime,
+    /// End of the first occurrence; every occurrence lasts as long
+    pub ends_at: NaiveDateTime,
+    /// IANA time zone name, such as `Europe/London`
+    #[validate(custom(function = "known_time_zone"))]
+    pub time_zone: String,
+    /// RRULE with FREQ=DAILY, WEEKLY or MONTHLY, optional INTERVAL and BYDAY, and COUNT or UNTIL
+    #[validate(
+        length(max = 200, message = "must be at most 200 characters"),
+        custom(function = "valid_recurrence")
+    )]
+    pub recurrence: Option<String>,
+}
+
+// One occurrence of a session, with times in the session's time zone
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Occurrence {
+    pub session_id: i32,
+    pub topic_id: i32,
+    pub starts_at: DateTime<FixedOffset>,
+    pub ends_at: DateTime<FixedOffset>,
+}
+
+// A UTC interval, as stored in `session_occurrence`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Interval {
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DateRange {
+    /// First day to include, in UTC
+    pub from: NaiveDate,
+    /// Last day to include, in UTC; the range can cover at most 366 days
+    pub to: NaiveDate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RecurrenceEnd {
+    Count(usize),
+    // Compared with each occurrence's start, in UTC or as a wall-clock time
+    UntilUtc(NaiveDateTime),
+    UntilLocal(NaiveDateTime),
+}
+
+// The subset of an RFC 5545 RRULE that sessions support
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    by_day: Vec<Weekday>,
+    end: RecurrenceEnd,
+}
+
+pub(crate) fn known_time_zone(time_zone: &str) -> Result<(), ValidationError> {
+    Tz::from_str(time_zone).map(|_tz| ()).map_err(|_err| {
+        ValidationError::new("time_zone").with_message("must be an IANA time zone name".into())
+    })
+}
+
+fn valid_recurrence(recurrence: &str) -> Result<(), ValidationError> {
+    Recurrence::from_str(recurrence)
+        .map(|_recurrence| ())
+        .map_err(|message| ValidationError::new("rrule").with_message(message.into()))
+}
+
+pub(crate) fn invalid(field: &str, code: &str, message: &str) -> AppErrorType {
+    AppErrorType::ValidationError(vec![FieldError {
+        field: field.into(),
+        code: code.into(),
+        message: message.into(),
+    }])
+}
+
+impl DateRange {
+    // The requested days as a half-open UTC interval
+    pub fn interval(&self) -> Result<Interval, AppErrorType> {
+        Interval::days(self.from, self.to, MAX_OCCURRENCE_RANGE_DAYS)
+    }
+}
+
+impl Interval {
+    // The UTC days `from` to `to`, both included
+    pub fn days(from: NaiveDate, to: NaiveDate, max_days: i64) -> Result<Interval, AppErrorType> {
+        let days = (to - from).num_days();
+        if !(0..max_days).contains(&days) {
+            return Err(AppErrorType::InvalidInput(format!(
+                "to must be on or after from, and the range can cover at most {} days",
+                max_days
+            )));
+        }
+        let end = to.checked_add_signed(Duration::days(1)).ok_or_else(|| {
+            AppErrorType::InvalidInput("to must be before the last supported date".into())
+        })?;
+        Ok(Interval {
+            starts_at: from.and_time(NaiveTime::MIN),
+            ends_at: end.and_time(NaiveTime::MIN),
+        })
+    }
+
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.starts_at < other.ends_at && other.starts_at < self.ends_at
+    }
+}
+
+impl SessionSchedule {
+    // Every occurrence in UTC, in order. Errors are reported like failed validation.
+    pub fn occurrences(&self) -> Result<Vec<Interval>, AppErrorType> {
+        if self.ends_at <= self.starts_at {
+            return Err(invalid("ends_at", "range", "must be after starts_at"));
+        }
+        let tz = Tz::from_str(&self.time_zone)
+            .map_err(|_err| invalid("time_zone", "time_zone", "must be an IANA time zone name"))?;
+        let starts = match &self.recurrence {
+            None => vec![self.starts_at],
+            Some(recurrence) => Recurrence::from_str(recurrence)
+                .map_err(|message| invalid("recurrence", "rrule", &message))?
+                .local_starts(tz, self.starts_at)?,
+        };
+        if starts.is_empty() {
+            return Err(invalid(
+                "recurrence",
+                "rrule",
+                "UNTIL is before the first occurrence",
+            ));
+        }
+
+        // Occurrences keep their wall-clock start across DST changes, and their length in
+        // absolute time
+        let length = self.ends_at - self.starts_at;
+        let occurrences: Vec<Interval> = starts
+            .into_iter()
+            .map(|local| {
+                let starts_at = to_utc(tz, local);
+                let ends_at = starts_at
+                    .checked_add_signed(length)
+                    .ok_or_else(|| past_the_calendar("ends_at", "range"))?;
+                Ok(Interval { starts_at, ends_at })
+            })
+            .collect::<Result<_, AppErrorType>>()?;
+        if occurrences
+            .windows(2)
+            .any(|pair| pair[0].overlaps(&pair[1]))
+        {
+            return Err(invalid(
+                "ends_at",
+                "range",
+                "must be before the next occurrence starts",
+            ));
+        }
+        Ok(occurrences)
+    }
+}
+
+impl Session {
+    pub fn occurrence(&self, interval: Interval) -> Occurrence {
+        let tz = Tz::from_str(&self.time_zone).unwrap_or(Tz::UTC);
+        Occurrence {
+            session_id: self.id,
+            topic_id: self.topic_id,
+            starts_at: in_zone(tz, interval.starts_at),
+            ends_at: in_zone(tz, interval.ends_at),
+        }
+    }
+}
+
+// Ambiguous times take the earlier offset; times skipped by a DST change keep the offset
+// from before the change, so they move forward by the gap, as RFC 5545 specifies
+pub(crate) fn to_utc(tz: Tz, local: NaiveDateTime) -> NaiveDateTime {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.naive_utc(),
+        LocalResult::None => {
+            let day_before = local.checked_sub_signed(Duration::days(1)).unwrap_or(local);
+            let before = tz.offset_from_utc_datetime(&day_before).fix();
+            local
+                .checked_sub_signed(Duration::seconds(before.local_minus_utc() as i64))
+                .unwrap_or(local)
+        }
+    }
+}
+
+// For dates the arithmetic would carry past `NaiveDate::MAX`
+fn past_the_calendar(field: &str, code: &str) -> AppErrorType {
+    invalid(field, code, "must end before the last supported date")
+}
+
+pub(crate) fn in_zone(tz: Tz, utc: NaiveDateTime) -> DateTime<FixedOffset> {
+    tz.from_utc_datetime(&utc).fixed_offset()
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut end = None;
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("{} is not NAME=VALUE", part))?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err("FREQ must be DAILY, WEEKLY or MONTHLY".into()),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| (1..=1000).contains(interval))
+                        .ok_or("INTERVAL must be a number from 1 to 1000")?
+                }
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or("BYDAY must list days as MO, TU, WE, TH, FR, SA or SU")?
+                }
+                "COUNT" if end.is_none() => {
+                    end = Some(RecurrenceEnd::Count(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|count| (1..=MAX_OCCURRENCES).contains(count))
+                            .ok_or_else(|| {
+                                format!("COUNT must be a number from 1 to {}", MAX_OCCURRENCES)
+                            })?,
+                    ))
+                }
+                "UNTIL" if end.is_none() => end = Some(parse_until(value)?),
+                "COUNT" | "UNTIL" => return Err("COUNT and UNTIL cannot be combined".into()),
+                _ => return Err(format!("{} is not supported", name)),
+            }
+        }
+
+        let frequency = frequency.ok_or("FREQ is required")?;
+        if !by_day.is_empty() && frequency != Frequency::Weekly {
+            return Err("BYDAY is only supported with FREQ=WEEKLY".into());
+        }
+        by_day.sort_by_key(|day: &Weekday| day.num_days_from_monday());
+        by_day.dedup();
+        Ok(Recurrence {
+            frequency,
+            interval,
+            by_day,
+            end: end.ok_or("COUNT or UNTIL is required")?,
+        })
+    }
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    Some(match day.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+// `20240630`, `20240630T170000` (wall-clock) or `20240630T170000Z` (UTC). A date includes
+// every occurrence starting that day.
+fn parse_until(value: &str) -> Result<RecurrenceEnd, String> {
+    let malformed = || "UNTIL must be a date like 20240630 or a time like 20240630T170000Z".into();
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map(RecurrenceEnd::UntilUtc)
+            .map_err(|_err| malformed());
+    }
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .or_else(|_err| {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(|date| date.and_hms_opt(23, 59, 59).unwrap())
+        })
+        .map(RecurrenceEnd::UntilLocal)
+        .map_err(|_err| malformed())
+}
+
+impl Recurrence {
+    // Wall-clock starts from `first` onwards, in order
+    fn local_starts(
+        &self,
+        tz: Tz,
+        first: NaiveDateTime,
+    ) -> Result<Vec<NaiveDateTime>, AppErrorType> {
+        let mut starts = Vec::new();
+        // Bounds the loop for rules that skip most periods, such as the 31st of every month
+        for period in 0..(MAX_OCCURRENCES as i64 * 12) {
+            for start in self.period_starts(first, period)? {
+                if start < first {
+                    continue;
+                }
+                match self.end {
+                    RecurrenceEnd::Count(count) if starts.len() >= count => return Ok(starts),
+                    RecurrenceEnd::UntilLocal(until) if start > until => return Ok(starts),
+                    RecurrenceEnd::UntilUtc(until) if to_utc(tz, start) > until => {
+                        return Ok(starts)
+                    }
+                    _ => {}
+                }
+                if starts.len() == MAX_OCCURRENCES {
+                    return Err(invalid(
+                        "recurrence",
+                        "rrule",
+                        &format!("must have at most {} occurrences", MAX_OCCURRENCES),
+                    ));
+                }
+                starts.push(start);
+            }
+        }
+        Ok(starts)
+    }
+
+    // Candidate starts in the `period`th day, week or month after `first`'s
+    fn period_starts(
+        &self,
+        first: NaiveDateTime,
+        period: i64,
+    ) -> Result<Vec<NaiveDateTime>, AppErrorType> {
+        let step = period * self.interval as i64;
+        let days_after = |days: i64| {
+            first
+                .checked_add_signed(Duration::days(days))
+                .ok_or_else(|| past_the_calendar("recurrence", "rrule"))
+        };
+        Ok(match self.frequency {
+            Frequency::Daily => vec![days_after(step)?],
+            Frequency::Weekly if self.by_day.is_empty() => vec![days_after(step * 7)?],
+            Frequency::Weekly => {
+                let monday = step * 7 - first.weekday().num_days_from_monday() as i64;
+                self.by_day
+                    .iter()
+                    .map(|day| days_after(monday + day.num_days_from_monday() as i64))
+                    .collect::<Result<_, _>>()?
+            }
+            // Months without the start's day of the month are skipped
+            Frequency::Monthly => {
+                let month = first.month0() as i64 + step;
+                let (year, month) = (
+                    first.year() as i64 + month.div_euclid(12),
+                    month.rem_euclid(12) as u32 + 1,
+                );
+                let year = i32::try_from(year)
+                    .ok()
+                    .filter(|year| NaiveDate::from_ymd_opt(*year, month, 1).is_some())
+                    .ok_or_else(|| past_the_calendar("recurrence", "rrule"))?;
+                NaiveDate::from_ymd_opt(year, month, first.day())
+                    .map(|date| date.and_time(first.time()))
+                    .into_iter()
+                    .collect()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn schedule(starts_at: &str, recurrence: Option<&str>) -> SessionSchedule {
+        let starts_at = at(starts_at);
+        SessionSchedule {
+            starts_at,
+            ends_at: starts_at + Duration::minutes(90),
+            time_zone: "Europe/London".into(),
+            recurrence: recurrence.map(String::from),
+        }
+    }
+
+    #[test]
+    fn schedules_past_the_last_supported_date_are_rejected() {
+        let far_future = (NaiveDate::MAX - Duration::days(30))
+            .and_hms_opt(18, 0, 0)
+            .unwrap();
+        for rule in [
+            "FREQ=DAILY;INTERVAL=1000;COUNT=3",
+            "FREQ=WEEKLY;INTERVAL=1000;COUNT=3",
+            "FREQ=WEEKLY;BYDAY=MO,FR;INTERVAL=1000;COUNT=3",
+            "FREQ=MONTHLY;INTERVAL=1000;COUNT=3",
+        ] {
+            let schedule = SessionSchedule {
+                starts_at: far_future,
+                ends_at: far_future + Duration::minutes(90),
+                time_zone: "Europe/London".into(),
+                recurrence: Some(rule.into()),
+            };
+            let err = schedule.occurrences().unwrap_err();
+            assert_eq!(
+                err.status_code(),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{}",
+                rule
+            );
+        }
+
+        let err = Interval::days(NaiveDate::MAX, NaiveDate::MAX, 7).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn weekly_occurrences_keep_their_wall_clock_time_across_dst() {
+        // Mondays and Wednesdays at 18:00 London time; the clocks go forward on 31 March 2024
+        let occurrences = schedule("2024-03-25 18:00", Some("FREQ=WEEKLY;BYDAY=WE,MO;COUNT=4"))
+            .occurrences()
+            .unwrap();
+        let starts: Vec<NaiveDateTime> = occurrences.iter().map(|o| o.starts_at).collect();
+        assert_eq!(
+            starts,
+            [
+                at("2024-03-25 18:00"),
+                at("2024-03-27 18:00"),
+                at("2024-04-01 17:00"),
+                at("2024-04-03 17:00"),
+            ]
+        );
+        assert_eq!(occurrences[0].ends_at, at("2024-03-25 19:30"));
+    }
+
+    #[test]
+    fn recurrences_end_with_until_or_count() {
+        let starts = |rule: &str| -> Vec<NaiveDateTime> {
+            schedule("2024-01-31 09:00", Some(rule))
+                .occurrences()
+                .unwrap()
+                .into_iter()
+                .map(|o| o.starts_at)
+                .collect()
+        };
+        assert_eq!(starts("FREQ=DAILY;INTERVAL=2;UNTIL=20240204").len(), 3);
+        // Months without a 31st are skipped
+        assert_eq!(
+            starts("FREQ=MONTHLY;COUNT=3"),
+            [
+                at("2024-01-31 09:00"),
+                at("2024-03-31 08:00"),
+                at("2024-05-31 08:00")
+            ]
+        );
+    }
+
+    #[test]
+    fn unbounded_or_unsupported_rules_are_rejected() {
+        for rule in [
+            "FREQ=WEEKLY",
+            "FREQ=YEARLY;COUNT=2",
+            "FREQ=DAILY;COUNT=2;UNTIL=20240101",
+            "FREQ=DAILY;BYDAY=MO;COUNT=2",
+            "FREQ=DAILY;COUNT=501",
+            "FREQ=DAILY;BYHOUR=9;COUNT=2",
+        ] {
+            assert!(Recurrence::from_str(rule).is_err(), "{}", rule);
+        }
+        let err = schedule("2024-01-01 09:00", Some("FREQ=DAILY;UNTIL=20260101"))
+            .occurrences()
+            .unwrap_err();
+        assert!(matches!(err, AppErrorType::ValidationError(_)));
+
+        let mut overlapping = schedule("2024-01-01 09:00", Some("FREQ=DAILY;COUNT=2"));
+        overlapping.ends_at = at("2024-01-02 10:00");
+        assert!(overlapping.occurrences().is_err());
+    }
+
+    #[test]
+    fn occurrence_range_is_inclusive_and_bounded() {
+        let params = DateRange {
+            from: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            to: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+        };
+        assert_eq!(params.interval().unwrap().ends_at, at("2024-01-02 00:00"));
+        let params = DateRange {
+            from: NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(),
+            to: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+        };
+        assert!(params.interval().is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::errors::AppErrorType;
+use crate::models::page::{Page, SortKey};
+use crate::models::topic::Topic;
+use crate::models::validation::not_blank;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct Student {
+    pub id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct CreateStudent {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub first_name: String,
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub last_name: String,
+    #[validate(
+        email(message = "must be a valid email address"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub email: String,
+}
+
+// A student signing themselves up, the only way a student gets a password
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct RegisterStudent {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub first_name: String,
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub last_name: String,
+    #[validate(
+        email(message = "must be a valid email address"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub email: String,
+    #[serde(skip_serializing)]
+    #[schema(write_only)]
+    #[validate(length(min = 8, max = 128, message = "must be between 8 and 128 characters"))]
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct NewEnrollment {
+    #[validate(range(min = 1, message = "must be a student id"))]
+    pub student_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Enrollment {
+    pub topic_id: i32,
+    pub student_id: i32,
+    pub enrolled_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct EnrolledStudent {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub student: Student,
+    pub enrolled_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct EnrolledTopic {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub topic: Topic,
+    pub enrolled_at: NaiveDateTime,
+}
+
+// A page of the students enrolled in a topic, in enrollment order. `enrolled` counts all of
+// them, and can exceed `capacity` when the capacity was lowered after students enrolled.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Roster {
+    pub topic_id: i32,
+    pub capacity: Option<i32>,
+    pub enrolled: i64,
+    pub students: Page<EnrolledStudent>,
+}
+
+impl From<RegisterStudent> for CreateStudent {
+    fn from(registration: RegisterStudent) -> Self {
+        CreateStudent {
+            first_name: registration.first_name,
+            last_name: registration.last_name,
+            email: registration.email,
+        }
+    }
+}
+
+// Enrollment order; not by id, so these listings page by offset
+pub fn enrollment_order() -> [SortKey; 2] {
+    [SortKey::asc("enrolled_at"), SortKey::asc("id")]
+}
+
+// Whether one more student fits in a topic that already has `enrolled`
+pub fn check_capacity(capacity: Option<i32>, enrolled: i64) -> Result<(), AppErrorType> {
+    match capacity {
+        Some(capacity) if enrolled >= capacity as i64 => Err(AppErrorType::ConflictError(format!(
+            "The topic is full: {} of {} places are taken",
+            enrolled, capacity
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_counts_every_enrolled_student() {
+        assert!(check_capacity(None, 1000).is_ok());
+        assert!(check_capacity(Some(2), 1).is_ok());
+        assert!(check_capacity(Some(2), 2).is_err());
+        // Lowering the capacity below the current count keeps everyone but admits no one
+        assert!(check_capacity(Some(1), 3).is_err());
+        assert!(check_capacity(Some(0), 0).is_err());
+    }
+}
@@ -1,8 +1,26 @@
+use crate::errors::AppErrorType;
+use crate::models::page::{parse_sort, SortKey};
+use crate::models::patch::present;
+use crate::models::validation::not_blank;
 use actix_web::web;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub const TOPIC_SORT_COLUMNS: &[&str] = &[
+    "id",
+    "tutor_id",
+    "title",
+    "format",
+    "topic_level",
+    "created_at",
+    "updated_at",
+];
+
+pub const TOPIC_LEVELS: &[&str] = &["Beginner", "Intermediate", "Advanced"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
 pub struct Topic {
     pub id: i32,
     pub tutor_id: i32,
@@ -11,27 +29,133 @@ pub struct Topic {
     pub format: Option<String>,
     pub duration: Option<String>,
     pub topic_level: Option<String>,
+    // Most students that can enroll; None is unlimited
+    pub capacity: Option<i32>,
+    // Mean rating of the topic's reviews; None until it has one
+    pub average_rating: Option<f64>,
+    pub review_count: i32,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    // Incremented by every update and served as the ETag
+    pub version: i32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
 pub struct CreateTopic {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 140, message = "must be at most 140 characters")
+    )]
     pub title: String,
+    #[validate(range(min = 1, message = "must be a positive id"))]
     pub tutor_id: i32,
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
     pub topic_description: Option<String>,
+    #[validate(length(max = 30, message = "must be at most 30 characters"))]
     pub format: Option<String>,
+    #[validate(length(max = 30, message = "must be at most 30 characters"))]
     pub duration: Option<String>,
+    #[validate(custom(function = "allowed_topic_level"))]
     pub topic_level: Option<String>,
+    #[validate(range(min = 0, message = "must not be negative"))]
+    pub capacity: Option<i32>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+// The full replacement sent with `PUT`; optional fields left out are cleared
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
 pub struct UpdateTopic {
-    pub title: Option<String>,
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 140, message = "must be at most 140 characters")
+    )]
+    pub title: String,
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
     pub topic_description: Option<String>,
+    #[validate(length(max = 30, message = "must be at most 30 characters"))]
     pub format: Option<String>,
+    #[validate(length(max = 30, message = "must be at most 30 characters"))]
     pub duration: Option<String>,
+    #[validate(custom(function = "allowed_topic_level"))]
+    pub topic_level: Option<String>,
+    #[validate(range(min = 0, message = "must not be negative"))]
+    pub capacity: Option<i32>,
+}
+
+// A JSON merge patch sent with `PATCH`: an absent field is kept, `null` clears it and a
+// value replaces it. `title` cannot be cleared, so it rejects `null`.
+#[derive(Deserialize, Debug, Clone, Default, Validate, ToSchema)]
+pub struct TopicPatch {
+    #[serde(default, deserialize_with = "present")]
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 140, message = "must be at most 140 characters")
+    )]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(length(max = 2000, message = "must be at most 2000 characters"))]
+    #[schema(value_type = Option<String>)]
+    pub topic_description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(length(max = 30, message = "must be at most 30 characters"))]
+    #[schema(value_type = Option<String>)]
+    pub format: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(length(max = 30, message = "must be at most 30 characters"))]
+    #[schema(value_type = Option<String>)]
+    pub duration: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(custom(function = "allowed_topic_level"))]
+    #[schema(value_type = Option<String>)]
+    pub topic_level: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(range(min = 0, message = "must not be negative"))]
+    #[schema(value_type = Option<i32>)]
+    pub capacity: Option<Option<i32>>,
+}
+
+fn allowed_topic_level(level: &str) -> Result<(), ValidationError> {
+    if !TOPIC_LEVELS.contains(&level) {
+        return Err(ValidationError::new("one_of")
+            .with_message(format!("must be one of {}", TOPIC_LEVELS.join(", ")).into()));
+    }
+    Ok(())
+}
+
+// Query parameters narrowing a topic listing; `created_after` is inclusive, `created_before` exclusive
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TopicFilter {
+    pub tutor_id: Option<i32>,
     pub topic_level: Option<String>,
+    pub format: Option<String>,
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+    /// Comma-separated columns from `TOPIC_SORT_COLUMNS`, `-` prefix for descending
+    pub sort: Option<String>,
+}
+
+impl TopicFilter {
+    pub fn sort_keys(&self, default: SortKey) -> Result<Vec<SortKey>, AppErrorType> {
+        parse_sort(self.sort.as_deref(), TOPIC_SORT_COLUMNS, default)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TopicSearchParams {
+    /// Web-search syntax: words, `"exact phrase"`, `-excluded`, `or`
+    pub q: String,
+}
+
+// A full-text match with `<b>`-highlighted title and description fragments
+#[derive(Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct TopicSearchHit {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub topic: Topic,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub snippet: Option<String>,
 }
 
 impl From<web::Json<CreateTopic>> for CreateTopic {
@@ -43,6 +167,28 @@ impl From<web::Json<CreateTopic>> for CreateTopic {
             format: ct.format.clone(),
             duration: ct.duration.clone(),
             topic_level: ct.topic_level.clone(),
+            capacity: ct.capacity,
+        }
+    }
+}
+
+impl TopicPatch {
+    pub fn apply(self, topic: &mut Topic) {
+        if let Some(title) = self.title {
+            topic.title = title;
+        }
+        for (field, value) in [
+            (&mut topic.topic_description, self.topic_description),
+            (&mut topic.format, self.format),
+            (&mut topic.duration, self.duration),
+            (&mut topic.topic_level, self.topic_level),
+        ] {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        if let Some(capacity) = self.capacity {
+            topic.capacity = capacity;
         }
     }
 }
@@ -55,6 +201,7 @@ impl From<web::Json<UpdateTopic>> for UpdateTopic {
             format: ut.format.clone(),
             duration: ut.duration.clone(),
             topic_level: ut.topic_level.clone(),
+            capacity: ut.capacity,
         }
     }
 }
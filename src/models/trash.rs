@@ -0,0 +1,45 @@
+use crate::models::tutor::{TopicRef, Tutor};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Everything waiting in the trash, oldest deletions first
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+pub struct Trash {
+    pub tutors: Vec<TrashedTutor>,
+    pub topics: Vec<TrashedTopic>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct TrashedTutor {
+    pub id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub deleted_at: NaiveDateTime,
+    // When the purge job will remove it for good
+    pub purge_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct TrashedTopic {
+    pub id: i32,
+    pub tutor_id: i32,
+    pub title: String,
+    pub deleted_at: NaiveDateTime,
+    pub purge_at: NaiveDateTime,
+}
+
+// A restored tutor, with the topics that were trashed along with them
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TutorRestoration {
+    pub tutor: Tutor,
+    pub topic_count: usize,
+    pub topics: Vec<TopicRef>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeSummary {
+    pub tutors: u64,
+    pub topics: u64,
+}
@@ -1,34 +1,152 @@
+use crate::errors::AppErrorType;
+use crate::models::page::{parse_sort, SortKey};
+use crate::models::patch::present;
+use crate::models::validation::not_blank;
 use actix_web::web;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+pub const TUTOR_SORT_COLUMNS: &[&str] = &["id", "first_name", "last_name", "email"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow, ToSchema)]
 pub struct Tutor {
     pub id: i32,
     pub first_name: String,
     pub last_name: String,
     pub email: String,
+    // Over the reviews of the tutor's topics that are not in the trash
+    pub average_rating: Option<f64>,
+    pub review_count: i32,
+    // Incremented by every update and served as the ETag
+    pub version: i32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
 pub struct CreateTutor {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
     pub first_name: String,
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
     pub last_name: String,
+    #[validate(
+        email(message = "must be a valid email address"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
     pub email: String,
+    #[serde(skip_serializing)]
+    #[schema(write_only)]
+    #[validate(length(min = 8, max = 128, message = "must be between 8 and 128 characters"))]
+    pub password: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// The full replacement sent with `PUT`
+#[derive(Serialize, Deserialize, Debug, Clone, Validate, ToSchema)]
 pub struct UpdateTutor {
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub first_name: String,
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub last_name: String,
+    #[validate(
+        email(message = "must be a valid email address"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
+    pub email: String,
+}
+
+// A JSON merge patch sent with `PATCH`: absent fields are kept. Every tutor column is
+// required, so `null` is rejected rather than clearing anything.
+#[derive(Deserialize, Debug, Clone, Default, Validate, ToSchema)]
+pub struct TutorPatch {
+    #[serde(default, deserialize_with = "present")]
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
     pub first_name: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(
+        custom(function = "not_blank"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
     pub last_name: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[validate(
+        email(message = "must be a valid email address"),
+        length(max = 200, message = "must be at most 200 characters")
+    )]
     pub email: Option<String>,
 }
 
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TutorFilter {
+    pub last_name_prefix: Option<String>,
+    /// Comma-separated columns from `TUTOR_SORT_COLUMNS`, `-` prefix for descending
+    pub sort: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteTutorParams {
+    /// Report what would be deleted without deleting anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// What a tutor delete removed, or with `dry_run` would have removed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct TutorDeletion {
+    pub tutor_id: i32,
+    pub dry_run: bool,
+    pub topic_count: usize,
+    pub topics: Vec<TopicRef>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct TopicRef {
+    pub id: i32,
+    pub title: String,
+}
+
+impl TutorFilter {
+    pub fn sort_keys(&self) -> Result<Vec<SortKey>, AppErrorType> {
+        parse_sort(self.sort.as_deref(), TUTOR_SORT_COLUMNS, SortKey::asc("id"))
+    }
+}
+
 impl From<web::Json<CreateTutor>> for CreateTutor {
     fn from(ct: web::Json<CreateTutor>) -> Self {
         CreateTutor {
             first_name: ct.first_name.clone(),
             last_name: ct.last_name.clone(),
             email: ct.email.clone(),
+            password: ct.password.clone(),
+        }
+    }
+}
+
+impl TutorPatch {
+    pub fn apply(self, tutor: &mut Tutor) {
+        if let Some(first_name) = self.first_name {
+            tutor.first_name = first_name;
+        }
+        if let Some(last_name) = self.last_name {
+            tutor.last_name = last_name;
+        }
+        if let Some(email) = self.email {
+            tutor.email = email;
         }
     }
 }
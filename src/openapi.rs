@@ -0,0 +1,263 @@
+use crate::errors::{AppErrorResponse, FieldError};
+use crate::handlers::{
+    auth, availability, calendar, catalogue, general, review, session, student, topic, trash, tutor,
+};
+use crate::models::auth::{LoginRequest, RefreshRequest, TokenResponse};
+use crate::models::availability::{
+    Availability, AvailabilityException, Booking, ExceptionWindows, NewBooking, Slot, TimeWindow,
+    WeeklyAvailability, WeeklyWindow,
+};
+use crate::models::catalogue::{
+    CatalogueFormat, CatalogueRecord, ImportMode, ImportOutcome, ImportReport, ImportRowReport,
+    RecordKind,
+};
+use crate::models::health::{
+    DatabaseHealth, HealthStatus, LivenessReport, PoolStats, ReadinessChecks, ReadinessReport,
+};
+use crate::models::page::{Page, StreamFormat};
+use crate::models::review::{Review, WriteReview};
+use crate::models::session::{Occurrence, Session, SessionSchedule};
+use crate::models::student::{
+    CreateStudent, EnrolledStudent, EnrolledTopic, Enrollment, NewEnrollment, RegisterStudent,
+    Roster, Student,
+};
+use crate::models::topic::{CreateTopic, Topic, TopicPatch, TopicSearchHit, UpdateTopic};
+use crate::models::trash::{Trash, TrashedTopic, TrashedTutor, TutorRestoration};
+use crate::models::tutor::{CreateTutor, TopicRef, Tutor, TutorDeletion, TutorPatch, UpdateTutor};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+// Served at /openapi.json; every route registered in routes.rs must be listed in `paths`
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Rust Tutors API"),
+    paths(
+        general::health_check_handler,
+        general::liveness_handler,
+        general::readiness_handler,
+        general::metrics_handler,
+        auth::login,
+        auth::student_login,
+        auth::refresh,
+        tutor::get_all_tutors,
+        tutor::post_new_tutor,
+        tutor::get_tutor_details,
+        tutor::update_tutor_details,
+        tutor::patch_tutor,
+        tutor::delete_tutor,
+        topic::get_all_topics,
+        topic::get_topics_for_tutor,
+        topic::search_topics,
+        topic::get_topic_details,
+        topic::post_new_topic,
+        topic::update_topic_details,
+        topic::patch_topic,
+        topic::delete_topic,
+        trash::list_trash,
+        trash::restore_tutor,
+        trash::restore_topic,
+        catalogue::export_catalogue,
+        catalogue::import_catalogue,
+        student::get_all_students,
+        student::post_new_student,
+        student::register_student,
+        student::get_student_details,
+        student::delete_student,
+        student::get_topics_for_student,
+        student::enroll_student,
+        student::unenroll_student,
+        student::get_topic_roster,
+        session::get_sessions_for_topic,
+        session::post_new_session,
+        session::update_session,
+        session::delete_session,
+        session::get_occurrences_for_topic,
+        availability::get_availability,
+        availability::put_weekly_availability,
+        availability::put_availability_exception,
+        availability::delete_availability_exception,
+        availability::get_free_slots,
+        availability::get_bookings,
+        availability::book_slot,
+        availability::cancel_booking,
+        calendar::get_tutor_calendar,
+        calendar::get_topic_calendar,
+        review::get_reviews_for_topic,
+        review::put_review,
+        review::delete_review,
+        review::moderate_review,
+    ),
+    components(schemas(
+        Tutor,
+        CreateTutor,
+        UpdateTutor,
+        TutorPatch,
+        TutorDeletion,
+        TopicRef,
+        Topic,
+        CreateTopic,
+        UpdateTopic,
+        TopicPatch,
+        TopicSearchHit,
+        Trash,
+        TrashedTutor,
+        TrashedTopic,
+        StreamFormat,
+        CatalogueFormat,
+        CatalogueRecord,
+        RecordKind,
+        ImportMode,
+        ImportOutcome,
+        ImportReport,
+        ImportRowReport,
+        TutorRestoration,
+        Student,
+        CreateStudent,
+        RegisterStudent,
+        NewEnrollment,
+        Enrollment,
+        EnrolledStudent,
+        EnrolledTopic,
+        Roster,
+        Session,
+        SessionSchedule,
+        Occurrence,
+        Availability,
+        WeeklyAvailability,
+        WeeklyWindow,
+        TimeWindow,
+        ExceptionWindows,
+        AvailabilityException,
+        Slot,
+        NewBooking,
+        Booking,
+        Review,
+        WriteReview,
+        Page<Tutor>,
+        Page<Topic>,
+        Page<TopicSearchHit>,
+        Page<Student>,
+        Page<Review>,
+        Page<EnrolledStudent>,
+        Page<EnrolledTopic>,
+        LoginRequest,
+        RefreshRequest,
+        TokenResponse,
+        HealthStatus,
+        LivenessReport,
+        ReadinessReport,
+        ReadinessChecks,
+        DatabaseHealth,
+        PoolStats,
+        AppErrorResponse,
+        FieldError,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "tutors", description = "Tutor profiles"),
+        (name = "topics", description = "Topics taught by tutors"),
+        (name = "trash", description = "Soft-deleted tutors and topics"),
+        (name = "students", description = "Students and their enrollments in topics"),
+        (name = "sessions", description = "Scheduled sessions of topics and their occurrences"),
+        (name = "availability", description = "Tutor availability, free slots and bookings"),
+        (name = "calendar", description = "iCalendar feeds of tutors' and topics' sessions"),
+        (name = "reviews", description = "Students' ratings and reviews of topics"),
+        (name = "catalogue", description = "Bulk CSV and NDJSON export and import"),
+        (name = "auth", description = "Login and token refresh"),
+        (name = "general", description = "Service status"),
+    )
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn first_string_literal(source: &str) -> Option<&str> {
+        let start = source.find('"')? + 1;
+        let end = start + source[start..].find('"')?;
+        Some(&source[start..end])
+    }
+
+    // (method, path) for every `.route(...)` in routes.rs, prefixed with its function's `web::scope`
+    fn registered_routes() -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+        for function in include_str!("routes.rs").split("pub fn ").skip(1) {
+            let scope = function
+                .find("web::scope(")
+                .and_then(|start| first_string_literal(&function[start..]))
+                .unwrap_or_default();
+            let parts: Vec<&str> = function.split(".route(").collect();
+            for (before, route) in parts.iter().zip(&parts[1..]) {
+                // `web::resource("/path").route(web::post()...)` names the path before the route
+                let path = if route.starts_with("web::") {
+                    let resource = before.rfind("web::resource(").unwrap();
+                    first_string_literal(&before[resource..]).unwrap()
+                } else {
+                    first_string_literal(route).unwrap()
+                };
+                let method_start = route.find("web::").unwrap() + "web::".len();
+                let method_end = method_start + route[method_start..].find('(').unwrap();
+                routes.insert((
+                    route[method_start..method_end].to_string(),
+                    format!("{}{}", scope, path),
+                ));
+            }
+        }
+        routes
+    }
+
+    fn documented_routes() -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            for (method, operation) in [
+                ("get", &item.get),
+                ("post", &item.post),
+                ("put", &item.put),
+                ("patch", &item.patch),
+                ("delete", &item.delete),
+            ] {
+                if operation.is_some() {
+                    routes.insert((method.to_string(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let registered = registered_routes();
+        assert!(registered.contains(&("delete".to_string(), "/tutors/{tutor_id}".to_string())));
+
+        assert_eq!(registered, documented_routes());
+    }
+
+    #[test]
+    fn document_is_openapi_3_1_with_problem_schema() {
+        let json = ApiDoc::openapi().to_json().unwrap();
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert!(document["openapi"].as_str().unwrap().starts_with("3.1"));
+        assert!(document["components"]["schemas"]["AppErrorResponse"].is_object());
+        assert!(document["components"]["securitySchemes"]["bearer_auth"].is_object());
+    }
+}
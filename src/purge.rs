@@ -0,0 +1,70 @@
+use crate::dbaccess::TrashRepository;
+use crate::errors::AppErrorType;
+use crate::models::trash::PurgeSummary;
+use crate::settings::TrashSettings;
+use chrono::Utc;
+use std::sync::Arc;
+
+// Hard-deletes whatever has been in the trash for longer than `trash.retention_days`,
+// once at startup and then every `trash.purge_interval_secs`
+pub fn spawn(trash: Arc<dyn TrashRepository>, settings: TrashSettings) {
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(settings.purge_interval());
+        loop {
+            interval.tick().await;
+            match purge_expired(trash.as_ref(), settings.retention()).await {
+                Ok(summary) if summary == PurgeSummary::default() => {}
+                Ok(summary) => tracing::info!(
+                    tutors = summary.tutors,
+                    topics = summary.topics,
+                    "purged expired trash"
+                ),
+                Err(err) => tracing::error!(error = %err, "trash purge failed"),
+            }
+        }
+    });
+}
+
+async fn purge_expired(
+    trash: &dyn TrashRepository,
+    retention: chrono::Duration,
+) -> Result<PurgeSummary, AppErrorType> {
+    trash.purge_trash(Utc::now().naive_utc() - retention).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbaccess::memory::InMemoryRepository;
+    use crate::dbaccess::{TopicRepository, TutorRepository};
+
+    #[actix_rt::test]
+    async fn only_items_older_than_the_retention_are_purged() {
+        let repo = InMemoryRepository::with_seed_data().await;
+        repo.delete_topic(1, 1).await.unwrap();
+        repo.delete_tutor(3, false).await.unwrap();
+
+        let summary = purge_expired(&repo, chrono::Duration::days(30))
+            .await
+            .unwrap();
+        assert_eq!(summary, PurgeSummary::default());
+
+        // A negative retention puts every deletion past the cutoff
+        let summary = purge_expired(&repo, chrono::Duration::seconds(-1))
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            PurgeSummary {
+                tutors: 1,
+                topics: 3
+            }
+        );
+        assert!(repo
+            .list_trash(chrono::Duration::days(30))
+            .await
+            .unwrap()
+            .tutors
+            .is_empty());
+    }
+}
@@ -1,32 +1,257 @@
-use crate::handlers::{general::*, topic::*, tutor::*};
+use crate::auth::{Permission, RequirePermission};
+use crate::handlers::{
+    auth::*, availability::*, calendar::*, catalogue::*, general::*, review::*, session::*,
+    student::*, topic::*, trash::*, tutor::*,
+};
+use crate::openapi::ApiDoc;
 use actix_web::web;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 pub fn general_routes(cfg: &mut web::ServiceConfig) {
-    cfg.route("/health", web::get().to(health_check_handler));
+    cfg.route("/health", web::get().to(health_check_handler))
+        .route("/health/live", web::get().to(liveness_handler))
+        .route("/health/ready", web::get().to(readiness_handler))
+        .route("/metrics", web::get().to(metrics_handler));
+}
+
+// The OpenAPI document and a Swagger UI page for exploring it at /docs/
+pub fn docs_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", ApiDoc::openapi()));
+}
+
+pub fn auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/auth")
+            .route("/login", web::post().to(login))
+            .route("/student-login", web::post().to(student_login))
+            .route("/refresh", web::post().to(refresh)),
+    );
 }
 
 pub fn tutor_routes(cfg: &mut web::ServiceConfig) {
+    let read = RequirePermission(Permission::ReadCatalogue);
+    let update_tutor = RequirePermission(Permission::UpdateTutor);
+    let write_topic = RequirePermission(Permission::WriteTopic);
+    let manage_trash = RequirePermission(Permission::ManageTrash);
+    let manage_bookings = RequirePermission(Permission::ManageBookings);
+    let book_slots = RequirePermission(Permission::BookSlot);
+    let cancel_bookings = RequirePermission(Permission::CancelBooking);
+
+    // The fixed second segments must come before `/{tutor_id}/{topic_id}`
     cfg.service(
         web::scope("/tutors")
-            .route("/", web::get().to(get_all_tutors))
-            .route("/", web::post().to(post_new_tutor))
-            .route("/{tutor_id}", web::get().to(get_tutor_details))
-            .route("/{tutor_id}", web::put().to(update_tutor_details))
-            .route("/{tutor_id}", web::delete().to(delete_tutor))
-            .route("/{tutor_id}/topics", web::get().to(get_topics_for_tutor))
+            .route("/", web::get().to(get_all_tutors).wrap(read))
+            .route(
+                "/",
+                web::post()
+                    .to(post_new_tutor)
+                    .wrap(RequirePermission(Permission::RegisterTutor)),
+            )
+            .route("/{tutor_id}", web::get().to(get_tutor_details).wrap(read))
+            .route(
+                "/{tutor_id}",
+                web::put().to(update_tutor_details).wrap(update_tutor),
+            )
+            .route(
+                "/{tutor_id}",
+                web::patch().to(patch_tutor).wrap(update_tutor),
+            )
+            .route(
+                "/{tutor_id}",
+                web::delete()
+                    .to(delete_tutor)
+                    .wrap(RequirePermission(Permission::DeleteTutor)),
+            )
+            .route(
+                "/{tutor_id}/restore",
+                web::post().to(restore_tutor).wrap(manage_trash),
+            )
+            .route(
+                "/{tutor_id}/topics",
+                web::get().to(get_topics_for_tutor).wrap(read),
+            )
+            .route(
+                "/{tutor_id}/calendar.ics",
+                web::get().to(get_tutor_calendar).wrap(read),
+            )
+            .route(
+                "/{tutor_id}/availability",
+                web::get().to(get_availability).wrap(read),
+            )
+            .route(
+                "/{tutor_id}/availability",
+                web::put().to(put_weekly_availability).wrap(update_tutor),
+            )
+            .route(
+                "/{tutor_id}/availability/exceptions/{date}",
+                web::put().to(put_availability_exception).wrap(update_tutor),
+            )
+            .route(
+                "/{tutor_id}/availability/exceptions/{date}",
+                web::delete()
+                    .to(delete_availability_exception)
+                    .wrap(update_tutor),
+            )
+            .route(
+                "/{tutor_id}/slots",
+                web::get().to(get_free_slots).wrap(read),
+            )
+            .route(
+                "/{tutor_id}/bookings",
+                web::get().to(get_bookings).wrap(manage_bookings),
+            )
+            .route(
+                "/{tutor_id}/bookings",
+                web::post().to(book_slot).wrap(book_slots),
+            )
+            .route(
+                "/{tutor_id}/bookings/{booking_id}",
+                web::delete().to(cancel_booking).wrap(cancel_bookings),
+            )
+            .route(
+                "/{tutor_id}/{topic_id}",
+                web::put().to(update_topic_details).wrap(write_topic),
+            )
             .route(
                 "/{tutor_id}/{topic_id}",
-                web::put().to(update_topic_details),
+                web::patch().to(patch_topic).wrap(write_topic),
             )
-            .route("/{tutor_id}/{topic_id}", web::delete().to(delete_topic)),
+            .route(
+                "/{tutor_id}/{topic_id}",
+                web::delete().to(delete_topic).wrap(write_topic),
+            ),
     );
 }
 
 pub fn topic_routes(cfg: &mut web::ServiceConfig) {
+    let read = RequirePermission(Permission::ReadCatalogue);
+    let manage_students = RequirePermission(Permission::ManageStudents);
+    let write_topic = RequirePermission(Permission::WriteTopic);
+    let write_review = RequirePermission(Permission::WriteReview);
+    let moderate_reviews = RequirePermission(Permission::ModerateReviews);
+
     cfg.service(
         web::scope("/topics")
-            .route("/", web::post().to(post_new_topic))
-            .route("/", web::get().to(get_all_topics))
-            .route("/{topic_id}", web::get().to(get_topic_details)),
+            .route("/", web::post().to(post_new_topic).wrap(write_topic))
+            .route("/", web::get().to(get_all_topics).wrap(read))
+            .route("/search", web::get().to(search_topics).wrap(read))
+            .route("/{topic_id}", web::get().to(get_topic_details).wrap(read))
+            .route(
+                "/{topic_id}/restore",
+                web::post()
+                    .to(restore_topic)
+                    .wrap(RequirePermission(Permission::ManageTrash)),
+            )
+            .route(
+                "/{topic_id}/enrollments",
+                web::post().to(enroll_student).wrap(manage_students),
+            )
+            .route(
+                "/{topic_id}/enrollments/{student_id}",
+                web::delete().to(unenroll_student).wrap(manage_students),
+            )
+            .route(
+                "/{topic_id}/students",
+                web::get()
+                    .to(get_topic_roster)
+                    .wrap(RequirePermission(Permission::ReadStudents)),
+            )
+            .route(
+                "/{topic_id}/sessions",
+                web::get().to(get_sessions_for_topic).wrap(read),
+            )
+            .route(
+                "/{topic_id}/sessions",
+                web::post().to(post_new_session).wrap(write_topic),
+            )
+            .route(
+                "/{topic_id}/sessions/{session_id}",
+                web::put().to(update_session).wrap(write_topic),
+            )
+            .route(
+                "/{topic_id}/sessions/{session_id}",
+                web::delete().to(delete_session).wrap(write_topic),
+            )
+            .route(
+                "/{topic_id}/occurrences",
+                web::get().to(get_occurrences_for_topic).wrap(read),
+            )
+            .route(
+                "/{topic_id}/calendar.ics",
+                web::get().to(get_topic_calendar).wrap(read),
+            )
+            .route(
+                "/{topic_id}/reviews",
+                web::get().to(get_reviews_for_topic).wrap(read),
+            )
+            .route(
+                "/{topic_id}/review",
+                web::put().to(put_review).wrap(write_review),
+            )
+            .route(
+                "/{topic_id}/review",
+                web::delete().to(delete_review).wrap(write_review),
+            )
+            .route(
+                "/{topic_id}/reviews/{student_id}",
+                web::delete().to(moderate_review).wrap(moderate_reviews),
+            ),
+    );
+}
+
+pub fn trash_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route(
+        "/trash",
+        web::get()
+            .to(list_trash)
+            .wrap(RequirePermission(Permission::ManageTrash)),
+    );
+}
+
+// A whole catalogue is far larger than any JSON body, so uploads have their own limit
+pub fn catalogue_routes(cfg: &mut web::ServiceConfig, import_limit_bytes: usize) {
+    let transfer = RequirePermission(Permission::TransferCatalogue);
+
+    cfg.route("/export", web::get().to(export_catalogue).wrap(transfer))
+        .service(
+            web::resource("/import")
+                .app_data(web::PayloadConfig::new(import_limit_bytes))
+                .route(web::post().to(import_catalogue).wrap(transfer)),
+        );
+}
+
+pub fn student_routes(cfg: &mut web::ServiceConfig) {
+    let read = RequirePermission(Permission::ReadStudents);
+
+    cfg.service(
+        web::scope("/students")
+            .route("/", web::get().to(get_all_students).wrap(read))
+            .route(
+                "/",
+                web::post()
+                    .to(post_new_student)
+                    .wrap(RequirePermission(Permission::ManageStudents)),
+            )
+            .route(
+                "/register",
+                web::post()
+                    .to(register_student)
+                    .wrap(RequirePermission(Permission::RegisterStudent)),
+            )
+            .route(
+                "/{student_id}",
+                web::get().to(get_student_details).wrap(read),
+            )
+            .route(
+                "/{student_id}",
+                web::delete()
+                    .to(delete_student)
+                    .wrap(RequirePermission(Permission::DeleteStudent)),
+            )
+            .route(
+                "/{student_id}/topics",
+                web::get().to(get_topics_for_student).wrap(read),
+            ),
     );
 }
@@ -0,0 +1,459 @@
+use config::{Config, Environment, File, FileFormat, Map};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+// Environment variables override the file as TUTORS_<SECTION>__<KEY>, e.g. TUTORS_SERVER__BIND
+pub const ENV_PREFIX: &str = "TUTORS";
+// Names a TOML file to load instead of the optional `tutors.toml` in the working directory
+pub const CONFIG_FILE_VAR: &str = "TUTORS_CONFIG";
+const DEFAULT_CONFIG_FILE: &str = "tutors.toml";
+
+// Variables the server read before settings existed, with the keys they now stand for.
+// The prefixed form wins when both are set.
+const LEGACY_VARS: &[(&str, &str)] = &[
+    ("SERVER_HOST_PORT", "SERVER__BIND"),
+    ("DATABASE_URL", "DATABASE__URL"),
+    ("STORAGE_BACKEND", "DATABASE__BACKEND"),
+    ("JWT_SECRET", "AUTH__JWT_SECRET"),
+];
+
+const LOG_LEVELS: &[&str] = &["trace", "debug", "info", "warn", "error"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub auth: AuthSettings,
+    pub log: LogSettings,
+    pub trash: TrashSettings,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub bind: String,
+    pub workers: usize,
+    pub request_timeout_secs: u64,
+    pub keep_alive_secs: u64,
+    pub shutdown_timeout_secs: u64,
+    pub json_limit_bytes: usize,
+    pub import_limit_bytes: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Postgres,
+    Memory,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    pub backend: StorageBackend,
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub ping_timeout_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AuthSettings {
+    pub jwt_secret: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LogSettings {
+    pub level: String,
+    pub format: LogFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    // Compact lines for a terminal
+    Human,
+    // One JSON object per line, for log shippers
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TrashSettings {
+    // How long soft-deleted tutors and topics can be restored before they are purged
+    pub retention_days: u32,
+    // How often the purge job looks for expired items
+    pub purge_interval_secs: u64,
+}
+
+// Everything wrong with the configuration, so it can be fixed in one go
+#[derive(Debug)]
+pub struct SettingsError(pub Vec<String>);
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            bind: "127.0.0.1:3000".into(),
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            request_timeout_secs: 5,
+            keep_alive_secs: 5,
+            shutdown_timeout_secs: 30,
+            json_limit_bytes: 256 * 1024,
+            import_limit_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        DatabaseSettings {
+            backend: StorageBackend::Postgres,
+            url: String::new(),
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_secs: 5,
+            ping_timeout_ms: 500,
+        }
+    }
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            level: "info".into(),
+            format: LogFormat::Human,
+        }
+    }
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        TrashSettings {
+            retention_days: 30,
+            purge_interval_secs: 3600,
+        }
+    }
+}
+
+impl Settings {
+    // Defaults, then the TOML file, then the environment
+    pub fn load() -> Result<Settings, SettingsError> {
+        let (file, env) = Settings::read_sources()?;
+        Settings::from_sources(file.as_deref(), env)
+    }
+
+    // Layered like `load`, but only the database section has to be valid. `migrate` needs
+    // nothing else, so it runs on a host that has only the database configured.
+    pub fn load_database() -> Result<DatabaseSettings, SettingsError> {
+        let (file, env) = Settings::read_sources()?;
+        Settings::database_from_sources(file.as_deref(), env)
+    }
+
+    fn read_sources() -> Result<(Option<String>, Map<String, String>), SettingsError> {
+        let env: Map<String, String> = std::env::vars().collect();
+        let (path, required) = match env.get(CONFIG_FILE_VAR) {
+            Some(path) => (path.clone(), true),
+            None => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+        let file = match std::fs::read_to_string(&path) {
+            Ok(contents) => Some(contents),
+            Err(_) if !required && !Path::new(&path).exists() => None,
+            Err(err) => {
+                return Err(SettingsError(vec![format!(
+                    "cannot read config file {}: {}",
+                    path, err
+                )]))
+            }
+        };
+        Ok((file, env))
+    }
+
+    fn from_sources(
+        toml: Option<&str>,
+        env: Map<String, String>,
+    ) -> Result<Settings, SettingsError> {
+        let settings = Settings::merge(toml, env)?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn database_from_sources(
+        toml: Option<&str>,
+        env: Map<String, String>,
+    ) -> Result<DatabaseSettings, SettingsError> {
+        let settings = Settings::merge(toml, env)?;
+        let mut problems = Vec::new();
+        settings.database.validate(&mut problems);
+        if problems.is_empty() {
+            Ok(settings.database)
+        } else {
+            Err(SettingsError(problems))
+        }
+    }
+
+    fn merge(toml: Option<&str>, mut env: Map<String, String>) -> Result<Settings, SettingsError> {
+        for (legacy, key) in LEGACY_VARS {
+            let prefixed = format!("{}_{}", ENV_PREFIX, key);
+            if let Some(value) = env.get(*legacy).cloned() {
+                env.entry(prefixed).or_insert(value);
+            }
+        }
+
+        let mut builder = Config::builder().add_source(
+            Config::try_from(&Settings::default())
+                .map_err(|err| SettingsError(vec![err.to_string()]))?,
+        );
+        if let Some(toml) = toml {
+            builder = builder.add_source(File::from_str(toml, FileFormat::Toml));
+        }
+        let settings: Settings = builder
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .prefix_separator("_")
+                    .separator("__")
+                    .source(Some(env)),
+            )
+            .build()
+            .and_then(Config::try_deserialize)
+            .map_err(|err| SettingsError(vec![err.to_string()]))?;
+
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), SettingsError> {
+        let mut problems = Vec::new();
+
+        let port = self.server.bind.rsplit_once(':').map(|(_host, port)| port);
+        if port.and_then(|port| port.parse::<u16>().ok()).is_none() {
+            problems.push(format!(
+                "server.bind must be a host:port address, got {:?}",
+                self.server.bind
+            ));
+        }
+        if self.server.workers == 0 {
+            problems.push("server.workers must be at least 1".to_string());
+        }
+        if self.server.request_timeout_secs == 0 {
+            problems.push("server.request_timeout_secs must be at least 1".to_string());
+        }
+        if self.server.json_limit_bytes == 0 {
+            problems.push("server.json_limit_bytes must be at least 1".to_string());
+        }
+        if self.server.import_limit_bytes == 0 {
+            problems.push("server.import_limit_bytes must be at least 1".to_string());
+        }
+
+        self.database.validate(&mut problems);
+
+        if self.auth.jwt_secret.is_empty() {
+            problems.push("auth.jwt_secret must be set".to_string());
+        }
+
+        if !LOG_LEVELS.contains(&self.log.level.to_lowercase().as_str()) {
+            problems.push(format!(
+                "log.level must be one of {}, got {:?}",
+                LOG_LEVELS.join(", "),
+                self.log.level
+            ));
+        }
+
+        if self.trash.retention_days == 0 {
+            problems.push("trash.retention_days must be at least 1".to_string());
+        }
+        if self.trash.purge_interval_secs == 0 {
+            problems.push("trash.purge_interval_secs must be at least 1".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError(problems))
+        }
+    }
+}
+
+impl ServerSettings {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    pub fn keep_alive(&self) -> Duration {
+        Duration::from_secs(self.keep_alive_secs)
+    }
+}
+
+impl DatabaseSettings {
+    fn validate(&self, problems: &mut Vec<String>) {
+        if self.backend == StorageBackend::Postgres && self.url.is_empty() {
+            problems.push("database.url must be set when database.backend is postgres".to_string());
+        }
+        if self.max_connections == 0 {
+            problems.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.min_connections > self.max_connections {
+            problems.push(format!(
+                "database.min_connections ({}) must not exceed database.max_connections ({})",
+                self.min_connections, self.max_connections
+            ));
+        }
+        if self.acquire_timeout_secs == 0 {
+            problems.push("database.acquire_timeout_secs must be at least 1".to_string());
+        }
+        if self.ping_timeout_ms == 0 {
+            problems.push("database.ping_timeout_ms must be at least 1".to_string());
+        }
+    }
+
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    pub fn ping_timeout(&self) -> Duration {
+        Duration::from_millis(self.ping_timeout_ms)
+    }
+}
+
+impl TrashSettings {
+    pub fn retention(&self) -> chrono::Duration {
+        chrono::Duration::days(self.retention_days.into())
+    }
+
+    pub fn purge_interval(&self) -> Duration {
+        Duration::from_secs(self.purge_interval_secs)
+    }
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Map<String, String> {
+        vars.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    const VALID_ENV: &[(&str, &str)] = &[
+        ("TUTORS_DATABASE__URL", "postgres://localhost/tutors"),
+        ("TUTORS_AUTH__JWT_SECRET", "a-secret-of-sufficient-length"),
+    ];
+
+    #[test]
+    fn environment_overrides_file_which_overrides_defaults() {
+        let toml = r#"
+            [server]
+            bind = "0.0.0.0:8080"
+            workers = 2
+
+            [database]
+            max_connections = 20
+        "#;
+        let settings = Settings::from_sources(
+            Some(toml),
+            env(&[
+                VALID_ENV,
+                &[
+                    ("TUTORS_SERVER__WORKERS", "6"),
+                    ("TUTORS_LOG__FORMAT", "json"),
+                ],
+            ]
+            .concat()),
+        )
+        .unwrap();
+
+        assert_eq!(settings.server.bind, "0.0.0.0:8080");
+        assert_eq!(settings.server.workers, 6);
+        assert_eq!(settings.database.max_connections, 20);
+        assert_eq!(settings.database.acquire_timeout_secs, 5);
+        assert_eq!(settings.log.level, "info");
+        assert_eq!(settings.log.format, LogFormat::Json);
+    }
+
+    #[test]
+    fn legacy_variables_are_honoured_below_prefixed_ones() {
+        let settings = Settings::from_sources(
+            None,
+            env(&[
+                ("SERVER_HOST_PORT", "127.0.0.1:4000"),
+                ("DATABASE_URL", "postgres://legacy/tutors"),
+                ("STORAGE_BACKEND", "memory"),
+                ("JWT_SECRET", "legacy-secret-that-is-long"),
+                ("TUTORS_DATABASE__URL", "postgres://prefixed/tutors"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(settings.server.bind, "127.0.0.1:4000");
+        assert_eq!(settings.database.url, "postgres://prefixed/tutors");
+        assert_eq!(settings.database.backend, StorageBackend::Memory);
+        assert_eq!(settings.auth.jwt_secret, "legacy-secret-that-is-long");
+    }
+
+    #[test]
+    fn migrations_need_only_the_database_settings() {
+        let database = Settings::database_from_sources(
+            None,
+            env(&[
+                ("DATABASE_URL", "postgres://localhost/tutors"),
+                ("TUTORS_SERVER__WORKERS", "0"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(database.url, "postgres://localhost/tutors");
+
+        let err = Settings::database_from_sources(None, env(&[])).unwrap_err();
+        assert_eq!(
+            err.0,
+            vec!["database.url must be set when database.backend is postgres"]
+        );
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let toml = r#"
+            [server]
+            bind = "localhost"
+            workers = 0
+
+            [database]
+            min_connections = 5
+            max_connections = 2
+
+            [log]
+            level = "loud"
+
+            [trash]
+            retention_days = 0
+        "#;
+        let err = Settings::from_sources(Some(toml), env(&[])).unwrap_err();
+
+        assert_eq!(
+            err.0,
+            vec![
+                "server.bind must be a host:port address, got \"localhost\"",
+                "server.workers must be at least 1",
+                "database.url must be set when database.backend is postgres",
+                "database.min_connections (5) must not exceed database.max_connections (2)",
+                "auth.jwt_secret must be set",
+                "log.level must be one of trace, debug, info, warn, error, got \"loud\"",
+                "trash.retention_days must be at least 1",
+            ]
+        );
+    }
+}
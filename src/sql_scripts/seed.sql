@@ -1,32 +1,17 @@
-DROP TABLE IF EXISTS tutor;
-DROP TABLE IF EXISTS topic;
-
-CREATE TABLE tutor (
-    id serial primary key,
-    first_name varchar(200) not null,
-    last_name varchar(200) not null,
-    email varchar(200) not null
-);
-
-CREATE TABLE topic
-(
-    id serial primary key,
-    tutor_id INT not null,
-    title varchar(140) not null,
-    topic_description varchar(2000),
-    format varchar(30),
-    duration varchar(30),
-    topic_level varchar(30),
-    created_at TIMESTAMP default now(),
-    updated_at TIMESTAMP default now(),
-    CONSTRAINT fk_tutor
-    FOREIGN KEY(tutor_id)
-        REFERENCES tutor(id)
-        ON DELETE cascade
-);
+/* Schema lives in migrations/; run `cargo run -- migrate up` before seeding */
+TRUNCATE TABLE review, booking, availability_exception, availability_window, availability, session_occurrence, session, enrollment, student, topic, tutor RESTART IDENTITY;
 
 GRANT all privileges ON TABLE tutor TO testuser;
 GRANT all privileges ON TABLE topic TO testuser;
+GRANT all privileges ON TABLE student TO testuser;
+GRANT all privileges ON TABLE enrollment TO testuser;
+GRANT all privileges ON TABLE session TO testuser;
+GRANT all privileges ON TABLE session_occurrence TO testuser;
+GRANT all privileges ON TABLE availability TO testuser;
+GRANT all privileges ON TABLE availability_window TO testuser;
+GRANT all privileges ON TABLE availability_exception TO testuser;
+GRANT all privileges ON TABLE booking TO testuser;
+GRANT all privileges ON TABLE review TO testuser;
 GRANT all privileges ON all sequences IN SCHEMA public TO testuser;
 
 SELECT setval('topic_id_seq', 1);
@@ -60,5 +45,23 @@ INSERT INTO topic
 VALUES(4, 3, 'Strings', 'video', '2022-09-22 05:45:00', '2023-10-12 05:45:00');
 
 
+INSERT INTO student(id, first_name, last_name, email)
+VALUES(1,'Alice','Nguyen','alice.nguyen@fakemail.com');
+
+INSERT INTO student(id, first_name, last_name, email)
+VALUES(2,'Raj','Patel','raj.patel@fakemail.com');
+
+
+INSERT INTO enrollment(topic_id, student_id, enrolled_at)
+VALUES(1, 1, '2023-12-01 09:00:00');
+
+
 SELECT setval('tutor_id_seq', (SELECT MAX(id) FROM tutor) + 1);
-SELECT setval('topic_id_seq', (SELECT MAX(id) FROM topic) + 1);
\ No newline at end of file
+SELECT setval('topic_id_seq', (SELECT MAX(id) FROM topic) + 1);
+SELECT setval('student_id_seq', (SELECT MAX(id) FROM student) + 1);
+
+-- Every seed tutor logs in with the password "tutor-password"
+UPDATE tutor SET password_hash = '$argon2id$v=19$m=19456,t=2,p=1$NEN/FmCnhzP4E+l8QS/3Ng$jaF7YvaF/92HiT0Ms5BT9hpp2faNr9+/YcId4q4k/rg';
+-- and every seed student with "student-password"
+UPDATE student SET password_hash = '$argon2id$v=19$m=19456,t=2,p=1$oCo6y+R6MSamRE3JCKVFuA$JWmob/22n9frB8auk9k7Ina1OIDbuOYcXLrvxuN1QXA';
+UPDATE topic SET search_vector = topic_search_vector(title, topic_description);
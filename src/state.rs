@@ -1,7 +1,59 @@
-use sqlx::postgres::PgPool;
-use std::sync::Mutex;
+use crate::auth::JwtKeys;
+use crate::dbaccess::{
+    AvailabilityRepository, CatalogueRepository, HealthRepository, ReviewRepository,
+    SessionRepository, StudentRepository, TopicRepository, TrashRepository, TutorRepository,
+};
+use crate::settings::Settings;
+use std::sync::Arc;
 
 pub struct AppState {
     pub health_check_response: String,
-    pub db: PgPool,
+    pub tutors: Arc<dyn TutorRepository>,
+    pub topics: Arc<dyn TopicRepository>,
+    pub trash: Arc<dyn TrashRepository>,
+    pub catalogue: Arc<dyn CatalogueRepository>,
+    pub students: Arc<dyn StudentRepository>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub availability: Arc<dyn AvailabilityRepository>,
+    pub reviews: Arc<dyn ReviewRepository>,
+    pub health: Arc<dyn HealthRepository>,
+    pub jwt: JwtKeys,
+    pub settings: Settings,
+}
+
+impl AppState {
+    // One backend serves every repository trait
+    pub fn with_repository<R>(
+        repo: Arc<R>,
+        health_check_response: String,
+        jwt: JwtKeys,
+        settings: Settings,
+    ) -> Self
+    where
+        R: TutorRepository
+            + TopicRepository
+            + TrashRepository
+            + CatalogueRepository
+            + StudentRepository
+            + SessionRepository
+            + AvailabilityRepository
+            + ReviewRepository
+            + HealthRepository
+            + 'static,
+    {
+        AppState {
+            health_check_response,
+            tutors: repo.clone(),
+            topics: repo.clone(),
+            trash: repo.clone(),
+            catalogue: repo.clone(),
+            students: repo.clone(),
+            sessions: repo.clone(),
+            availability: repo.clone(),
+            reviews: repo.clone(),
+            health: repo,
+            jwt,
+            settings,
+        }
+    }
 }
@@ -0,0 +1,96 @@
+use crate::dbaccess::RowStream;
+use crate::errors::AppErrorType;
+use crate::metrics::METRICS;
+use crate::models::page::StreamFormat;
+use actix_web::web::Bytes;
+use actix_web::HttpResponse;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+
+// Rows already read from the database go out together, so a fast query is not written one
+// row per chunk. Nothing is awaited to fill a batch, and the body is only polled when the
+// connection can take more, so a slow client holds back the query instead of filling memory.
+const MAX_BATCH_ROWS: usize = 64;
+
+// A chunked 200 with every row of `rows`. The status is sent before the first row is read,
+// so a database error part way through can only abort the connection; the client sees a
+// truncated body rather than a closing `]` or a final newline.
+pub fn streamed<T: Serialize + Send + 'static>(
+    format: StreamFormat,
+    rows: RowStream<T>,
+) -> HttpResponse {
+    let mut first = true;
+    let batches = rows.ready_chunks(MAX_BATCH_ROWS).map(move |batch| {
+        let mut chunk = Vec::new();
+        for row in batch {
+            let row = row.inspect_err(|err| {
+                METRICS.count_error(err);
+                tracing::error!(error.variant = err.variant(), error.code = err.code(), error = %err, "streamed response aborted");
+            })?;
+            match format {
+                StreamFormat::Json => {
+                    if !first {
+                        chunk.push(b',');
+                    }
+                    first = false;
+                    serde_json::to_writer(&mut chunk, &row)
+                }
+                StreamFormat::Ndjson => serde_json::to_writer(&mut chunk, &row)
+                    .map(|()| chunk.push(b'\n')),
+            }
+            .map_err(|err| AppErrorType::ActixError(err.to_string()))?;
+        }
+        Ok::<_, AppErrorType>(Bytes::from(chunk))
+    });
+    let body = match format {
+        StreamFormat::Json => stream::once(async { Ok(Bytes::from_static(b"[")) })
+            .chain(batches)
+            .chain(stream::once(async { Ok(Bytes::from_static(b"]")) }))
+            .boxed(),
+        StreamFormat::Ndjson => batches.boxed(),
+    };
+
+    HttpResponse::Ok()
+        .content_type(match format {
+            StreamFormat::Json => "application/json",
+            StreamFormat::Ndjson => "application/x-ndjson",
+        })
+        .streaming(body.map(|chunk| chunk.map_err(actix_web::Error::from)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    async fn body_of(format: StreamFormat, rows: Vec<i32>) -> String {
+        let rows = stream::iter(rows.into_iter().map(Ok)).boxed();
+        let resp = streamed(format, rows);
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn rows_are_written_as_an_array_or_as_lines() {
+        assert_eq!(body_of(StreamFormat::Json, vec![1, 2, 3]).await, "[1,2,3]");
+        assert_eq!(body_of(StreamFormat::Json, vec![]).await, "[]");
+        assert_eq!(
+            body_of(StreamFormat::Ndjson, vec![1, 2, 3]).await,
+            "1\n2\n3\n"
+        );
+        assert_eq!(body_of(StreamFormat::Ndjson, vec![]).await, "");
+
+        // Larger than one batch, so the separator carries across chunks
+        let rows: Vec<i32> = (0..200).collect();
+        let body: Vec<i32> =
+            serde_json::from_str(&body_of(StreamFormat::Json, rows.clone()).await).unwrap();
+        assert_eq!(body, rows);
+    }
+
+    #[actix_rt::test]
+    async fn an_error_aborts_the_body() {
+        let rows = stream::iter(vec![Ok(1), Err(AppErrorType::DbError("gone".into()))]).boxed();
+        let resp = streamed(StreamFormat::Json, rows);
+        assert!(to_bytes(resp.into_body()).await.is_err());
+    }
+}
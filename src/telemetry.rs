@@ -0,0 +1,117 @@
+use crate::settings::{LogFormat, LogSettings};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    HttpMessage,
+};
+use std::time::Instant;
+use tracing::{field::Empty, Instrument};
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Incoming ids longer than this are replaced rather than trusted
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// The id of the current request, as echoed in the `X-Request-Id` response header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+// Installs the global subscriber. `RUST_LOG`, when set, takes precedence over `log.level`
+// so individual modules can be turned up without a config change. Spans are logged as they
+// close, which is when the request status and `db.rows` fields have been filled in.
+pub fn init(settings: &LogSettings) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_err| EnvFilter::new(settings.level.to_lowercase()));
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+    match settings.format {
+        LogFormat::Human => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
+
+// Runs each request in a `request` span keyed by its request id. The caller's
+// `X-Request-Id` is kept so ids can be followed across services; otherwise one is generated.
+pub async fn request_span(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.path(),
+        status = Empty,
+        latency_ms = Empty,
+    );
+    let started = Instant::now();
+    let result = next.call(req).instrument(span.clone()).await;
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+
+    let mut res = result.inspect_err(|err| {
+        let status = err.as_response_error().status_code();
+        span.record("status", status.as_u16());
+        tracing::error!(parent: &span, error = %err, "request failed");
+    })?;
+    span.record("status", res.status().as_u16());
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{middleware, test, web, App, HttpResponse};
+
+    async fn echo_request_id(req: actix_web::HttpRequest) -> HttpResponse {
+        let request_id = req.extensions().get::<RequestId>().cloned().unwrap();
+        HttpResponse::Ok().body(request_id.0)
+    }
+
+    #[actix_rt::test]
+    async fn request_id_is_propagated_or_generated() {
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(request_span))
+                .route("/", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "upstream-id"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get(REQUEST_ID_HEADER).unwrap(),
+            "upstream-id"
+        );
+        assert_eq!(test::read_body(resp).await, "upstream-id");
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let resp = test::call_service(&app, req).await;
+        let generated = resp
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(Uuid::parse_str(generated).is_ok());
+    }
+}